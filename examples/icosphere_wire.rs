@@ -13,14 +13,18 @@ use nalgebra::*;
 fn main() { game().or_crash(); }
 fn game() -> EngineResult<()>
 {
-	let engine = EngineBuilder::<EmptyInput>::new("com.cterm2.interlude.examples.icosphere_wire", (0, 1, 0),
+	// runs as a golden-image test fixture when INTERLUDE_GOLDEN_DIR is set(line rasterization may differ slightly on edges)
+	let golden = GoldenImageTest::from_env("icosphere_wire").map(|t| t.tolerance(2, 16));
+	let builder = EngineBuilder::<EmptyInput>::new("com.cterm2.interlude.examples.icosphere_wire", (0, 1, 0),
 		"Rendering Icosphere[Wireframe]".into(), &Size2(640, 480))
-		.asset_base(std::env::current_dir().unwrap().into()).device_feature_nonsolid_fillmode().launch()?;
+		.asset_base(std::env::current_dir().unwrap().into()).device_feature_nonsolid_fillmode();
+	let engine = if golden.is_some() { builder.headless().preferred_adapter_type(AdapterType::Cpu) } else { builder }.launch()?;
 
 	// make framebuffer
-	let &Size2(w, h) = engine.render_window().size();
-	let vport = Viewport::from(engine.render_window().size());
-	let fb = engine.render_window().render_targets().iter().map(|v| Framebuffer::new_for_presented(&engine, v, Some(true), &Size2(w, h)))
+	let (targets, target_size) = (engine.render_targets().render_targets(), engine.render_targets().size());
+	let &Size2(w, h) = &target_size;
+	let vport = Viewport::from(&target_size);
+	let fb = targets.iter().map(|v| Framebuffer::new_for_presented(&engine, v, Some(true), &Size2(w, h)))
 		.collect::<Result<Vec<_>, _>>()?;
 
 	let (bp, stg, dev) =
@@ -82,7 +86,7 @@ fn game() -> EngineResult<()>
 	}.flip();
 	ImmediateTransferCommandSubmission::begin(&engine)?
 		.pipeline_barrier_on(PipelineStage::Transfer, false, &[], &initial_buffer_transfer_barriers,
-			&engine.render_window().render_targets().iter().map(|x| ImageMemoryBarrier::initialize_undef(x, ImageSubresourceRange
+			&targets.iter().map(|x| ImageMemoryBarrier::initialize_undef(x, ImageSubresourceRange
 			{
 				aspect: ImageAspect::Color.into(), .. Default::default()
 			}, AccessFlag::MemoryRead.into(), ImageLayout::PresentSrc)).collect::<Vec<_>>())
@@ -90,13 +94,13 @@ fn game() -> EngineResult<()>
 		.pipeline_barrier_on(PipelineStage::Transfer, false, &[], &[initial_buffer_transfer_ret], &[]);
 
 	// Draw commands and submit it
-	let cb = GraphicsCommandBuffers::new(&engine, engine.render_window().render_targets().len())?;
+	let cb = GraphicsCommandBuffers::new(&engine, targets.len())?;
 	for (n, recorder) in cb.begin_all().enumerate()
 	{
 		recorder?
 			.pipeline_barrier_on(PipelineStage::ColorAttachmentOutput, true, &[], &[], &[ImageMemoryBarrier
 			{
-				image: &engine.render_window().render_targets()[n],
+				image: &targets[n],
 				subresource_range: ImageSubresourceRange { aspect: ImageAspect::Color.into(), .. Default::default() },
 				src_access: AccessFlag::MemoryRead.into(), dst_access: AccessFlag::ColorAttachmentWrite.into(),
 				src_layout: ImageLayout::PresentSrc, dst_layout: ImageLayout::ColorAttachmentOptimal, .. Default::default()
@@ -112,6 +116,19 @@ fn game() -> EngineResult<()>
 		.end()?;
 	}
 
+	// Golden-image test: renders the first frame only
+	if let Some(test) = golden
+	{
+		let ordersem = QueueFence::new(&engine)?;
+		let index = engine.render_targets().acquire_next_target_index(&ordersem)?.0 as usize;
+		engine.submit_graphics_commands(&[cb[index]], &[(&ordersem, &PipelineStage::ColorAttachmentOutput)], None, None)?;
+		engine.wait_device()?;
+		let captured = CapturedImage::from_render_target(&engine, &targets[index], &target_size,
+			ImageLayout::PresentSrc)?;
+		test.verify(&captured)?.check()?;
+		return Ok(());
+	}
+
 	// Update commands
 	let updated_matrix_start = bp.offset(0) + size_of::<CMatrix4>();
 	let ucb = TransferCommandBuffers::new(&engine, 1)?;
@@ -138,7 +155,7 @@ fn game() -> EngineResult<()>
 
 	// Process Loop(Saving CPU usage)
 	{
-		let window_system = engine.render_window().clone();
+		let window_system = engine.render_window().cloned().ok_or(EngineError::GenericError("The render loop requires a window"))?;
 		let ordersem = QueueFence::new(&engine)?;
 		let (copy_completion, render_completion) = (Fence::new(&engine)?, Fence::new(&engine)?);
 		let exit_signal = Arc::new(AtomicBool::new(false));
@@ -147,7 +164,7 @@ fn game() -> EngineResult<()>
 		let update_event_uo = update_event.clone();
 		let update_observer = unsafe { thread_scoped::scoped(move ||
 		{
-			let mut frame_index = engine.render_window().acquire_next_target_index(&ordersem).and_then(|f|
+			let mut frame_index = engine.render_targets().acquire_next_target_index(&ordersem).and_then(|(f, _)|
 				engine.submit_graphics_commands(&[cb[f as usize]], &[(&ordersem, &PipelineStage::ColorAttachmentOutput)],
 					None, Some(&render_completion)).map(|_| f)
 				).or_crash();
@@ -157,8 +174,8 @@ fn game() -> EngineResult<()>
 				engine.submit_transfer_commands(&ucb[..], &[], None, Some(&copy_completion)).or_crash();
 				copy_completion.wait(None).and_then(|()| copy_completion.clear()).or_crash();
 				update_event_uo.set();
-				frame_index = engine.render_window().present(&engine, frame_index, None).and_then(|_|
-					engine.render_window().acquire_next_target_index(&ordersem).and_then(|f|
+				frame_index = engine.render_targets().present(&engine, frame_index, None).and_then(|_|
+					engine.render_targets().acquire_next_target_index(&ordersem).and_then(|(f, _)|
 						engine.submit_graphics_commands(&[cb[f as usize]], &[(&ordersem, &PipelineStage::ColorAttachmentOutput)],
 							None, Some(&render_completion)).map(|_| f)
 						)
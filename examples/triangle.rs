@@ -11,8 +11,11 @@ fn game() -> EngineResult<()>
 {
 	let color_subres = ImageSubresourceRange { aspect: ImageAspect::Color.into(), .. Default::default() };
 
-	let engine = EngineBuilder::<EmptyInput>::new("com.cterm2.interlude.examples.triangle".into(), (0, 1, 0), "Rendering Triangle".into(), &Size2(640, 480))
-		.asset_base(std::env::current_dir().unwrap().into()).launch()?;
+	// runs as a golden-image test fixture when INTERLUDE_GOLDEN_DIR is set
+	let golden = GoldenImageTest::from_env("triangle");
+	let builder = EngineBuilder::<EmptyInput>::new("com.cterm2.interlude.examples.triangle".into(), (0, 1, 0), "Rendering Triangle".into(), &Size2(640, 480))
+		.asset_base(std::env::current_dir().unwrap().into());
+	let engine = if golden.is_some() { builder.headless().preferred_adapter_type(AdapterType::Cpu) } else { builder }.launch()?;
 
 	// make buffer and staging data
 	let bp = BufferPreallocator::new(&engine, &[BufferContent::Vertex(std::mem::size_of::<[[CVector4; 2]; 3]>())]);
@@ -27,8 +30,9 @@ fn game() -> EngineResult<()>
 	})?;
 
 	// make Framebuffer
-	let vport = Viewport::from(engine.render_window().size());
-	let fb = engine.render_window().render_targets().iter().map(|v| Framebuffer::new_for_presented(&engine, v, Some(true), engine.render_window().size()))
+	let (targets, target_size) = (engine.render_targets().render_targets(), engine.render_targets().size());
+	let vport = Viewport::from(&target_size);
+	let fb = targets.iter().map(|v| Framebuffer::new_for_presented(&engine, v, Some(true), &target_size))
 		.collect::<EngineResult<Vec<_>>>()?;
 
 	// load shaders and build pipeline state
@@ -53,7 +57,7 @@ fn game() -> EngineResult<()>
 		];
 		ImmediateCommands!(Transfer engine;
 		{
-			pipeline_barrier_on	PipelineStage::Transfer, false, &[], &bmbarriers, &engine.render_window().render_targets().iter()
+			pipeline_barrier_on	PipelineStage::Transfer, false, &[], &bmbarriers, &targets.iter()
 				.map(|x| ImageMemoryBarrier::initialize_undef(x, color_subres.clone(), AccessFlag::MemoryRead.into(), ImageLayout::PresentSrc))
 				.collect::<Vec<_>>();
 			copy_buffer			&stg, &dev, &[BufferCopyRegion(0, 0, bp.total_size())];
@@ -88,10 +92,10 @@ fn game() -> EngineResult<()>
 	// Draw commands and submit them
 	let ordersem = QueueFence::new(&engine)?;
 	let render_completion = QueueFence::new(&engine)?;
-	let index = engine.render_window().acquire_next_target_index(&ordersem)? as usize;
+	let index = engine.render_targets().acquire_next_target_index(&ordersem)?.0 as usize;
 	let rt_barrier = ImageMemoryBarrier
 	{
-		image: &engine.render_window().render_targets()[index], subresource_range: color_subres.clone(),
+		image: &targets[index], subresource_range: color_subres.clone(),
 		src_access: AccessFlag::MemoryRead.into(), dst_access: AccessFlag::ColorAttachmentWrite.into(),
 		src_layout: ImageLayout::PresentSrc, dst_layout: ImageLayout::ColorAttachmentOptimal, .. Default::default()
 	};
@@ -105,7 +109,15 @@ fn game() -> EngineResult<()>
 		draw				3, 1;
 		end_render_pass;
 	}).submit_opt(&[(&ordersem, &PipelineStage::ColorAttachmentOutput)], Some(&render_completion), None)?;
-	engine.render_window().present(&engine, index as _, Some(&render_completion))?;
+	engine.render_targets().present(&engine, index as _, Some(&render_completion))?;
+	if let Some(test) = golden
+	{
+		engine.wait_device()?;
+		let captured = CapturedImage::from_render_target(&engine, &targets[index], &target_size,
+			ImageLayout::PresentSrc)?;
+		test.verify(&captured)?.check()?;
+		return Ok(());
+	}
 
 	engine.process_all_messages();
 	engine.wait_device()
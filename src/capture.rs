@@ -0,0 +1,175 @@
+//! Interlude: Frame Capture(Reading back images into host memory, PNG/PPM encoding)
+
+use interlude_vk_defs::*;
+use {EngineResult, EngineError, GraphicsInterface, Size2, ImageResource, ImageView, StagingResource};
+use {Image2D, StagingBuffer, WindowRenderTargetView, ImageLayout, ImageSubresourceRange, ImageSubresourceLayers, ImageAspect, PipelineStage, AccessFlag};
+use {ImageMemoryBarrier, BufferMemoryBarrier, BufferImageCopyRegion, ImmediateGraphicsCommandSubmission};
+use {QueueSyncOperationCommandRecorder, PrimaryTransferCommandRecorder, ImmediateSubmissionCommands};
+use std::fs::File;
+use std::io::{Read, Write, BufWriter};
+use std::path::Path;
+use png;
+use png::HasParameters;
+
+/// Pixels read back from an image, in RGBA8(sRGB encoded)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedImage { pub size: Size2, pub pixels: Vec<u8> }
+impl CapturedImage
+{
+	/// Reads back the base level and layer of the color image.
+	/// The image is transitioned from `layout` into TransferSrcOptimal for copying, and back to `layout` after that
+	/// (images in Undefined or Preinitialized layouts stay in TransferSrcOptimal).
+	pub fn read(engine: &GraphicsInterface, image: &ImageResource, format: VkFormat, size: &Size2, layout: ImageLayout) -> EngineResult<Self>
+	{
+		let texel_size = texel_size(format).ok_or(EngineError::GenericError("Unsupported format for capturing"))?;
+		let &Size2(width, height) = size;
+		let buffer_size = width as usize * height as usize * texel_size;
+		let buffer = StagingBuffer::new(engine, buffer_size as _, VK_BUFFER_USAGE_TRANSFER_DST_BIT)?;
+
+		let subresource_range = ImageSubresourceRange { aspect: ImageAspect::Color.into(), .. Default::default() };
+		let into_transfer = ImageMemoryBarrier
+		{
+			src_access: AccessFlag::MemoryWrite.into(), dst_access: AccessFlag::TransferRead.into(),
+			src_layout: layout, dst_layout: ImageLayout::TransferSrcOptimal, image, subresource_range: subresource_range.clone(), .. Default::default()
+		};
+		let restore_layout = if layout == ImageLayout::Undefined || layout == ImageLayout::Preinitialized { ImageLayout::TransferSrcOptimal } else { layout };
+		let restore = ImageMemoryBarrier
+		{
+			src_access: AccessFlag::TransferRead.into(), dst_access: AccessFlag::MemoryRead.into(),
+			src_layout: ImageLayout::TransferSrcOptimal, dst_layout: restore_layout, image, subresource_range, .. Default::default()
+		};
+		let into_host = BufferMemoryBarrier
+		{
+			buffer: &buffer, range: 0 .. buffer_size, src_access: AccessFlag::TransferWrite.into(), dst_access: AccessFlag::HostRead.into(),
+			.. Default::default()
+		};
+		let region = BufferImageCopyRegion::packed(0, ImageSubresourceLayers { aspect: ImageAspect::Color.into(), .. Default::default() }, &size.clone().into());
+		ImmediateGraphicsCommandSubmission::begin(engine)?
+			.pipeline_barrier(PipelineStage::AllCommands, PipelineStage::Transfer, false, &[], &[], &[into_transfer])
+			.copy_image_to_buffer(image, &buffer, &[region])
+			.pipeline_barrier(PipelineStage::Transfer, PipelineStage::Host, false, &[], &[into_host], &[])
+			.pipeline_barrier(PipelineStage::Transfer, PipelineStage::AllCommands, false, &[], &[], &[restore])
+			.submit_opt(&[], None, None)?;
+
+		let mapped = buffer.map()?;
+		let pixels = to_rgba8(format, mapped.range_mut::<u8>(0, buffer_size));
+		Ok(CapturedImage { size: size.clone(), pixels })
+	}
+	/// Reads back an Image2D created with `format`
+	pub fn from_image2d(engine: &GraphicsInterface, image: &Image2D, format: VkFormat, layout: ImageLayout) -> EngineResult<Self>
+	{
+		Self::read(engine, image, format, image.size(), layout)
+	}
+	/// Reads back a render target of RenderWindow or OffscreenTargets(`size` is the size of the targets)
+	pub fn from_render_target(engine: &GraphicsInterface, target: &WindowRenderTargetView, size: &Size2, layout: ImageLayout) -> EngineResult<Self>
+	{
+		Self::read(engine, target, ImageView::format(target), size, layout)
+	}
+
+	/// RGBA of the pixel
+	pub fn pixel(&self, x: u32, y: u32) -> &[u8]
+	{
+		let offs = (y as usize * self.size.0 as usize + x as usize) * 4;
+		&self.pixels[offs .. offs + 4]
+	}
+
+	/// Loads an 8bit PNG image(palette and grayscale images are expanded into RGBA)
+	pub fn load_png<P: AsRef<Path>>(path: P) -> EngineResult<Self>
+	{
+		decode_png(File::open(path)?).map(|(size, pixels)| CapturedImage { size, pixels })
+	}
+	pub fn write_png<P: AsRef<Path>>(&self, path: P) -> EngineResult<()>
+	{
+		let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), self.size.0, self.size.1);
+		encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+		encoder.write_header()?.write_image_data(&self.pixels).map_err(From::from)
+	}
+	/// Binary PPM(P6). Alpha channel is dropped
+	pub fn write_ppm<P: AsRef<Path>>(&self, path: P) -> EngineResult<()>
+	{
+		let mut writer = BufWriter::new(File::create(path)?);
+		write!(writer, "P6\n{} {}\n255\n", self.size.0, self.size.1)?;
+		for p in self.pixels.chunks(4) { writer.write_all(&p[..3])?; }
+		writer.flush().map_err(From::from)
+	}
+}
+
+/// Decodes an 8bit PNG image into RGBA8(palette and grayscale images are expanded)
+pub(crate) fn decode_png<R: Read>(source: R) -> EngineResult<(Size2, Vec<u8>)>
+{
+	let mut decoder = png::Decoder::new(source);
+	decoder.set(png::Transformations::EXPAND);
+	let (info, mut reader) = decoder.read_info()?;
+	if info.bit_depth != png::BitDepth::Eight { return Err(EngineError::GenericError("Unsupported bit depth for loading PNG")); }
+	let mut buf = vec![0; info.buffer_size()];
+	reader.next_frame(&mut buf)?;
+	let pixels = match info.color_type
+	{
+		png::ColorType::RGBA => buf,
+		png::ColorType::RGB => buf.chunks(3).flat_map(|p| vec![p[0], p[1], p[2], 255]).collect(),
+		png::ColorType::GrayscaleAlpha => buf.chunks(2).flat_map(|p| vec![p[0], p[0], p[0], p[1]]).collect(),
+		png::ColorType::Grayscale => buf.iter().flat_map(|&v| vec![v, v, v, 255]).collect(),
+		png::ColorType::Indexed => return Err(EngineError::GenericError("Unexpanded indexed PNG"))
+	};
+	Ok((Size2(info.width, info.height), pixels))
+}
+
+fn texel_size(format: VkFormat) -> Option<usize>
+{
+	match format
+	{
+		VK_FORMAT_R8_UNORM | VK_FORMAT_R8_SRGB => Some(1),
+		VK_FORMAT_R8G8B8_UNORM | VK_FORMAT_R8G8B8_SRGB | VK_FORMAT_B8G8R8_UNORM | VK_FORMAT_B8G8R8_SRGB => Some(3),
+		VK_FORMAT_R8G8B8A8_UNORM | VK_FORMAT_R8G8B8A8_SRGB | VK_FORMAT_B8G8R8A8_UNORM | VK_FORMAT_B8G8R8A8_SRGB |
+		VK_FORMAT_A8B8G8R8_UNORM_PACK32 | VK_FORMAT_A8B8G8R8_SRGB_PACK32 => Some(4),
+		VK_FORMAT_R16G16B16A16_SFLOAT => Some(8),
+		VK_FORMAT_R32G32B32A32_SFLOAT => Some(16),
+		_ => None
+	}
+}
+/// 8bit formats are copied as is(UNORM values are assumed to be display-referred),
+/// floating-point formats are treated as linear and encoded into sRGB
+fn to_rgba8(format: VkFormat, src: &[u8]) -> Vec<u8>
+{
+	let mut dst = Vec::with_capacity(src.len() / texel_size(format).unwrap() * 4);
+	match format
+	{
+		VK_FORMAT_R8_UNORM | VK_FORMAT_R8_SRGB => for &v in src { dst.extend_from_slice(&[v, v, v, 255]); },
+		VK_FORMAT_R8G8B8_UNORM | VK_FORMAT_R8G8B8_SRGB => for p in src.chunks(3) { dst.extend_from_slice(&[p[0], p[1], p[2], 255]); },
+		VK_FORMAT_B8G8R8_UNORM | VK_FORMAT_B8G8R8_SRGB => for p in src.chunks(3) { dst.extend_from_slice(&[p[2], p[1], p[0], 255]); },
+		// A8B8G8R8 packed in little-endian has the same memory order as R8G8B8A8
+		VK_FORMAT_R8G8B8A8_UNORM | VK_FORMAT_R8G8B8A8_SRGB | VK_FORMAT_A8B8G8R8_UNORM_PACK32 | VK_FORMAT_A8B8G8R8_SRGB_PACK32 => dst.extend_from_slice(src),
+		VK_FORMAT_B8G8R8A8_UNORM | VK_FORMAT_B8G8R8A8_SRGB => for p in src.chunks(4) { dst.extend_from_slice(&[p[2], p[1], p[0], p[3]]); },
+		VK_FORMAT_R16G16B16A16_SFLOAT => for p in src.chunks(8)
+		{
+			let c = |n: usize| half_to_f32(p[n * 2] as u16 | (p[n * 2 + 1] as u16) << 8);
+			dst.extend_from_slice(&[linear_to_srgb8(c(0)), linear_to_srgb8(c(1)), linear_to_srgb8(c(2)), unorm8(c(3))]);
+		},
+		VK_FORMAT_R32G32B32A32_SFLOAT => for p in src.chunks(16)
+		{
+			let c = |n: usize| f32::from_bits(p[n * 4] as u32 | (p[n * 4 + 1] as u32) << 8 | (p[n * 4 + 2] as u32) << 16 | (p[n * 4 + 3] as u32) << 24);
+			dst.extend_from_slice(&[linear_to_srgb8(c(0)), linear_to_srgb8(c(1)), linear_to_srgb8(c(2)), unorm8(c(3))]);
+		},
+		_ => unreachable!()
+	}
+	dst
+}
+fn half_to_f32(h: u16) -> f32
+{
+	let (sign, exp, mant) = ((h as u32 & 0x8000) << 16, (h as u32 >> 10) & 0x1f, h as u32 & 0x3ff);
+	let bits = match exp
+	{
+		0 if mant == 0 => sign,
+		// subnormal: normalize the mantissa
+		0 => { let mut e = 113; let mut m = mant; while (m & 0x400) == 0 { m <<= 1; e -= 1; } sign | (e << 23) | ((m & 0x3ff) << 13) },
+		0x1f => sign | 0x7f80_0000 | (mant << 13),
+		_ => sign | ((exp + 112) << 23) | (mant << 13)
+	};
+	f32::from_bits(bits)
+}
+fn unorm8(v: f32) -> u8 { (v.max(0.0).min(1.0) * 255.0 + 0.5) as u8 }
+fn linear_to_srgb8(v: f32) -> u8
+{
+	let v = v.max(0.0).min(1.0);
+	unorm8(if v <= 0.0031308 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 })
+}
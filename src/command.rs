@@ -4,9 +4,10 @@ use interlude_vk_defs::*;
 use interlude_vk_funport::*;
 use device::Device;
 use {
-	ImageSubresourceLayers, EngineResult, ImageResource, BufferResource, Filter, AttachmentClearValue, GraphicsInterface,
-	Framebuffer, PipelineLayout, DescriptorSetArrayView, GraphicsPipeline, QueueFence, Fence, PreciseRenderPass,
-	ImageSubresourceRange, Size3, Offset3, UnrecoverableExt, AccessFlags, ImageLayout, PipelineStageFlag
+	ImageSubresourceLayers, EngineResult, EngineError, ImageResource, BufferResource, Filter, AttachmentClearValue, GraphicsInterface,
+	Framebuffer, PipelineLayout, DescriptorSetArrayView, GraphicsPipeline, ComputePipeline, QueueFence, Fence, PreciseRenderPass,
+	ImageSubresourceRange, Size3, Offset3, UnrecoverableExt, AccessFlags, ImageLayout, PipelineStageFlag,
+	Size2, ImageAspect, AccessFlag, PipelineStage, MipmapChain, Viewport, Rect2, StencilFace
 };
 use shading::ShaderStageSet;
 use std::ops::{Deref, Range};
@@ -14,6 +15,7 @@ use std::mem::{forget, size_of, uninitialized as reserved, zeroed, transmute};
 use std::ptr::null;
 use subsystem_layer::{NativeResultValueHandler, NativeHandleProvider};
 use std::rc::Rc;
+use std::marker::PhantomData;
 
 use subsystem_layer::NativeCommandPool;
 pub struct CommandPoolPair { persistent: Rc<NativeCommandPool>, transient: Rc<NativeCommandPool> }
@@ -158,6 +160,32 @@ impl<'a> Into<VkImageMemoryBarrier> for &'a ImageMemoryBarrier<'a>
 /// An element of Indirect Draw
 /// (vertex_count, instance_count, first_vertex, first_instance)
 pub struct IndirectCallParameter(pub u32, pub u32, pub u32, pub u32);
+/// An element of Indexed Indirect Draw
+/// (index_count, instance_count, first_index, vertex_offset, first_instance)
+pub struct IndexedIndirectCallParameter(pub u32, pub u32, pub u32, pub i32, pub u32);
+/// An element of Indirect Dispatch
+/// (group_count_x, group_count_y, group_count_z)
+pub struct DispatchIndirectParameter(pub u32, pub u32, pub u32);
+
+/// Element type of index buffers
+pub trait IndexType: Copy { const NATIVE: VkIndexType; }
+impl IndexType for u16 { const NATIVE: VkIndexType = VK_INDEX_TYPE_UINT16; }
+impl IndexType for u32 { const NATIVE: VkIndexType = VK_INDEX_TYPE_UINT32; }
+/// Range of a buffer holding indices of type `T`
+pub struct IndexBuffer<'a, T: IndexType> { buffer: &'a BufferResource, offset: usize, ty: PhantomData<T> }
+impl<'a, T: IndexType> IndexBuffer<'a, T>
+{
+	/// Fails if `offset` is not a multiple of the size of `T`
+	pub fn new(buffer: &'a BufferResource, offset: usize) -> EngineResult<Self>
+	{
+		if offset % size_of::<T>() != 0 { return Err(EngineError::GenericError("Misaligned offset of index buffer")); }
+		Ok(IndexBuffer { buffer, offset, ty: PhantomData })
+	}
+	pub fn buffer(&self) -> &'a BufferResource { self.buffer }
+	pub fn offset(&self) -> usize { self.offset }
+}
+impl<'a, T: IndexType> Clone for IndexBuffer<'a, T> { fn clone(&self) -> Self { IndexBuffer { buffer: self.buffer, offset: self.offset, ty: PhantomData } } }
+impl<'a, T: IndexType> Copy for IndexBuffer<'a, T> {}
 
 // Typedefs for BufferType and View //
 pub type GraphicsCommandBuffer = VkCommandBuffer;
@@ -521,11 +549,71 @@ pub trait DrawingCommandRecorder: CommandRecorder + Sized
 		unsafe { vkCmdBindVertexBuffers(self.buffer(), start_binding, buffer_native.len() as _, buffer_native.as_ptr(), offsets_native.as_ptr()) };
 		self
 	}
+	/// Requires `DynamicState::Viewport`
+	fn set_viewport(self, first: u32, viewports: &[Viewport]) -> Self
+	{
+		unsafe { vkCmdSetViewport(self.buffer(), first, viewports.len() as _, viewports.as_ptr() as *const VkViewport) };
+		self
+	}
+	/// Requires `DynamicState::Scissor`
+	fn set_scissor(self, first: u32, scissors: &[Rect2]) -> Self
+	{
+		unsafe { vkCmdSetScissor(self.buffer(), first, scissors.len() as _, scissors.as_ptr() as *const VkRect2D) };
+		self
+	}
+	/// Requires `DynamicState::LineWidth`
+	fn set_line_width(self, width: f32) -> Self
+	{
+		unsafe { vkCmdSetLineWidth(self.buffer(), width) };
+		self
+	}
+	/// Requires `DynamicState::DepthBias`
+	fn set_depth_bias(self, constant_factor: f32, clamp: f32, slope_factor: f32) -> Self
+	{
+		unsafe { vkCmdSetDepthBias(self.buffer(), constant_factor, clamp, slope_factor) };
+		self
+	}
+	/// Requires `DynamicState::BlendConstants`
+	fn set_blend_constants(self, constants: [f32; 4]) -> Self
+	{
+		unsafe { vkCmdSetBlendConstants(self.buffer(), constants) };
+		self
+	}
+	/// Requires `DynamicState::DepthBounds` and the depthBounds feature
+	fn set_depth_bounds(self, min: f32, max: f32) -> Self
+	{
+		unsafe { vkCmdSetDepthBounds(self.buffer(), min, max) };
+		self
+	}
+	/// Requires `DynamicState::StencilCompareMask`
+	fn set_stencil_compare_mask(self, face: StencilFace, mask: u32) -> Self
+	{
+		unsafe { vkCmdSetStencilCompareMask(self.buffer(), face as _, mask) };
+		self
+	}
+	/// Requires `DynamicState::StencilWriteMask`
+	fn set_stencil_write_mask(self, face: StencilFace, mask: u32) -> Self
+	{
+		unsafe { vkCmdSetStencilWriteMask(self.buffer(), face as _, mask) };
+		self
+	}
+	/// Requires `DynamicState::StencilReference`
+	fn set_stencil_reference(self, face: StencilFace, reference: u32) -> Self
+	{
+		unsafe { vkCmdSetStencilReference(self.buffer(), face as _, reference) };
+		self
+	}
+	/// Binds 16bit indices
 	fn bind_index_buffer(self, buffer: &BufferResource, offset: usize) -> Self
 	{
 		unsafe { vkCmdBindIndexBuffer(self.buffer(), transmute(buffer.internal()), offset as _, VK_INDEX_TYPE_UINT16) };
 		self
 	}
+	fn bind_typed_index_buffer<T: IndexType>(self, indices: IndexBuffer<T>) -> Self
+	{
+		unsafe { vkCmdBindIndexBuffer(self.buffer(), transmute(indices.buffer.internal()), indices.offset as _, T::NATIVE) };
+		self
+	}
 	
 	fn draw(self, vertex_count: u32, instance_count: u32) -> Self
 	{
@@ -542,6 +630,12 @@ pub trait DrawingCommandRecorder: CommandRecorder + Sized
 		unsafe { vkCmdDrawIndexed(self.buffer(), index_count, instance_count, 0, index_offset, 0) };
 		self
 	}
+	/// `vertex_offset` is added to each index before fetching vertices
+	fn draw_indexed_range(self, indices: Range<u32>, vertex_offset: i32, instances: Range<u32>) -> Self
+	{
+		unsafe { vkCmdDrawIndexed(self.buffer(), indices.len() as _, instances.len() as _, indices.start, vertex_offset, instances.start) };
+		self
+	}
 	fn draw_indirect(self, param_buffer: &BufferResource, param_offs: usize) -> Self
 	{
 		unsafe { vkCmdDrawIndirect(self.buffer(), transmute(param_buffer.internal()), param_offs as _, 1, 0) };
@@ -552,6 +646,44 @@ pub trait DrawingCommandRecorder: CommandRecorder + Sized
 		unsafe { vkCmdDrawIndirect(self.buffer(), transmute(param_buffer.internal()), param_offs as _, param_count, size_of::<VkDrawIndirectCommand>() as _) };
 		self
 	}
+	fn draw_indexed_indirect(self, param_buffer: &BufferResource, param_offs: usize) -> Self
+	{
+		unsafe { vkCmdDrawIndexedIndirect(self.buffer(), transmute(param_buffer.internal()), param_offs as _, 1, 0) };
+		self
+	}
+	fn draw_indexed_indirect_mult(self, param_buffer: &BufferResource, param_offs: usize, param_count: u32) -> Self
+	{
+		unsafe { vkCmdDrawIndexedIndirect(self.buffer(), transmute(param_buffer.internal()), param_offs as _, param_count, size_of::<VkDrawIndexedIndirectCommand>() as _) };
+		self
+	}
+}
+/// Provides how to record some compute dispatching commands
+pub trait ComputeCommandRecorder: CommandRecorder + Sized
+{
+	fn bind_compute_pipeline(self, pipeline: &ComputePipeline) -> Self
+	{
+		unsafe { vkCmdBindPipeline(self.buffer(), VK_PIPELINE_BIND_POINT_COMPUTE, pipeline.native()) }; self
+	}
+	fn bind_compute_descriptor_sets(self, layout: &PipelineLayout, sets: &DescriptorSetArrayView) -> Self
+	{
+		self.bind_compute_descriptor_sets_partial(layout, 0, sets)
+	}
+	fn bind_compute_descriptor_sets_partial(self, layout: &PipelineLayout, start_set: u32, sets: &DescriptorSetArrayView) -> Self
+	{
+		unsafe { vkCmdBindDescriptorSets(self.buffer(), VK_PIPELINE_BIND_POINT_COMPUTE, layout.native(), start_set, sets.len() as _, sets.as_ptr(), 0, null()) };
+		self
+	}
+
+	fn dispatch(self, group_count_x: u32, group_count_y: u32, group_count_z: u32) -> Self
+	{
+		unsafe { vkCmdDispatch(self.buffer(), group_count_x, group_count_y, group_count_z) };
+		self
+	}
+	fn dispatch_indirect(self, param_buffer: &BufferResource, param_offs: usize) -> Self
+	{
+		unsafe { vkCmdDispatchIndirect(self.buffer(), transmute(param_buffer.internal()), param_offs as _) };
+		self
+	}
 }
 pub trait QueueSyncOperationCommandRecorder : CommandRecorder + Sized
 {
@@ -616,6 +748,49 @@ pub trait PrimaryGraphicsCommandRecorder: DrawingCommandRecorder + Sized
 			regions_native.len() as _, regions_native.as_ptr(), filter as _) };
 		self
 	}
+	/// Fills levels after 0 of the chain by successive linear-filtered blits.
+	/// Level 0 must be in `base_layout`(e.g. TransferDstOptimal after uploading), and all levels are in `final_layout` after that.
+	fn generate_mipmaps(self, chain: &MipmapChain, base_layout: ImageLayout, final_layout: ImageLayout) -> Self
+		where Self: QueueSyncOperationCommandRecorder
+	{
+		let image = chain.image;
+		let color_levels = |levels: Range<u32>| ImageSubresourceRange
+		{
+			aspect: ImageAspect::Color.into(), mip_level_range: levels, array_range: 0 .. chain.layers
+		};
+		let color_layers = |mip_level| ImageSubresourceLayers { aspect: ImageAspect::Color.into(), array_range: 0 .. chain.layers, mip_level };
+		let mut r = self.pipeline_barrier(PipelineStage::AllCommands, PipelineStage::Transfer, false, &[], &[], &[
+			ImageMemoryBarrier
+			{
+				src_access: AccessFlag::MemoryWrite.into(), dst_access: AccessFlag::TransferRead.into(),
+				src_layout: base_layout, dst_layout: ImageLayout::TransferSrcOptimal, image, subresource_range: color_levels(0 .. 1), .. Default::default()
+			}
+		]);
+		let Size2(w, h) = image.size().clone();
+		for level in 1 .. chain.levels
+		{
+			let (sw, sh, dw, dh) = ((w >> (level - 1)).max(1), (h >> (level - 1)).max(1), (w >> level).max(1), (h >> level).max(1));
+			r = r.pipeline_barrier(PipelineStage::Top, PipelineStage::Transfer, false, &[], &[], &[
+				ImageMemoryBarrier::initialize_undef(image, color_levels(level .. level + 1), AccessFlag::TransferWrite.into(), ImageLayout::TransferDstOptimal)
+			]).blit_image(image, image, VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, &[ImageBlitRegion
+			{
+				src_layers: color_layers(level - 1), dst_layers: color_layers(level),
+				src_offsets: [Default::default(), VkOffset3D { x: sw as _, y: sh as _, z: 1 }],
+				dst_offsets: [Default::default(), VkOffset3D { x: dw as _, y: dh as _, z: 1 }]
+			}], Filter::Linear).pipeline_barrier_on(PipelineStage::Transfer, false, &[], &[], &[ImageMemoryBarrier
+			{
+				src_access: AccessFlag::TransferWrite.into(), dst_access: AccessFlag::TransferRead.into(),
+				src_layout: ImageLayout::TransferDstOptimal, dst_layout: ImageLayout::TransferSrcOptimal,
+				image, subresource_range: color_levels(level .. level + 1), .. Default::default()
+			}]);
+		}
+		r.pipeline_barrier(PipelineStage::Transfer, PipelineStage::AllCommands, false, &[], &[], &[ImageMemoryBarrier
+		{
+			src_access: AccessFlag::TransferRead.into(), dst_access: AccessFlag::MemoryRead.into(),
+			src_layout: ImageLayout::TransferSrcOptimal, dst_layout: final_layout, image, subresource_range: color_levels(0 .. chain.levels),
+			.. Default::default()
+		}])
+	}
 }
 pub trait PrimaryTransferCommandRecorder : CommandRecorder + Sized
 {
@@ -632,6 +807,22 @@ pub trait PrimaryTransferCommandRecorder : CommandRecorder + Sized
 			transmute(dst.internal()), VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, regions_native.len() as _, regions_native.as_ptr()) };
 		self
 	}
+	/// Copy buffer contents into the image in TransferDstOptimal layout
+	fn copy_buffer_to_image(self, src: &BufferResource, dst: &ImageResource, regions: &[BufferImageCopyRegion]) -> Self
+	{
+		let regions_native = regions.into_iter().map(Into::into).collect::<Vec<_>>();
+		unsafe { vkCmdCopyBufferToImage(self.buffer(), transmute(src.internal()), transmute(dst.internal()), VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+			regions_native.len() as _, regions_native.as_ptr()) };
+		self
+	}
+	/// Copy the image in TransferSrcOptimal layout into buffer
+	fn copy_image_to_buffer(self, src: &ImageResource, dst: &BufferResource, regions: &[BufferImageCopyRegion]) -> Self
+	{
+		let regions_native = regions.into_iter().map(Into::into).collect::<Vec<_>>();
+		unsafe { vkCmdCopyImageToBuffer(self.buffer(), transmute(src.internal()), VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL, transmute(dst.internal()),
+			regions_native.len() as _, regions_native.as_ptr()) };
+		self
+	}
 }
 pub trait ClosableCommandRecorder : CommandRecorder + Sized
 {
@@ -646,12 +837,16 @@ pub trait ImmediateSubmissionCommands<'a> : CommandRecorder + Sized
 impl<'a> DrawingCommandRecorder for GraphicsCommandRecorder<'a> {}
 impl<'a> PrimaryGraphicsCommandRecorder for GraphicsCommandRecorder<'a> {}
 impl<'a> QueueSyncOperationCommandRecorder for GraphicsCommandRecorder<'a> {}
+impl<'a> ComputeCommandRecorder for GraphicsCommandRecorder<'a> {}
+impl<'a> PrimaryTransferCommandRecorder for GraphicsCommandRecorder<'a> {}
 impl<'a> CommandInjection for GraphicsCommandRecorder<'a> {}
 impl<'a> ClosableCommandRecorder for GraphicsCommandRecorder<'a> {}
 
 impl<'a> DrawingCommandRecorder for ImmediateGraphicsCommandSubmission<'a> {}
 impl<'a> PrimaryGraphicsCommandRecorder for ImmediateGraphicsCommandSubmission<'a> {}
 impl<'a> QueueSyncOperationCommandRecorder for ImmediateGraphicsCommandSubmission<'a> {}
+impl<'a> ComputeCommandRecorder for ImmediateGraphicsCommandSubmission<'a> {}
+impl<'a> PrimaryTransferCommandRecorder for ImmediateGraphicsCommandSubmission<'a> {}
 impl<'a> CommandInjection for ImmediateGraphicsCommandSubmission<'a> {}
 
 impl<'a> DrawingCommandRecorder for BundleCommandRecorder<'a> {}
@@ -765,6 +960,45 @@ impl<'a> Into<VkImageCopy> for &'a ImageCopyRegion
 		}
 	}
 }
+/// Region for copying between buffer and image
+/// (`row_length` and `image_height` are in texels, zero means tightly packed according to `image_extent`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferImageCopyRegion
+{
+	pub buffer_offset: usize, pub row_length: u32, pub image_height: u32,
+	pub image_layers: ImageSubresourceLayers, pub image_offset: VkOffset3D, pub image_extent: VkExtent3D
+}
+impl Default for BufferImageCopyRegion
+{
+	/// Tightly packed, Entire Bits
+	fn default() -> Self
+	{
+		BufferImageCopyRegion
+		{
+			buffer_offset: 0, row_length: 0, image_height: 0,
+			image_layers: Default::default(), image_offset: Default::default(), image_extent: Default::default()
+		}
+	}
+}
+impl BufferImageCopyRegion
+{
+	/// Tightly packed data at `buffer_offset` for the whole extent of the subresource
+	pub fn packed(buffer_offset: usize, image_layers: ImageSubresourceLayers, size: &Size3) -> Self
+	{
+		BufferImageCopyRegion { buffer_offset, image_layers, image_extent: size.clone().into(), .. Default::default() }
+	}
+}
+impl<'a> Into<VkBufferImageCopy> for &'a BufferImageCopyRegion
+{
+	fn into(self) -> VkBufferImageCopy
+	{
+		VkBufferImageCopy
+		{
+			bufferOffset: self.buffer_offset as _, bufferRowLength: self.row_length, bufferImageHeight: self.image_height,
+			imageSubresource: (&self.image_layers).into(), imageOffset: self.image_offset.clone(), imageExtent: self.image_extent.clone()
+		}
+	}
+}
 #[derive(Debug, PartialEq, Eq)]
 pub struct ImageBlitRegion
 {
@@ -1,36 +1,87 @@
-// empty implementation for container-based automation(CI)
+//! Interlude: Offscreen render targets for container-based automation(CI)
 
-use std::hash::Hash;
-use std::marker::PhantomData;
-use {EngineResult, ApplicationState, Event, Size2, vk};
+use interlude_vk_defs::*;
+use interlude_vk_funport::*;
+use {EngineResult, GraphicsInterface, QueueFence, Size2, ImageResource};
+use {ImageDescriptor2, ImagePreallocator};
+use render_surface::{WindowRenderTargetView, SurfaceState, RenderTargetProvider, RenderTargets, RenderTargetImages};
+use subsystem_layer::{NativeHandleProvider, NativeResultValueHandler};
+use device::Device;
 use std::rc::Rc;
+use std::cell::Cell;
+use std::ptr::null;
+use std::mem::{transmute, uninitialized as reserved};
 
-// dummy
-pub struct NativeWindow {}
-pub struct NativeInput<N: Eq + Copy + Hash> { ph: PhantomData<N> }
-
-impl NativeWindow
+/// Ring of offscreen color images that stands in for the swapchain of RenderWindow in headless engines
+pub struct OffscreenTargets
 {
-	pub fn new(_: &Size2, _: &str, _: bool) -> EngineResult<Self>
-	{
-		Ok(NativeWindow {})
-	}
-	pub fn process_messages(&self) -> ApplicationState { ApplicationState::Exited }
-	pub fn process_all_messages(&self) {}
-	pub fn process_events_and_messages(&self, _: &[&Event]) -> ApplicationState
+	targets: Rc<RenderTargets>,
+	format: VkFormat, size: Size2, next_index: Cell<u32>, parent: Rc<Device>
+}
+impl OffscreenTargets
+{
+	pub(crate) fn new(g: &GraphicsInterface, size: &Size2, format: VkFormat, count: usize) -> EngineResult<Self>
 	{
-		ApplicationState::Exited
+		let desc = ImageDescriptor2::new(format, size.clone(),
+			VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT | VK_IMAGE_USAGE_TRANSFER_SRC_BIT | VK_IMAGE_USAGE_SAMPLED_BIT).device_local();
+		let (images, _) = ImagePreallocator::new(g, Vec::new(), vec![&desc; count], Vec::new()).instantiate()?;
+		let handles = images.dim2().iter().map(|res| unsafe { transmute(res.internal()) }).collect::<Vec<VkImage>>();
+		// the views are destroyed before the images, also on failures below
+		let mut targets = RenderTargets::new(g.device(), Vec::new(), RenderTargetImages::Offscreen(images));
+		for image in handles
+		{
+			let mut iv = unsafe { reserved() };
+			unsafe { vkCreateImageView(g.device().native(), &VkImageViewCreateInfo
+			{
+				image, subresourceRange: VkImageSubresourceRange { aspectMask: VK_IMAGE_ASPECT_COLOR_BIT, .. Default::default() },
+				format, viewType: VK_IMAGE_VIEW_TYPE_2D, components: Default::default(), .. Default::default()
+			}, null(), &mut iv) }.into_result()?;
+			targets.push_view(WindowRenderTargetView(image, iv, format));
+		}
+		info!(target: "Interlude::OffscreenTargets", "Created {} offscreen render targets: {:?}", count, size);
+
+		Ok(OffscreenTargets { targets: Rc::new(targets), format, size: size.clone(), next_index: Cell::new(0), parent: g.device().clone() })
 	}
-	pub fn is_vk_presentation_support(&self, _: &vk::PhysicalDevice, _: u32) -> bool
+
+	pub fn render_targets(&self) -> Rc<RenderTargets> { self.targets.clone() }
+	pub fn format(&self) -> VkFormat { self.format }
+	pub fn size(&self) -> Size2 { self.size.clone() }
+	/// Acquire next render target in the ring. `wait_semaphore` is signaled immediately, as a swapchain does.
+	pub fn acquire_next_target_index(&self, wait_semaphore: &QueueFence) -> EngineResult<(u32, SurfaceState)>
 	{
-		false
+		let index = self.next_index.get();
+		self.next_index.set((index + 1) % self.targets.len() as u32);
+		let sem = wait_semaphore.native();
+		unsafe { vkQueueSubmit(self.parent.graphics_queue, 1, &VkSubmitInfo
+		{
+			signalSemaphoreCount: 1, pSignalSemaphores: &sem, .. Default::default()
+		}, 0 as _) }.make_result((index, SurfaceState::Optimal))
 	}
-	pub fn make_vk_surface(&self, _: &Rc<vk::Instance>) -> EngineResult<vk::Surface>
+	/// "Present" a render target: only waits for `wait_semaphore` so that it can be reused.
+	pub fn present(&self, engine: &GraphicsInterface, _index: u32, wait_semaphore: Option<&QueueFence>) -> EngineResult<SurfaceState>
 	{
-		unreachable!("make_vk_surface is not provided for container-based automation");
+		if let Some(s) = wait_semaphore
+		{
+			let (sem, stage) = (s.native(), VK_PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT);
+			unsafe { vkQueueSubmit(engine.device().graphics_queue, 1, &VkSubmitInfo
+			{
+				waitSemaphoreCount: 1, pWaitSemaphores: &sem, pWaitDstStageMask: &stage, .. Default::default()
+			}, 0 as _) }.into_result()?;
+		}
+		Ok(SurfaceState::Optimal)
 	}
 }
-impl<N: Eq + Copy + Hash> NativeInput<N>
+impl RenderTargetProvider for OffscreenTargets
 {
-	pub fn new() -> EngineResult<Self> { Ok(NativeInput { ph: PhantomData }) }
+	fn render_targets(&self) -> Rc<RenderTargets> { OffscreenTargets::render_targets(self) }
+	fn format(&self) -> VkFormat { OffscreenTargets::format(self) }
+	fn size(&self) -> Size2 { OffscreenTargets::size(self) }
+	fn acquire_next_target_index(&self, wait_semaphore: &QueueFence) -> EngineResult<(u32, SurfaceState)>
+	{
+		OffscreenTargets::acquire_next_target_index(self, wait_semaphore)
+	}
+	fn present(&self, engine: &GraphicsInterface, index: u32, wait_semaphore: Option<&QueueFence>) -> EngineResult<SurfaceState>
+	{
+		OffscreenTargets::present(self, engine, index, wait_semaphore)
+	}
 }
@@ -320,7 +320,7 @@ pub fn format(f: &Format) -> EngineResult<VkFormat>
 		{
 			ASTCPixelPack::_4x4 => Ok(VK_FORMAT_ASTC_4x4_UNORM_BLOCK),
 			ASTCPixelPack::_5x4 => Ok(VK_FORMAT_ASTC_5x4_UNORM_BLOCK),
-			ASTCPixelPack::_5x5 => Ok(VK_FORMAT_ASTC_5x4_UNORM_BLOCK),
+			ASTCPixelPack::_5x5 => Ok(VK_FORMAT_ASTC_5x5_UNORM_BLOCK),
 			ASTCPixelPack::_6x5 => Ok(VK_FORMAT_ASTC_6x5_UNORM_BLOCK),
 			ASTCPixelPack::_6x6 => Ok(VK_FORMAT_ASTC_6x6_UNORM_BLOCK),
 			ASTCPixelPack::_8x5 => Ok(VK_FORMAT_ASTC_8x5_UNORM_BLOCK),
@@ -337,7 +337,7 @@ pub fn format(f: &Format) -> EngineResult<VkFormat>
 		{
 			ASTCPixelPack::_4x4 => Ok(VK_FORMAT_ASTC_4x4_SRGB_BLOCK),
 			ASTCPixelPack::_5x4 => Ok(VK_FORMAT_ASTC_5x4_SRGB_BLOCK),
-			ASTCPixelPack::_5x5 => Ok(VK_FORMAT_ASTC_5x4_SRGB_BLOCK),
+			ASTCPixelPack::_5x5 => Ok(VK_FORMAT_ASTC_5x5_SRGB_BLOCK),
 			ASTCPixelPack::_6x5 => Ok(VK_FORMAT_ASTC_6x5_SRGB_BLOCK),
 			ASTCPixelPack::_6x6 => Ok(VK_FORMAT_ASTC_6x6_SRGB_BLOCK),
 			ASTCPixelPack::_8x5 => Ok(VK_FORMAT_ASTC_8x5_SRGB_BLOCK),
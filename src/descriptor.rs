@@ -9,7 +9,7 @@ use shading::ShaderStageSet;
 use std::rc::Rc;
 use std::ptr::null;
 use std::mem::{transmute, uninitialized as reserved};
-use {ImageView, Sampler, BufferResource, EngineResult, GraphicsInterface};
+use {ImageView, Sampler, BufferResource, EngineResult, EngineError, GraphicsInterface};
 use subsystem_layer::{NativeHandleProvider, NativeResultValueHandler};
 
 #[derive(Clone, Debug, PartialEq, Eq, Copy)]
@@ -43,17 +43,41 @@ impl Descriptor
 }
 
 use subsystem_layer::NativeDescriptorSetLayout;
-pub struct DescriptorSetLayout(Rc<NativeDescriptorSetLayout>, Vec<Descriptor>);
+/// Descriptor bindings, and immutable samplers kept alive with the layout
+pub struct DescriptorSetLayout(Rc<NativeDescriptorSetLayout>, Vec<Descriptor>, Vec<Rc<Sampler>>);
 impl DescriptorSetLayout
 {
 	pub fn new<'a>(engine: &GraphicsInterface, bindings: Cow<'a, [Descriptor]>) -> EngineResult<Self>
 	{
-		let native_bindings = bindings.iter().enumerate().map(|(i, x)| x.into_binding(i as u32)).collect::<Vec<_>>();
+		Self::with_immutable_samplers(engine, bindings, &[])
+	}
+	/// Layout with samplers baked into CombinedSampler bindings: pairs of the binding index and samplers for each array element.
+	/// Samplers in `ImageInfo` are ignored when writing to those bindings
+	pub fn with_immutable_samplers<'a>(engine: &GraphicsInterface, bindings: Cow<'a, [Descriptor]>, immutable_samplers: &[(u32, &[Rc<Sampler>])])
+		-> EngineResult<Self>
+	{
+		let mut native_samplers = Vec::with_capacity(immutable_samplers.len());
+		for &(binding, samplers) in immutable_samplers
+		{
+			match bindings.get(binding as usize)
+			{
+				Some(&Descriptor::CombinedSampler(count, _)) if count as usize == samplers.len() => (),
+				_ => return Err(EngineError::GenericError("Immutable samplers must be given for each element of a CombinedSampler binding"))
+			}
+			native_samplers.push((binding, samplers.iter().map(|s| s.native()).collect::<Vec<_>>()));
+		}
+		let native_bindings = bindings.iter().enumerate().map(|(i, x)|
+		{
+			let mut b = x.into_binding(i as u32);
+			if let Some(&(_, ref s)) = native_samplers.iter().find(|&&(n, _)| n == i as u32) { b.pImmutableSamplers = s.as_ptr(); }
+			b
+		}).collect::<Vec<_>>();
+		let retained = immutable_samplers.iter().flat_map(|&(_, s)| s.iter().cloned()).collect();
 		let mut dsl = unsafe { reserved() };
 		unsafe { vkCreateDescriptorSetLayout(engine.device().native(), &VkDescriptorSetLayoutCreateInfo
 		{
 			bindingCount: native_bindings.len() as _, pBindings: native_bindings.as_ptr(), .. Default::default()
-		}, null(), &mut dsl) }.make_result_with(|| DescriptorSetLayout(Rc::new(NativeDescriptorSetLayout(dsl, engine.device().clone())), bindings.into_owned()))
+		}, null(), &mut dsl) }.make_result_with(|| DescriptorSetLayout(Rc::new(NativeDescriptorSetLayout(dsl, engine.device().clone())), bindings.into_owned(), retained))
 	}
 	pub fn descriptors(&self) -> &[Descriptor] { &self.1 }
 }
@@ -68,7 +92,7 @@ impl DescriptorSets
 	{
 		let (mut uniform_total, mut storage_total, mut combined_sampler_total, mut input_attachment_total) = (0, 0, 0, 0);
 		let mut dsls = Vec::with_capacity(layouts.len());
-		for &&DescriptorSetLayout(ref dsl, ref ds) in layouts
+		for &&DescriptorSetLayout(ref dsl, ref ds, _) in layouts
 		{
 			dsls.push(dsl.native());
 			for desc in ds
@@ -7,6 +7,7 @@ use subsystem_layer::{NativeResultValueHandler, NativeHandleProvider};
 use std::ptr::null;
 use std::mem::uninitialized as reserved;
 use std::cmp::min;
+use libc::c_char;
 
 pub struct Device
 {
@@ -16,7 +17,7 @@ pub struct Device
 }
 impl Device
 {
-	pub fn new(adapter: VkPhysicalDevice, features: &VkPhysicalDeviceFeatures,
+	pub fn new(adapter: VkPhysicalDevice, features: &VkPhysicalDeviceFeatures, enabled_layers: &[*const c_char], enabled_extensions: &[*const c_char],
 		graphics_qf: u32, transfer_qf: Option<u32>, qf_props: &VkQueueFamilyProperties) -> EngineResult<Self>
 	{
 		// Ready Parameters //
@@ -39,8 +40,6 @@ impl Device
 			}]
 		};
 		let transfer_qf = transfer_qf.unwrap_or(graphics_qf);
-		let enabled_layers = ["VK_LAYER_LUNARG_standard_validation\x00".as_ptr()];
-		let enabled_extensions = ["VK_KHR_swapchain\x00".as_ptr()];
 		let mut dev = unsafe { reserved() };
 		unsafe { vkCreateDevice(adapter, &VkDeviceCreateInfo
 		{
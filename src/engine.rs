@@ -3,12 +3,12 @@
 use interlude_vk_defs::*;
 use interlude_vk_funport::vkQueueSubmit;
 use subsystem_layer::{NativeResultValueHandler, NativeHandleProvider};
-use ginterface::DeviceFeatures;
-use wsi::NativeWindowBase;
+use ginterface::{DeviceFeatures, AdapterPreference, AdapterType, AdapterInfo, ValidationConfig};
+use wsi::{NativeWindowBase, PlatformWindowType};
 use {
 	log, EngineResult, Fence, QueueFence, GraphicsCommandBuffersView, TransferCommandBuffersView, GraphicsInterface,
 	RenderPass, AttachmentDesc, PassDesc, VertexAttribute, VertexBinding, PosUV, VertexShader, ApplicationState, Event,
-	RenderWindow, Size2, PipelineStageFlag, Format, PackedPixelOrder, FormatType
+	RenderWindow, Size2, PipelineStageFlag, Format, PackedPixelOrder, FormatType, OffscreenTargets, RenderTargetProvider
 };
 use std::sync::{Arc, RwLock};
 use std::path::{Path, PathBuf};
@@ -78,7 +78,8 @@ pub trait EngineCoreExports
 pub struct EngineBuilder<'p, InputNames: Eq + Copy + Ord>
 {
 	app_name: &'static str, app_version: u32, asset_base: Option<Cow<'p, Path>>, extra_features: DeviceFeatures,
-	caption: &'static str, size: Size2, resizable: bool, ph: PhantomData<InputNames>
+	caption: &'static str, size: Size2, resizable: bool, adapter: AdapterPreference, validation: ValidationConfig,
+	headless: bool, pipeline_cache_file: Option<PathBuf>, ph: PhantomData<InputNames>
 }
 impl<'p, InputNames: Eq + Copy + Ord> EngineBuilder<'p, InputNames>
 {
@@ -87,8 +88,8 @@ impl<'p, InputNames: Eq + Copy + Ord> EngineBuilder<'p, InputNames>
 		EngineBuilder
 		{
 			app_name, app_version: VK_MAKE_VERSION!(app_version.0, app_version.1, app_version.2),
-			caption, size: size.clone(), resizable: false,
-			asset_base: None, extra_features: DeviceFeatures::new(), ph: PhantomData
+			caption, size: size.clone(), resizable: false, adapter: AdapterPreference::Any, validation: ValidationConfig::default(), headless: false,
+			asset_base: None, extra_features: DeviceFeatures::new(), pipeline_cache_file: None, ph: PhantomData
 		}
 	}
 	pub fn asset_base(mut self, asset_base: Cow<'p, Path>) -> Self
@@ -101,6 +102,16 @@ impl<'p, InputNames: Eq + Copy + Ord> EngineBuilder<'p, InputNames>
 		self.extra_features.enable_block_texture_compression();
 		self
 	}
+	pub fn device_feature_etc2_texture_compression(mut self) -> Self
+	{
+		self.extra_features.enable_etc2_texture_compression();
+		self
+	}
+	pub fn device_feature_astc_ldr_texture_compression(mut self) -> Self
+	{
+		self.extra_features.enable_astc_ldr_texture_compression();
+		self
+	}
 	pub fn device_feature_nonsolid_fillmode(mut self) -> Self
 	{
 		self.extra_features.enable_nonsolid_fillmode();
@@ -111,6 +122,56 @@ impl<'p, InputNames: Eq + Copy + Ord> EngineBuilder<'p, InputNames>
 		self.resizable = true;
 		self
 	}
+	/// Runs without the window system: renders into a ring of offscreen images instead of a window, and no input system is created
+	pub fn headless(mut self) -> Self
+	{
+		self.headless = true;
+		self
+	}
+	/// Persists the pipeline cache in the file(relative paths are resolved from the asset directory).
+	/// The cache is loaded on launch if it was made by the same adapter and driver, and saved when the engine is dropped
+	pub fn pipeline_cache_file<P: Into<PathBuf>>(mut self, path: P) -> Self
+	{
+		self.pipeline_cache_file = Some(path.into());
+		self
+	}
+	/// Prefers the adapter of the type(falls back to any other suitable adapter)
+	pub fn preferred_adapter_type(mut self, t: AdapterType) -> Self
+	{
+		self.adapter = AdapterPreference::DeviceType(t);
+		self
+	}
+	/// Uses the first suitable adapter that satisfies the predicate
+	pub fn adapter_predicate<F: Fn(&AdapterInfo) -> bool + 'static>(mut self, predicate: F) -> Self
+	{
+		self.adapter = AdapterPreference::Predicate(Box::new(predicate));
+		self
+	}
+	/// Enables/Disables validation layers and debug reporting(default: enabled only in debug builds)
+	pub fn validation(mut self, enabled: bool) -> Self
+	{
+		self.validation.enabled = enabled;
+		self
+	}
+	/// Replaces the validation layers to be enabled
+	pub fn validation_layers(mut self, layers: &[&str]) -> Self
+	{
+		self.validation.layers = layers.iter().map(|&l| l.to_owned()).collect();
+		self
+	}
+	/// Severities to be reported: combination of VK_DEBUG_REPORT_*_BIT_EXT
+	pub fn debug_report_flags(mut self, flags: VkDebugReportFlagsEXT) -> Self
+	{
+		self.validation.report_flags = flags;
+		self
+	}
+	/// Routes debug report messages to the handler instead of the engine logger
+	pub fn debug_report_handler<F>(mut self, handler: F) -> Self
+		where F: Fn(VkDebugReportFlagsEXT, VkDebugReportObjectTypeEXT, i32, &str) + 'static
+	{
+		self.validation.handler = Some(Box::new(handler));
+		self
+	}
 
 	pub fn launch(self) -> EngineResult<Engine<InputNames>> { Engine::new(self) }
 }
@@ -134,8 +195,8 @@ pub struct EngineResources
 }
 pub struct Engine<InputNames: Eq + Copy + Ord>
 {
-	window: Rc<RenderWindow>, input_system: Arc<RwLock<Input<InputNames>>>, gi: GraphicsInterface,
-	asset_dir: PathBuf, common_resources: EngineResources
+	window: Option<Rc<RenderWindow>>, targets: Rc<RenderTargetProvider>, input_system: Option<Arc<RwLock<Input<InputNames>>>>,
+	gi: GraphicsInterface, asset_dir: PathBuf, common_resources: EngineResources, pipeline_cache_file: Option<PathBuf>
 }
 unsafe impl<InputNames: Eq + Copy + Ord> Send for Engine<InputNames> {}
 impl<InputNames: Eq + Copy + Ord> EngineCoreExports for Engine<InputNames>
@@ -143,35 +204,80 @@ impl<InputNames: Eq + Copy + Ord> EngineCoreExports for Engine<InputNames>
 	fn graphics(&self) -> &GraphicsInterface { &self.gi }
 }
 macro_rules! FunComposite1 { ($f: expr; $g: expr) => {|x| $f($g(x))} }
+/// Number of offscreen render targets in headless engines
+const OFFSCREEN_TARGET_COUNT: usize = 2;
 impl<InputNames: Eq + Copy + Ord> Engine<InputNames>
 {
 	pub fn new(info: EngineBuilder<InputNames>) -> EngineResult<Self>
 	{
 		EngineLogger::setup();
 
-		let EngineBuilder { app_name, app_version, extra_features, size, caption, resizable, asset_base, .. } = info;
-		let gi = GraphicsInterface::new(app_name, app_version, &extra_features)?;
-		let window = RenderWindow::new(&gi, &size, caption, resizable).map(Rc::new)?;
+		let EngineBuilder { app_name, app_version, extra_features, size, caption, resizable, asset_base, adapter, validation, headless, pipeline_cache_file, .. } = info;
+		let asset_dir = asset_base.map(Cow::into_owned).or_else(|| env::current_exe().unwrap().parent().map(Path::to_path_buf)).unwrap().join("assets");
+		let pipeline_cache_file = pipeline_cache_file.map(|p| asset_dir.join(p));
+		// failures are logged in merge_file; the engine works without the cache
+		let load_pipeline_cache = |gi: &GraphicsInterface| if let Some(ref p) = pipeline_cache_file
+		{
+			gi.pipeline_cache().merge_file(gi.adapter_info(), p).unwrap_or(false);
+		};
+		if headless
+		{
+			let gi = GraphicsInterface::new(app_name, app_version, &extra_features, validation, &adapter, None)?;
+			load_pipeline_cache(&gi);
+			let targets = OffscreenTargets::new(&gi, &size, VK_FORMAT_R8G8B8A8_SRGB, OFFSCREEN_TARGET_COUNT).map(Rc::new)?;
+			return Ok(Engine
+			{
+				window: None, targets, input_system: None, gi, common_resources: EngineResources::new(), asset_dir, pipeline_cache_file
+			});
+		}
+
+		let underlying = PlatformWindowType::new(&size, caption, resizable)?;
+		let gi = GraphicsInterface::new(app_name, app_version, &extra_features, validation, &adapter, Some(&|a, qf| underlying.can_vk_present(a, qf)))?;
+		load_pipeline_cache(&gi);
+		let window = RenderWindow::new(&gi, underlying, &size).map(Rc::new)?;
 		let input_system = Input::new().map(FunComposite1!(Arc::new; RwLock::new))?;
 
 		window.show(); window.flush();
 		Ok(Engine
 		{
-			window, input_system, gi, common_resources: EngineResources::new(),
-			asset_dir: asset_base.map(Cow::into_owned).or_else(|| env::current_exe().unwrap().parent().map(Path::to_path_buf)).unwrap().join("assets")
+			targets: window.clone(), window: Some(window), input_system: Some(input_system), gi, common_resources: EngineResources::new(), asset_dir,
+			pipeline_cache_file
 		})
 	}
 
-	pub fn render_window(&self) -> &Rc<RenderWindow> { &self.window }
+	/// None in headless mode
+	pub fn render_window(&self) -> Option<&Rc<RenderWindow>> { self.window.as_ref() }
+	/// Render targets of the window, or offscreen targets in headless mode
+	pub fn render_targets(&self) -> &Rc<RenderTargetProvider> { &self.targets }
+	pub fn is_headless(&self) -> bool { self.window.is_none() }
+	/// Saves the pipeline cache into the file specified by `EngineBuilder::pipeline_cache_file`(does nothing if not specified)
+	pub fn save_pipeline_cache(&self) -> EngineResult<()>
+	{
+		match self.pipeline_cache_file { Some(ref p) => self.gi.pipeline_cache().save(p), None => Ok(()) }
+	}
 }
-// For any WindowSystems
+impl<InputNames: Eq + Copy + Ord> Drop for Engine<InputNames>
+{
+	fn drop(&mut self)
+	{
+		if let Err(e) = self.save_pipeline_cache() { warn!(target: "Interlude::PipelineCache", "Failed to save the pipeline cache: {:?}", e); }
+	}
+}
+// For any WindowSystems(no messages are delivered in headless mode)
 impl<InputNames: Eq + Copy + Ord> Engine<InputNames>
 {
-	pub fn input_system_ref(&self) -> &Arc<RwLock<Input<InputNames>>> { &self.input_system }
+	/// None in headless mode
+	pub fn input_system_ref(&self) -> Option<&Arc<RwLock<Input<InputNames>>>> { self.input_system.as_ref() }
 
-	pub fn process_messages(&self) -> bool { self.window.process_messages() == ApplicationState::Continue }
-	pub fn process_all_messages(&self) { self.window.process_all_messages() }
-	pub fn process_events_and_messages(&self, events: &[&Event]) -> ApplicationState { self.window.process_events_and_messages(events) }
+	pub fn process_messages(&self) -> bool
+	{
+		self.window.as_ref().map(|w| w.process_messages() == ApplicationState::Continue).unwrap_or(false)
+	}
+	pub fn process_all_messages(&self) { if let Some(ref w) = self.window { w.process_all_messages() } }
+	pub fn process_events_and_messages(&self, events: &[&Event]) -> ApplicationState
+	{
+		self.window.as_ref().map(|w| w.process_events_and_messages(events)).unwrap_or(ApplicationState::Exited)
+	}
 }
 /// The Asset Provider that can parse AssetPath and provides some of pre-defined objects
 pub trait AssetProvider
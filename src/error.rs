@@ -4,6 +4,8 @@ use std;
 use interlude_vk_defs::*;
 use std::os::raw::*;
 #[cfg(feature = "debugprint")] use freetype_sys::*;
+use png;
+use json;
 use std::borrow::Cow;
 
 pub enum EngineError
@@ -11,7 +13,8 @@ pub enum EngineError
 	DeviceError(VkResult), IOError(std::io::Error),
 	XServerError(c_int), #[cfg(feature = "debugprint")] FreeTypeError(FT_Error),
 	GenericError(&'static str), Win32ErrorWith(&'static str, std::io::Error),
-	NullError(std::ffi::NulError), Utf8Error(std::str::Utf8Error),
+	NullError(std::ffi::NulError), Utf8Error(std::str::Utf8Error), PngEncodingError(png::EncodingError),
+	PngDecodingError(png::DecodingError), JsonError(json::Error),
 	// Specific Errors //
 	AllocateMemoryWithEmptyResources, InvalidFormatCombination
 }
@@ -36,6 +39,18 @@ impl From<std::str::Utf8Error> for EngineError
 {
 	fn from(u8e: std::str::Utf8Error) -> EngineError { EngineError::Utf8Error(u8e) }
 }
+impl From<png::EncodingError> for EngineError
+{
+	fn from(pe: png::EncodingError) -> EngineError { EngineError::PngEncodingError(pe) }
+}
+impl From<png::DecodingError> for EngineError
+{
+	fn from(pd: png::DecodingError) -> EngineError { EngineError::PngDecodingError(pd) }
+}
+impl From<json::Error> for EngineError
+{
+	fn from(je: json::Error) -> EngineError { EngineError::JsonError(je) }
+}
 impl std::fmt::Debug for EngineError
 {
 	fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error>
@@ -50,6 +65,9 @@ impl std::fmt::Debug for EngineError
 			&EngineError::GenericError(ref e) => write!(formatter, "GenericError: {}", e),
 			&EngineError::NullError(ref n) => write!(formatter, "NulError: {:?}", n),
 			&EngineError::Utf8Error(ref e) => write!(formatter, "Utf8Error: {:?}", e),
+			&EngineError::PngEncodingError(ref e) => write!(formatter, "PngEncodingError: {:?}", e),
+			&EngineError::PngDecodingError(ref e) => write!(formatter, "PngDecodingError: {:?}", e),
+			&EngineError::JsonError(ref e) => write!(formatter, "JsonError: {:?}", e),
 			&EngineError::AllocateMemoryWithEmptyResources => write!(formatter, "GenericError: Attempting to allocate device memory with empty resources"),
 			&EngineError::InvalidFormatCombination => write!(formatter, "GenericError: Invalid format combination")
 		}
@@ -66,6 +84,9 @@ pub fn crash(err: EngineError) -> !
 		EngineError::XServerError(_) => "XServer Communication Error",
 		#[cfg(feature = "debugprint")] EngineError::FreeTypeError(_) => "FreeType Internal Error",
 		EngineError::NullError(_) | EngineError::Utf8Error(_) => "Internal Error",
+		EngineError::PngEncodingError(_) => "Image Encoding Error",
+		EngineError::PngDecodingError(_) => "Image Decoding Error",
+		EngineError::JsonError(_) => "Parsing Error",
 		EngineError::GenericError(_) | EngineError::AllocateMemoryWithEmptyResources | EngineError::InvalidFormatCombination => "Generic Error"
 	})
 }
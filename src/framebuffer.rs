@@ -57,6 +57,16 @@ impl AttachmentDesc
 			.. Default::default()
 		}
 	}
+	/// For Depth Buffer: Layout Transition = Undefined -> DepthStencilAttachmentOptimal, ClearOnLoad(also stencil), DiscardStoredValue
+	pub fn depth_buffer(format: VkFormat) -> Self
+	{
+		AttachmentDesc
+		{
+			format: format, clear_on_load: Some(true), stencil_clear_on_load: Some(true),
+			initial_layout: VK_IMAGE_LAYOUT_UNDEFINED, final_layout: VK_IMAGE_LAYOUT_DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+			.. Default::default()
+		}
+	}
 }
 #[repr(C)] #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct AttachmentRef(VkAttachmentReference);
@@ -64,6 +74,10 @@ impl AttachmentRef
 {
 	/// Color Attachment
 	pub fn color(index: u32) -> Self { AttachmentRef(VkAttachmentReference { attachment: index, layout: VK_IMAGE_LAYOUT_COLOR_ATTACHMENT_OPTIMAL }) }
+	/// Depth/Stencil Attachment
+	pub fn depth_stencil(index: u32) -> Self { AttachmentRef(VkAttachmentReference { attachment: index, layout: VK_IMAGE_LAYOUT_DEPTH_STENCIL_ATTACHMENT_OPTIMAL }) }
+	/// Input Attachment(read in fragment shaders)
+	pub fn input(index: u32) -> Self { AttachmentRef(VkAttachmentReference { attachment: index, layout: VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL }) }
 }
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct PassDesc
@@ -182,7 +196,29 @@ impl<'a> Into<VkClearValue> for &'a AttachmentClearValue
 
 use subsystem_layer::{NativeRenderPass, NativeFramebuffer};
 /// RenderPass: Determines how pixels in attachments are used.
-#[derive(Clone)] pub struct RenderPass(Rc<NativeRenderPass>);
+#[derive(Clone)] pub struct RenderPass { internal: Rc<NativeRenderPass>, subpasses: Rc<Vec<SubpassAttachments>> }
+/// Kinds and sample counts of the attachments rendered in a subpass(used for validating pipelines)
+pub(crate) struct SubpassAttachments { pub color: bool, pub depth: bool, pub stencil: bool, pub samples: Vec<u32> }
+impl SubpassAttachments
+{
+	fn new(attachments: &[AttachmentDesc], pass: &PassDesc) -> Self
+	{
+		let color = pass.color_attachment_indices.iter().filter_map(|r| attachments.get(r.0.attachment as usize)).collect::<Vec<_>>();
+		let depth_stencil = pass.depth_stencil_attachment_index.as_ref().and_then(|r| attachments.get(r.0.attachment as usize));
+		let (depth, stencil) = match depth_stencil.map(|a| a.format)
+		{
+			Some(VK_FORMAT_S8_UINT) => (false, true),
+			Some(VK_FORMAT_D16_UNORM_S8_UINT) | Some(VK_FORMAT_D24_UNORM_S8_UINT) | Some(VK_FORMAT_D32_SFLOAT_S8_UINT) => (true, true),
+			Some(_) => (true, false),
+			None => (false, false)
+		};
+		SubpassAttachments
+		{
+			color: !color.is_empty(), depth, stencil,
+			samples: color.iter().chain(depth_stencil.iter()).map(|a| a.samples).collect()
+		}
+	}
+}
 /// Framebuffer: A set of Attachments(ImageViews) and RenderPass
 #[derive(Clone)] pub struct Framebuffer { internal: Rc<NativeFramebuffer>, mold_ref: RenderPass, area: VkExtent2D }
 /// A pair of RenderPass and subpass index
@@ -193,6 +229,7 @@ impl RenderPass
 	/// Creates a Render Pass
 	pub fn new(engine: &GraphicsInterface, attachments: &[AttachmentDesc], passes: &[PassDesc], deps: &[PassDependency]) -> EngineResult<Self>
 	{
+		let subpasses = passes.iter().map(|p| SubpassAttachments::new(attachments, p)).collect();
 		let attachments = attachments.into_iter().map(Into::into).collect::<Vec<_>>();
 		let passes = passes.into_iter().map(Into::into).collect::<Vec<_>>();
 		let deps = deps.into_iter().map(Into::into).collect::<Vec<_>>();
@@ -201,8 +238,9 @@ impl RenderPass
 		{
 			attachmentCount: attachments.len() as _, subpassCount: passes.len() as _, dependencyCount: deps.len() as _,
 			pAttachments: attachments.as_ptr(), pSubpasses: passes.as_ptr(), pDependencies: deps.as_ptr(), .. Default::default()
-		}, null(), &mut rp) }.make_result_with(|| RenderPass(Rc::new(NativeRenderPass(rp, engine.device().clone()))))
+		}, null(), &mut rp) }.make_result_with(|| RenderPass { internal: Rc::new(NativeRenderPass(rp, engine.device().clone())), subpasses: Rc::new(subpasses) })
 	}
+	pub(crate) fn subpass_attachments(&self, index: u32) -> Option<&SubpassAttachments> { self.subpasses.get(index as usize) }
 }
 impl Framebuffer
 {
@@ -234,7 +272,7 @@ impl Framebuffer
 	pub fn renderpass(&self) -> &RenderPass { &self.mold_ref }
 	pub fn area(&self) -> &VkExtent2D { &self.area }
 }
-impl NativeHandleProvider for RenderPass { type NativeT = VkRenderPass; fn native(&self) -> VkRenderPass { self.0.native() } }
+impl NativeHandleProvider for RenderPass { type NativeT = VkRenderPass; fn native(&self) -> VkRenderPass { self.internal.native() } }
 impl NativeHandleProvider for Framebuffer { type NativeT = VkFramebuffer; fn native(&self) -> VkFramebuffer { self.internal.native() } }
 
 /// Access Flags Mask
@@ -2,7 +2,7 @@
 
 use interlude_vk_defs::*;
 use interlude_vk_funport::*;
-use {EngineResult, EngineError, DescriptorSetWriteInfo, UnrecoverableExt};
+use {EngineResult, EngineError, DescriptorSetWriteInfo, UnrecoverableExt, Sampler, SamplerState, SampleCount};
 use libc::{c_void, c_char, size_t};
 use std::ffi::{CString, CStr};
 use std::ptr::{null, null_mut};
@@ -11,7 +11,13 @@ use subsystem_layer::{NativeInstance, NativeHandleProvider, NativeResultValueHan
 use command::CommandPool;
 use device::Device;
 use descriptor::IntoWriteDescriptorSetNativeStruct;
+use memory::{MemoryAllocator, MemoryHeapStatistics};
+use pipeline_cache::PipelineCache;
 use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 
 #[cfg(windows)]
 static PLATFORM_SURFACE_EXTENSION_NAME: &'static str = "VK_KHR_win32_surface\x00";
@@ -44,6 +50,16 @@ impl DeviceFeatures
 		self.0.textureCompressionBC = true as VkBool32;
 		self
 	}
+	pub fn enable_etc2_texture_compression(&mut self) -> &mut Self
+	{
+		self.0.textureCompressionETC2 = true as VkBool32;
+		self
+	}
+	pub fn enable_astc_ldr_texture_compression(&mut self) -> &mut Self
+	{
+		self.0.textureCompressionASTC_LDR = true as VkBool32;
+		self
+	}
 	pub fn enable_nonsolid_fillmode(&mut self) -> &mut Self
 	{
 		self.0.fillModeNonSolid = true as VkBool32;
@@ -55,23 +71,53 @@ impl DeviceFeatures
 pub enum MemoryIndexType { DeviceLocal, HostVisible }
 pub struct MemoryTypeIndices { device_local: u32, host_visible: u32 }
 
+/// Receives messages from the debug report extension: (flags, object type, message code, message)
+pub type DebugReportHandler = Box<Fn(VkDebugReportFlagsEXT, VkDebugReportObjectTypeEXT, i32, &str)>;
+/// Validation layers and debug reporting settings
+pub struct ValidationConfig
+{
+	/// Enables the validation layers and the debug report callback(defaults to enabled in debug builds)
+	pub enabled: bool,
+	/// Layers to be enabled. Layers not present in the system are skipped with warnings
+	pub layers: Vec<String>,
+	/// Severities to be reported(combination of VK_DEBUG_REPORT_*_BIT_EXT)
+	pub report_flags: VkDebugReportFlagsEXT,
+	/// Routes messages to this handler instead of the engine logger
+	pub handler: Option<DebugReportHandler>
+}
+impl Default for ValidationConfig
+{
+	fn default() -> Self
+	{
+		ValidationConfig
+		{
+			enabled: cfg!(debug_assertions), layers: vec!["VK_LAYER_LUNARG_standard_validation".to_owned()],
+			report_flags: VK_DEBUG_REPORT_ERROR_BIT_EXT | VK_DEBUG_REPORT_PERFORMANCE_WARNING_BIT_EXT | VK_DEBUG_REPORT_WARNING_BIT_EXT,
+			handler: None
+		}
+	}
+}
+
 pub struct DebugReportCallback
 {
 	obj: VkDebugReportCallbackEXT, parent: Rc<NativeInstance>,
-	destroy_fn: PFN_vkDestroyDebugReportCallbackEXT
+	destroy_fn: PFN_vkDestroyDebugReportCallbackEXT,
+	#[allow(dead_code)] handler: Option<Box<DebugReportHandler>>
 }
 impl DebugReportCallback
 {
-	fn new(instance: &Rc<NativeInstance>) -> EngineResult<Self>
+	fn new(instance: &Rc<NativeInstance>, flags: VkDebugReportFlagsEXT, handler: Option<DebugReportHandler>) -> EngineResult<Self>
 	{
 		let create_fn: PFN_vkCreateDebugReportCallbackEXT = unsafe { transmute(vkGetInstanceProcAddr(instance.native(), "vkCreateDebugReportCallbackEXT\x00".as_ptr() as _)) };
 		let destroy_fn = unsafe { transmute(vkGetInstanceProcAddr(instance.native(), "vkDestroyDebugReportCallbackEXT\x00".as_ptr() as _)) };
+		// double boxing: passes the handler as a thin pointer
+		let handler = handler.map(Box::new);
+		let user_data = handler.as_ref().map(|h| &**h as *const DebugReportHandler as *mut c_void).unwrap_or(null_mut());
 		let mut obj = unsafe { reserved() };
 		create_fn(instance.native(), &VkDebugReportCallbackCreateInfoEXT
 		{
-			flags: VK_DEBUG_REPORT_ERROR_BIT_EXT | VK_DEBUG_REPORT_PERFORMANCE_WARNING_BIT_EXT | VK_DEBUG_REPORT_WARNING_BIT_EXT,
-			pfnCallback: debug_callback, .. Default::default()
-		}, null(), &mut obj).make_result_with(|| DebugReportCallback { obj, destroy_fn, parent: instance.clone() })
+			flags, pfnCallback: debug_callback, pUserData: user_data, .. Default::default()
+		}, null(), &mut obj).make_result_with(|| DebugReportCallback { obj, destroy_fn, parent: instance.clone(), handler })
 	}
 }
 impl Drop for DebugReportCallback
@@ -79,6 +125,143 @@ impl Drop for DebugReportCallback
 	fn drop(&mut self) { (self.destroy_fn)(self.parent.native(), self.obj, null()); }
 }
 
+fn available_instance_layers() -> EngineResult<Vec<String>>
+{
+	let mut count = 0;
+	unsafe { vkEnumerateInstanceLayerProperties(&mut count, null_mut()) }.into_result()?;
+	let mut props = Vec::with_capacity(count as _); unsafe { props.set_len(count as _); }
+	unsafe { vkEnumerateInstanceLayerProperties(&mut count, props.as_mut_ptr()) }.into_result()?;
+	props.iter().map(|p: &VkLayerProperties| Ok(unsafe { CStr::from_ptr(p.layerName.as_ptr()) }.to_str()?.to_owned())).collect()
+}
+fn available_instance_extensions(layer: Option<&CStr>) -> EngineResult<Vec<String>>
+{
+	let layer = layer.map(CStr::as_ptr).unwrap_or(null());
+	let mut count = 0;
+	unsafe { vkEnumerateInstanceExtensionProperties(layer, &mut count, null_mut()) }.into_result()?;
+	let mut props = Vec::with_capacity(count as _); unsafe { props.set_len(count as _); }
+	unsafe { vkEnumerateInstanceExtensionProperties(layer, &mut count, props.as_mut_ptr()) }.into_result()?;
+	props.iter().map(|p: &VkExtensionProperties| Ok(unsafe { CStr::from_ptr(p.extensionName.as_ptr()) }.to_str()?.to_owned())).collect()
+}
+fn available_device_extensions(adapter: VkPhysicalDevice) -> EngineResult<Vec<String>>
+{
+	let mut count = 0;
+	unsafe { vkEnumerateDeviceExtensionProperties(adapter, null(), &mut count, null_mut()) }.into_result()?;
+	let mut props = Vec::with_capacity(count as _); unsafe { props.set_len(count as _); }
+	unsafe { vkEnumerateDeviceExtensionProperties(adapter, null(), &mut count, props.as_mut_ptr()) }.into_result()?;
+	props.iter().map(|p: &VkExtensionProperties| Ok(unsafe { CStr::from_ptr(p.extensionName.as_ptr()) }.to_str()?.to_owned())).collect()
+}
+/// Device extensions enabled whenever the adapter supports them
+const OPTIONAL_DEVICE_EXTENSIONS: &'static [&'static str] = &["VK_KHR_sampler_mirror_clamp_to_edge", "VK_KHR_maintenance1"];
+
+/// Environment variable that overrides the adapter selection: an index in enumeration order or a part of the adapter name
+pub const ADAPTER_OVERRIDE_ENV: &'static str = "INTERLUDE_ADAPTER";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdapterType { Other, IntegratedGpu, DiscreteGpu, VirtualGpu, Cpu }
+impl From<VkPhysicalDeviceType> for AdapterType
+{
+	fn from(t: VkPhysicalDeviceType) -> Self
+	{
+		match t
+		{
+			VK_PHYSICAL_DEVICE_TYPE_INTEGRATED_GPU => AdapterType::IntegratedGpu,
+			VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU => AdapterType::DiscreteGpu,
+			VK_PHYSICAL_DEVICE_TYPE_VIRTUAL_GPU => AdapterType::VirtualGpu,
+			VK_PHYSICAL_DEVICE_TYPE_CPU => AdapterType::Cpu,
+			_ => AdapterType::Other
+		}
+	}
+}
+/// Properties of an enumerated PhysicalDevice
+pub struct AdapterInfo
+{
+	handle: VkPhysicalDevice,
+	pub index: usize, pub name: String, pub device_type: AdapterType,
+	pub api_version: (u32, u32, u32), pub driver_version: u32, pub vendor_id: u32, pub device_id: u32, pub pipeline_cache_uuid: [u8; VK_UUID_SIZE],
+	pub limits: VkPhysicalDeviceLimits, pub queue_families: Vec<VkQueueFamilyProperties>,
+	/// Supported device features
+	pub features: VkPhysicalDeviceFeatures,
+	/// Supported device extensions
+	pub extensions: Vec<String>,
+	/// Whether each queue family can present to the window surface(all false without window)
+	pub presentation_support: Vec<bool>
+}
+impl AdapterInfo
+{
+	fn query(index: usize, handle: VkPhysicalDevice, presentation_check: Option<&Fn(VkPhysicalDevice, u32) -> bool>) -> EngineResult<Self>
+	{
+		let mut props: VkPhysicalDeviceProperties = unsafe { reserved() };
+		unsafe { vkGetPhysicalDeviceProperties(handle, &mut props) };
+		let mut qfcount = 0;
+		unsafe { vkGetPhysicalDeviceQueueFamilyProperties(handle, &mut qfcount, null_mut()) };
+		let mut queue_families = vec![unsafe { reserved() }; qfcount as _];
+		unsafe { vkGetPhysicalDeviceQueueFamilyProperties(handle, &mut qfcount, queue_families.as_mut_ptr()) };
+		let mut features = unsafe { reserved() };
+		unsafe { vkGetPhysicalDeviceFeatures(handle, &mut features) };
+		let presentation_support = (0 .. qfcount).map(|qf| presentation_check.map(|f| f(handle, qf)).unwrap_or(false)).collect();
+
+		Ok(AdapterInfo
+		{
+			handle, index, name: unsafe { CStr::from_ptr(props.deviceName.as_ptr()) }.to_str()?.to_owned(), device_type: props.deviceType.into(),
+			api_version: (VK_VERSION!(MAJOR props.apiVersion), VK_VERSION!(MINOR props.apiVersion), VK_VERSION!(PATCH props.apiVersion)),
+			driver_version: props.driverVersion, vendor_id: props.vendorID, device_id: props.deviceID, pipeline_cache_uuid: props.pipelineCacheUUID,
+			limits: props.limits, features, extensions: available_device_extensions(handle)?,
+			queue_families, presentation_support
+		})
+	}
+	pub fn native(&self) -> VkPhysicalDevice { self.handle }
+	/// Index of the first queue family that supports graphics operations
+	pub fn graphics_queue_family(&self) -> Option<u32>
+	{
+		self.queue_families.iter().position(|fp| (fp.queueFlags & VK_QUEUE_GRAPHICS_BIT) != 0).map(|i| i as u32)
+	}
+	/// Whether the graphics queue family can present to the window surface
+	pub fn surface_support(&self) -> bool
+	{
+		self.graphics_queue_family().map(|qf| self.presentation_support[qf as usize]).unwrap_or(false)
+	}
+}
+/// Policy for choosing a PhysicalDevice. The `INTERLUDE_ADAPTER` environment variable takes priority over this.
+pub enum AdapterPreference
+{
+	/// The first suitable adapter in enumeration order
+	Any,
+	/// The first suitable adapter of the type, or the first suitable adapter if none are of the type
+	DeviceType(AdapterType),
+	/// The first suitable adapter satisfying the predicate
+	Predicate(Box<Fn(&AdapterInfo) -> bool>)
+}
+impl AdapterPreference
+{
+	fn select(&self, adapters: &[AdapterInfo], requires_presentation: bool) -> EngineResult<usize>
+	{
+		let suitable = |a: &&AdapterInfo| a.graphics_queue_family().is_some() && (!requires_presentation || a.surface_support());
+		if let Ok(v) = env::var(ADAPTER_OVERRIDE_ENV)
+		{
+			let found = match v.parse::<usize>()
+			{
+				Ok(n) => adapters.get(n),
+				Err(_) => adapters.iter().find(|a| a.name.to_lowercase().contains(&v.to_lowercase()))
+			};
+			match found
+			{
+				Some(a) if suitable(&a) => { info!(target: "Interlude", "Adapter is overridden by {}={:?}", ADAPTER_OVERRIDE_ENV, v); return Ok(a.index); },
+				Some(a) => warn!(target: "Interlude", "Adapter {:?} specified by {}={:?} is not suitable, falling back to the default selection",
+					a.name, ADAPTER_OVERRIDE_ENV, v),
+				None => warn!(target: "Interlude", "No adapter matches {}={:?}, falling back to the default selection", ADAPTER_OVERRIDE_ENV, v)
+			}
+		}
+
+		let found = match *self
+		{
+			AdapterPreference::Any => adapters.iter().find(&suitable),
+			AdapterPreference::DeviceType(t) => adapters.iter().filter(&suitable).find(|a| a.device_type == t).or_else(|| adapters.iter().find(&suitable)),
+			AdapterPreference::Predicate(ref f) => adapters.iter().filter(&suitable).find(|a| f(a))
+		};
+		found.map(|a| a.index).ok_or(EngineError::GenericError("No suitable PhysicalDevice is found"))
+	}
+}
+
 macro_rules! LogAdapterFeature
 {
 	($f: expr => $($x: ident),*) => { $(info!(target: "Interlude::DiagAdapter", "-- {}: {:?}", stringify!($x), $f.$x);)* }
@@ -86,12 +269,17 @@ macro_rules! LogAdapterFeature
 
 pub struct GraphicsInterface
 {
-	instance: (Rc<NativeInstance>, DebugReportCallback), device: Rc<Device>, pools: CommandPool,
-	pub device_limits: VkPhysicalDeviceLimits, memory_types: MemoryTypeIndices
+	instance: (Rc<NativeInstance>, Option<DebugReportCallback>), device: Rc<Device>, pools: CommandPool,
+	pub device_limits: VkPhysicalDeviceLimits, memory_types: MemoryTypeIndices, memory_properties: VkPhysicalDeviceMemoryProperties,
+	allocator: MemoryAllocator, adapters: Vec<AdapterInfo>, adapter_index: usize, pipeline_cache: PipelineCache,
+	enabled_features: VkPhysicalDeviceFeatures, enabled_extensions: Vec<String>, sampler_cache: RefCell<HashMap<SamplerState, Rc<Sampler>>>
 }
 impl GraphicsInterface
 {
-	pub fn new(app_name: &str, app_version: u32, device_features: &DeviceFeatures) -> EngineResult<Self>
+	/// `presentation_check` tells whether a queue family of the adapter can present to the window; adapters that cannot are skipped.
+	/// Window system extensions are not enabled without it(headless).
+	pub fn new(app_name: &str, app_version: u32, device_features: &DeviceFeatures, validation: ValidationConfig,
+		adapter_preference: &AdapterPreference, presentation_check: Option<&Fn(VkPhysicalDevice, u32) -> bool>) -> EngineResult<Self>
 	{
 		info!("=== Enumerating Instance Extensions... ===");
 		let mut ext_count = 0;
@@ -103,6 +291,25 @@ impl GraphicsInterface
 			info!(target: "Interlude::InstanceExtensions", "{:?} version {:?}", unsafe { CStr::from_ptr(ep.extensionName.as_ptr()).to_str()? }, ep.specVersion);
 		}
 
+		// Validation layers: skips missing layers, and reports only when the extension is available //
+		let ValidationConfig { enabled: validation_enabled, layers: requested_layers, report_flags, handler } = validation;
+		let layers_c = if validation_enabled
+		{
+			let available = available_instance_layers()?;
+			let mut layers = Vec::with_capacity(requested_layers.len());
+			for l in requested_layers
+			{
+				if available.contains(&l) { layers.push(CString::new(l)?); }
+				else { warn!(target: "Interlude", "Validation layer {} is not present, skipped", l); }
+			}
+			layers
+		}
+		else { Vec::new() };
+		let debug_report_available = validation_enabled && (available_instance_extensions(None)?.iter().any(|e| e == "VK_EXT_debug_report") ||
+			layers_c.iter().map(|l| available_instance_extensions(Some(&**l)).map(|es| es.iter().any(|e| e == "VK_EXT_debug_report")))
+				.collect::<EngineResult<Vec<_>>>()?.into_iter().any(|x| x));
+		if validation_enabled && !debug_report_available { warn!(target: "Interlude", "VK_EXT_debug_report is not available, validation messages are not reported"); }
+
 		let engine_name_c = CString::new("Interlude Multimedia Framework")?;
 		let app_name_c = CString::new(app_name)?;
 		let appinfo = VkApplicationInfo
@@ -111,51 +318,99 @@ impl GraphicsInterface
 			pEngineName: engine_name_c.as_ptr(), engineVersion: VK_MAKE_VERSION!(0, 1, 0),
 			.. Default::default()
 		};
-		let enabled_layers = ["VK_LAYER_LUNARG_standard_validation\x00".as_ptr()];
-		let enabled_extensions = ["VK_KHR_surface\x00".as_ptr(), PLATFORM_SURFACE_EXTENSION_NAME.as_ptr(), "VK_EXT_debug_report\x00".as_ptr()];
+		let enabled_layers = layers_c.iter().map(|l| l.as_ptr()).collect::<Vec<_>>();
+		let mut enabled_extensions = if presentation_check.is_some() { vec!["VK_KHR_surface\x00".as_ptr(), PLATFORM_SURFACE_EXTENSION_NAME.as_ptr()] } else { Vec::new() };
+		if debug_report_available { enabled_extensions.push("VK_EXT_debug_report\x00".as_ptr()); }
 		let instance = NativeInstance::new(&VkInstanceCreateInfo
 		{
 			pApplicationInfo: &appinfo, enabledLayerCount: enabled_layers.len() as _, ppEnabledLayerNames: enabled_layers.as_ptr() as _,
 			enabledExtensionCount: enabled_extensions.len() as _, ppEnabledExtensionNames: enabled_extensions.as_ptr() as _,
 			.. Default::default()
 		}).map(Rc::new)?;
-		let debug_report_callback = DebugReportCallback::new(&instance)?;
+		let debug_report_callback = if debug_report_available { DebugReportCallback::new(&instance, report_flags, handler).map(Some)? } else { None };
 
 		let mut devcount = 0;
 		unsafe { vkEnumeratePhysicalDevices(instance.native(), &mut devcount, null_mut()) }.into_result()?;
 		let mut pdevs = vec![unsafe { reserved() }; devcount as _];
 		unsafe { vkEnumeratePhysicalDevices(instance.native(), &mut devcount, pdevs.as_mut_ptr()) }.into_result()?;
 		if pdevs.is_empty() { return Err(EngineError::GenericError("PhysicalDevices are not found")); }
-		let pdev = pdevs.swap_remove(0);
-		let mut device_properties = unsafe { reserved() };
-		unsafe { vkGetPhysicalDeviceProperties(pdev, &mut device_properties) };
-		info!("Graphics Device: {}", unsafe { CStr::from_ptr(device_properties.deviceName.as_ptr()) }.to_str()?);
-		info!("-- API Version: {}.{}.{}", VK_VERSION!(MAJOR device_properties.apiVersion), VK_VERSION!(MINOR device_properties.apiVersion),
-			VK_VERSION!(PATCH device_properties.apiVersion));
+		let adapters = pdevs.into_iter().enumerate().map(|(n, pd)| AdapterInfo::query(n, pd, presentation_check)).collect::<EngineResult<Vec<_>>>()?;
+		for a in &adapters
+		{
+			info!(target: "Interlude::Adapters", "#{}: {} ({:?}, API {}.{}.{})", a.index, a.name, a.device_type, a.api_version.0, a.api_version.1, a.api_version.2);
+		}
+		let adapter_index = adapter_preference.select(&adapters, presentation_check.is_some())?;
+		let (pdev, device_limits, queue_family_properties) =
+		{
+			let a = &adapters[adapter_index];
+			info!("Graphics Device: {}", a.name);
+			info!("-- API Version: {}.{}.{}", a.api_version.0, a.api_version.1, a.api_version.2);
+			(a.handle, a.limits.clone(), a.queue_families.clone())
+		};
 		Self::diagnose_pdev(pdev);
-		let mut qfcount = 0;
-		unsafe { vkGetPhysicalDeviceQueueFamilyProperties(pdev, &mut qfcount, null_mut()) };
-		let mut queue_family_properties = vec![unsafe { reserved() }; qfcount as _];
-		unsafe { vkGetPhysicalDeviceQueueFamilyProperties(pdev, &mut qfcount, queue_family_properties.as_mut_ptr()) };
 		let gqf = queue_family_properties.iter().enumerate().find(|&(_, fp)| (fp.queueFlags & VK_QUEUE_GRAPHICS_BIT) != 0).map(|(i, _)| i as u32)
 			.ok_or(EngineError::GenericError("Unable to find graphics queue"))?;
 		let tqf = queue_family_properties.iter().enumerate().find(|&(qf, fp)| (fp.queueFlags & VK_QUEUE_TRANSFER_BIT) != 0 && qf != gqf as _).map(|(i, _)| i as u32);
-		let device = Device::new(pdev, &device_features.0, gqf, tqf, &queue_family_properties[gqf as usize]).map(Rc::new)?;
+		let mut enabled_extensions = if presentation_check.is_some() { vec!["VK_KHR_swapchain".to_owned()] } else { Vec::new() };
+		enabled_extensions.extend(OPTIONAL_DEVICE_EXTENSIONS.iter().filter(|&&e| adapters[adapter_index].extensions.iter().any(|a| a == e)).map(|&e| e.to_owned()));
+		for e in &enabled_extensions { info!(target: "Interlude", "Enabling device extension {}", e); }
+		let extensions_c = enabled_extensions.iter().map(|e| CString::new(e.as_str())).collect::<Result<Vec<_>, _>>()?;
+		// Rasterizer/DepthStencil/Multisample/Blend features configurable through GraphicsPipelineBuilder are enabled whenever supported
+		let mut enabled_features = device_features.0.clone();
+		{
+			let supported = &adapters[adapter_index].features;
+			enabled_features.wideLines = supported.wideLines;
+			enabled_features.depthClamp = supported.depthClamp;
+			enabled_features.depthBiasClamp = supported.depthBiasClamp;
+			enabled_features.depthBounds = supported.depthBounds;
+			enabled_features.sampleRateShading = supported.sampleRateShading;
+			enabled_features.logicOp = supported.logicOp;
+			enabled_features.independentBlend = supported.independentBlend;
+			enabled_features.dualSrcBlend = supported.dualSrcBlend;
+		}
+		let device = Device::new(pdev, &enabled_features, &enabled_layers, &extensions_c.iter().map(|e| e.as_ptr()).collect::<Vec<_>>(),
+			gqf, tqf, &queue_family_properties[gqf as usize]).map(Rc::new)?;
 		let pools = CommandPool::new(&device)?;
 
 		let mut memory_types = unsafe { reserved() };
 		unsafe { vkGetPhysicalDeviceMemoryProperties(pdev, &mut memory_types) };
 		let mt_indices = MemoryTypeIndices::find_from(&memory_types)?;
+		let allocator = MemoryAllocator::new(&device, &device_limits, memory_types.clone());
+		let pipeline_cache = PipelineCache::new(&device, &[])?;
 
 		Ok(GraphicsInterface
 		{
 			instance: (instance, debug_report_callback), device, pools,
-			device_limits: device_properties.limits, memory_types: mt_indices
+			device_limits, memory_types: mt_indices, memory_properties: memory_types, allocator, adapters, adapter_index, pipeline_cache,
+			enabled_features, enabled_extensions, sampler_cache: RefCell::new(HashMap::new())
 		})
 	}
 	pub fn apicontext(&self) -> &Rc<NativeInstance> { &self.instance.0 }
 	pub fn device(&self) -> &Rc<Device> { &self.device }
 	pub fn pools(&self) -> &CommandPool { &self.pools }
+	pub fn allocator(&self) -> &MemoryAllocator { &self.allocator }
+	/// All adapters enumerated on initialization
+	pub fn adapters(&self) -> &[AdapterInfo] { &self.adapters }
+	/// The adapter in use
+	pub fn adapter_info(&self) -> &AdapterInfo { &self.adapters[self.adapter_index] }
+	/// Device features enabled on creation
+	pub fn enabled_features(&self) -> &VkPhysicalDeviceFeatures { &self.enabled_features }
+	/// Whether the device extension has been enabled on creation
+	pub fn device_extension_enabled(&self, name: &str) -> bool { self.enabled_extensions.iter().any(|e| e == name) }
+	/// Pipeline cache used by GraphicsPipelines and ComputePipelines
+	pub fn pipeline_cache(&self) -> &PipelineCache { &self.pipeline_cache }
+	/// Sampler shared among the same states, created on the first request
+	pub fn sampler(&self, state: &SamplerState) -> EngineResult<Rc<Sampler>>
+	{
+		if let Some(s) = self.sampler_cache.borrow().get(state) { return Ok(s.clone()); }
+		let sampler = Rc::new(Sampler::new(self, state)?);
+		self.sampler_cache.borrow_mut().insert(state.clone(), sampler.clone());
+		Ok(sampler)
+	}
+	/// Number of distinct samplers in the cache
+	pub fn cached_sampler_count(&self) -> usize { self.sampler_cache.borrow().len() }
+	/// Used/Free bytes of pooled device memory per memory type
+	pub fn memory_statistics(&self) -> Vec<MemoryHeapStatistics> { self.allocator.statistics() }
 	pub fn update_descriptors(&self, write_infos: &[DescriptorSetWriteInfo])
 	{
 		let write_infos_native_interp = write_infos.into_iter().map(Into::into).collect::<Vec<IntoWriteDescriptorSetNativeStruct>>();
@@ -190,6 +445,42 @@ impl GraphicsInterface
 			MemoryIndexType::HostVisible => self.memory_types.host_visible
 		}
 	}
+	/// Finds a memory type allowed in `type_bits`(memoryTypeBits of VkMemoryRequirements) that has all of `required` property flags.
+	/// Types having more of `preferred` flags are chosen first, then lower indices.
+	pub fn memory_type_index(&self, type_bits: u32, required: VkMemoryPropertyFlags, preferred: VkMemoryPropertyFlags) -> EngineResult<u32>
+	{
+		find_memory_type(&self.memory_properties, type_bits, required, preferred).map(|i| i as u32)
+			.ok_or(EngineError::GenericError("No memory type satisfies the requirements"))
+	}
+	pub fn memory_properties(&self) -> &VkPhysicalDeviceMemoryProperties { &self.memory_properties }
+	/// Supported features of the format on the adapter in use
+	pub fn format_properties(&self, format: VkFormat) -> VkFormatProperties
+	{
+		let mut props = unsafe { reserved() };
+		unsafe { vkGetPhysicalDeviceFormatProperties(self.device.adapter(), format, &mut props) };
+		props
+	}
+	/// Sample counts(combination of VK_SAMPLE_COUNT_*_BIT) supported by framebuffer color(and depth) attachments
+	pub fn framebuffer_sample_counts(&self, with_depth: bool) -> SampleCount
+	{
+		let color = self.device_limits.framebufferColorSampleCounts;
+		if with_depth { color & self.device_limits.framebufferDepthSampleCounts } else { color }
+	}
+	/// The highest supported sample count not exceeding `requested`(1 if multisampling is not supported at all)
+	pub fn max_sample_count(&self, requested: SampleCount, with_depth: bool) -> SampleCount
+	{
+		let supported = self.framebuffer_sample_counts(with_depth);
+		(0 .. 32).rev().map(|b| 1 << b).find(|&c| c <= requested && (supported & c) != 0).unwrap_or(VK_SAMPLE_COUNT_1_BIT)
+	}
+	/// Depth(/Stencil) format usable as an attachment in optimal tiling, in the order of precision
+	pub fn depth_format(&self, require_stencil: bool) -> EngineResult<VkFormat>
+	{
+		let candidates: &[VkFormat] = if require_stencil { &[VK_FORMAT_D32_SFLOAT_S8_UINT, VK_FORMAT_D24_UNORM_S8_UINT, VK_FORMAT_D16_UNORM_S8_UINT] }
+			else { &[VK_FORMAT_D32_SFLOAT, VK_FORMAT_X8_D24_UNORM_PACK32, VK_FORMAT_D16_UNORM, VK_FORMAT_D32_SFLOAT_S8_UINT, VK_FORMAT_D24_UNORM_S8_UINT] };
+		candidates.iter().cloned()
+			.find(|&f| (self.format_properties(f).optimalTilingFeatures & VK_FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT) != 0)
+			.ok_or(EngineError::GenericError("No supported depth format"))
+	}
 }
 impl Drop for GraphicsInterface
 {
@@ -199,18 +490,38 @@ impl MemoryTypeIndices
 {
 	fn find_from(p: &VkPhysicalDeviceMemoryProperties) -> EngineResult<Self>
 	{
-		let mtdx_find_by_flag = |bits| p.memoryTypes[..p.memoryTypeCount as usize].iter().enumerate().find(|&(_, mt)| (mt.propertyFlags & bits) != 0);
-		let (d, _) = mtdx_find_by_flag(VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT).ok_or(EngineError::GenericError("Device Local Memory could not be found"))?;
-		let (h, _) = mtdx_find_by_flag(VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT).ok_or(EngineError::GenericError("Host Visible Memory could not be found"))?;
+		let d = find_memory_type(p, !0, VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT, 0)
+			.ok_or(EngineError::GenericError("Device Local Memory could not be found"))?;
+		let h = find_memory_type(p, !0, VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT, VK_MEMORY_PROPERTY_HOST_COHERENT_BIT)
+			.ok_or(EngineError::GenericError("Host Visible Memory could not be found"))?;
 		info!(target: "Interlude", "MemoryType Indices: DeviceLocal={} HostVisible={}", d, h);
 		Ok(MemoryTypeIndices { device_local: d as _, host_visible: h as _ })
 	}
 }
 
+fn find_memory_type(p: &VkPhysicalDeviceMemoryProperties, type_bits: u32, required: VkMemoryPropertyFlags, preferred: VkMemoryPropertyFlags) -> Option<usize>
+{
+	p.memoryTypes[..p.memoryTypeCount as usize].iter().enumerate()
+		.filter(|&(i, mt)| (type_bits & (1 << i)) != 0 && (mt.propertyFlags & required) == required)
+		.max_by_key(|&(i, mt)| ((mt.propertyFlags & preferred).count_ones(), -(i as isize)))
+		.map(|(i, _)| i)
+}
+
 extern "system" fn debug_callback(flags: VkDebugReportFlagsEXT, object_type: VkDebugReportObjectTypeEXT, _: u64,
-	_: size_t, message_code: i32, _: *const c_char, message: *const c_char, _: *mut c_void) -> VkBool32
+	_: size_t, message_code: i32, _: *const c_char, message: *const c_char, user_data: *mut c_void) -> VkBool32
 {
-	if (flags & VK_DEBUG_REPORT_ERROR_BIT_EXT) != 0
+	if !user_data.is_null()
+	{
+		let handler = unsafe { &*(user_data as *const DebugReportHandler) };
+		let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+		// unwinding across the driver's stack frames is undefined behavior
+		if let Err(e) = catch_unwind(AssertUnwindSafe(|| handler(flags, object_type, message_code, &message)))
+		{
+			let reason = e.downcast_ref::<&str>().map(|s| *s).or_else(|| e.downcast_ref::<String>().map(|s| s.as_str())).unwrap_or("<unknown>");
+			error!(target: "Interlude", "DebugReportHandler panicked: {} (message: ({}){})", reason, message_code, message);
+		}
+	}
+	else if (flags & VK_DEBUG_REPORT_ERROR_BIT_EXT) != 0
 	{
 		error!(target: format!("Vulkan DebugCall [{:?}]", object_type).as_str(), "({}){}", message_code, unsafe { CStr::from_ptr(message).to_str().unwrap() });
 	}
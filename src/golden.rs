@@ -0,0 +1,179 @@
+//! Interlude: Golden-image Regression Testing(Comparing captured frames with stored reference images)
+//!
+//! Fixtures render a single frame in a headless engine, capture it and verify against `<directory>/<name>.png`.
+//! References are rendered with a CPU Vulkan implementation for reproducibility, e.g.
+//! `VK_ICD_FILENAMES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json INTERLUDE_GOLDEN_DIR=assets/golden INTERLUDE_UPDATE_GOLDEN=1 cargo run --example triangle`
+//! records the reference; a missing reference fails the test.
+
+use {EngineResult, EngineError, Size2, CapturedImage};
+use std::path::PathBuf;
+use std::env;
+use std::fs;
+
+/// Directory of reference images. Fixtures run as golden-image tests only when this is set
+pub const GOLDEN_DIR_ENV: &'static str = "INTERLUDE_GOLDEN_DIR";
+/// Set to overwrite reference images with the captured results instead of comparing
+pub const GOLDEN_UPDATE_ENV: &'static str = "INTERLUDE_UPDATE_GOLDEN";
+
+/// Acceptable differences between a capture and its reference
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GoldenTolerance
+{
+	/// Maximum absolute difference per channel for a pixel to be regarded as same
+	pub channel: u8,
+	/// Maximum number of differing pixels for an image to pass
+	pub max_differing_pixels: usize
+}
+impl Default for GoldenTolerance
+{
+	/// Small rounding differences are allowed, no differing pixels are allowed
+	fn default() -> Self { GoldenTolerance { channel: 2, max_differing_pixels: 0 } }
+}
+
+/// Result of comparing two images of the same size
+pub struct ImageDifference
+{
+	pub differing_pixels: usize, pub max_channel_difference: u8,
+	/// Differing pixels in red, over the dimmed reference
+	pub diff: CapturedImage
+}
+impl ImageDifference
+{
+	pub fn compute(actual: &CapturedImage, reference: &CapturedImage, channel_tolerance: u8) -> EngineResult<Self>
+	{
+		if actual.size != reference.size { return Err(EngineError::GenericError("Comparing images of different sizes")); }
+		let (mut differing_pixels, mut max_channel_difference) = (0, 0);
+		let mut diff = Vec::with_capacity(reference.pixels.len());
+		for (a, r) in actual.pixels.chunks(4).zip(reference.pixels.chunks(4))
+		{
+			let d = a.iter().zip(r.iter()).map(|(&a, &r)| if a > r { a - r } else { r - a }).max().unwrap_or(0);
+			max_channel_difference = max_channel_difference.max(d);
+			if d > channel_tolerance
+			{
+				differing_pixels += 1;
+				diff.extend_from_slice(&[255, 0, 0, 255]);
+			}
+			else
+			{
+				let luma = ((r[0] as u32 * 54 + r[1] as u32 * 183 + r[2] as u32 * 19) >> 10) as u8;
+				diff.extend_from_slice(&[luma, luma, luma, 255]);
+			}
+		}
+		Ok(ImageDifference { differing_pixels, max_channel_difference, diff: CapturedImage { size: reference.size.clone(), pixels: diff } })
+	}
+}
+
+/// Outcome of a golden-image test
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GoldenOutcome
+{
+	Passed { differing_pixels: usize, max_channel_difference: u8 },
+	/// Updating was requested: the capture has been stored as the reference
+	Recorded,
+	/// The reference does not exist(a failure). `actual` is the path of the written capture
+	MissingReference { actual: PathBuf },
+	/// `actual` and `diff` are the paths of written images(no diff image is written if the sizes differ)
+	Failed { differing_pixels: usize, max_channel_difference: u8, actual: PathBuf, diff: Option<PathBuf> }
+}
+impl GoldenOutcome
+{
+	pub fn passed(&self) -> bool { match *self { GoldenOutcome::Passed { .. } | GoldenOutcome::Recorded => true, _ => false } }
+	/// Err if the test has failed, with the paths of the written images logged(fixtures may propagate it to fail the run)
+	pub fn check(&self) -> EngineResult<()>
+	{
+		match *self
+		{
+			GoldenOutcome::Failed { ref actual, diff: Some(ref d), .. } =>
+				error!(target: "Interlude::Golden", "Golden-image test failed: see {} and {}", actual.display(), d.display()),
+			GoldenOutcome::Failed { ref actual, diff: None, .. } =>
+				error!(target: "Interlude::Golden", "Golden-image test failed: see {}", actual.display()),
+			GoldenOutcome::MissingReference { ref actual } =>
+				error!(target: "Interlude::Golden", "Golden-image test failed: the reference image is missing(captured: {}, set {} to record it)",
+					actual.display(), GOLDEN_UPDATE_ENV),
+			_ => return Ok(())
+		}
+		Err(EngineError::GenericError("Golden-image test failed"))
+	}
+}
+
+/// A named golden-image test case
+pub struct GoldenImageTest { name: String, directory: PathBuf, tolerance: GoldenTolerance }
+impl GoldenImageTest
+{
+	pub fn new<P: Into<PathBuf>>(name: &str, directory: P) -> Self
+	{
+		GoldenImageTest { name: name.to_owned(), directory: directory.into(), tolerance: Default::default() }
+	}
+	/// Test case in the directory specified by `INTERLUDE_GOLDEN_DIR`, if set
+	pub fn from_env(name: &str) -> Option<Self>
+	{
+		env::var_os(GOLDEN_DIR_ENV).map(|d| Self::new(name, d))
+	}
+	pub fn tolerance(mut self, channel: u8, max_differing_pixels: usize) -> Self
+	{
+		self.tolerance = GoldenTolerance { channel, max_differing_pixels };
+		self
+	}
+
+	pub fn name(&self) -> &str { &self.name }
+	pub fn reference_path(&self) -> PathBuf { self.output_path("") }
+	fn output_path(&self, suffix: &str) -> PathBuf { self.directory.join(format!("{}{}.png", self.name, suffix)) }
+
+	/// Compares the capture with the reference, and writes `<name>.actual.png` and `<name>.diff.png` on failure.
+	/// A missing reference is a failure unless `INTERLUDE_UPDATE_GOLDEN` is set
+	pub fn verify(&self, actual: &CapturedImage) -> EngineResult<GoldenOutcome>
+	{
+		let reference_path = self.reference_path();
+		if env::var_os(GOLDEN_UPDATE_ENV).is_some()
+		{
+			fs::create_dir_all(&self.directory)?;
+			actual.write_png(&reference_path)?;
+			warn!(target: "Interlude::Golden", "[{}] Recorded the reference image: {}", self.name, reference_path.display());
+			return Ok(GoldenOutcome::Recorded);
+		}
+		if !reference_path.exists()
+		{
+			error!(target: "Interlude::Golden", "[{}] Reference image is missing: {}(set {} to record it)",
+				self.name, reference_path.display(), GOLDEN_UPDATE_ENV);
+			fs::create_dir_all(&self.directory)?;
+			return self.write_failure(actual, None).map(|actual| GoldenOutcome::MissingReference { actual });
+		}
+		let reference = CapturedImage::load_png(&reference_path)?;
+		if actual.size != reference.size
+		{
+			let Size2(w, h) = reference.size;
+			error!(target: "Interlude::Golden", "[{}] Size mismatch: expected {}x{}, got {:?}", self.name, w, h, actual.size);
+			let actual_path = self.write_failure(actual, None)?;
+			return Ok(GoldenOutcome::Failed
+			{
+				differing_pixels: (w * h) as usize, max_channel_difference: 255, actual: actual_path, diff: None
+			});
+		}
+
+		let d = ImageDifference::compute(actual, &reference, self.tolerance.channel)?;
+		if d.differing_pixels <= self.tolerance.max_differing_pixels
+		{
+			info!(target: "Interlude::Golden", "[{}] Passed: {} differing pixels(max channel difference: {})",
+				self.name, d.differing_pixels, d.max_channel_difference);
+			Ok(GoldenOutcome::Passed { differing_pixels: d.differing_pixels, max_channel_difference: d.max_channel_difference })
+		}
+		else
+		{
+			error!(target: "Interlude::Golden", "[{}] Failed: {} differing pixels(allowed: {}, max channel difference: {})",
+				self.name, d.differing_pixels, self.tolerance.max_differing_pixels, d.max_channel_difference);
+			let actual_path = self.write_failure(actual, Some(&d.diff))?;
+			Ok(GoldenOutcome::Failed
+			{
+				differing_pixels: d.differing_pixels, max_channel_difference: d.max_channel_difference,
+				actual: actual_path, diff: Some(self.output_path(".diff"))
+			})
+		}
+	}
+	fn write_failure(&self, actual: &CapturedImage, diff: Option<&CapturedImage>) -> EngineResult<PathBuf>
+	{
+		let actual_path = self.output_path(".actual");
+		actual.write_png(&actual_path)?;
+		if let Some(d) = diff { d.write_png(self.output_path(".diff"))?; }
+		Ok(actual_path)
+	}
+}
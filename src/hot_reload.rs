@@ -0,0 +1,174 @@
+//! Interlude: Shader Hot-reloading(Rebuilding pipelines when shader assets are modified, for development)
+//!
+//! Changes are detected with inotify on Linux, and by polling modification times on other platforms.
+
+use {EngineResult, GraphicsInterface, AssetProvider, AssetPath, ShaderStage, GraphicsPipelineBuilder, GraphicsPipelines, GraphicsPipeline};
+#[cfg(target_os = "linux")] use linux::inotify::Inotify;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::ops::Deref;
+#[cfg(not(target_os = "linux"))] use std::fs;
+#[cfg(not(target_os = "linux"))] use std::time::SystemTime;
+
+/// Detects files written or replaced since the last query
+#[cfg(target_os = "linux")]
+struct FileWatcher { inotify: Inotify, directories: HashMap<i32, PathBuf>, files: HashSet<PathBuf> }
+#[cfg(target_os = "linux")]
+impl FileWatcher
+{
+	fn new() -> EngineResult<Self>
+	{
+		Ok(FileWatcher { inotify: Inotify::new()?, directories: HashMap::new(), files: HashSet::new() })
+	}
+	fn watch(&mut self, path: &Path) -> EngineResult<()>
+	{
+		// Directories are watched since editors and compilers often replace files by renaming
+		let directory = path.parent().unwrap_or(Path::new("."));
+		let wd = self.inotify.watch_directory(directory)?;
+		self.directories.insert(wd, directory.to_owned());
+		self.files.insert(path.to_owned());
+		Ok(())
+	}
+	fn changed_files(&mut self) -> EngineResult<HashSet<PathBuf>>
+	{
+		let (directories, files) = (&self.directories, &self.files);
+		Ok(self.inotify.read_changes()?.into_iter().filter_map(|(wd, name)| directories.get(&wd).map(|d| d.join(name)))
+			.filter(|p| files.contains(p)).collect())
+	}
+}
+/// Detects files written or replaced since the last query
+#[cfg(not(target_os = "linux"))]
+struct FileWatcher { files: HashMap<PathBuf, Option<SystemTime>> }
+#[cfg(not(target_os = "linux"))]
+impl FileWatcher
+{
+	fn new() -> EngineResult<Self> { Ok(FileWatcher { files: HashMap::new() }) }
+	fn watch(&mut self, path: &Path) -> EngineResult<()>
+	{
+		self.files.insert(path.to_owned(), modified_time(path));
+		Ok(())
+	}
+	fn changed_files(&mut self) -> EngineResult<HashSet<PathBuf>>
+	{
+		let mut changed = HashSet::new();
+		for (path, time) in &mut self.files
+		{
+			let current = modified_time(path);
+			if current.is_some() && current != *time { changed.insert(path.clone()); }
+			*time = current;
+		}
+		Ok(changed)
+	}
+}
+#[cfg(not(target_os = "linux"))]
+fn modified_time(path: &Path) -> Option<SystemTime> { fs::metadata(path).and_then(|m| m.modified()).ok() }
+
+struct ReloadablePipeline<'a> { builder: GraphicsPipelineBuilder<'a>, sources: Vec<(ShaderStage, PathBuf)>, pipeline: GraphicsPipeline }
+impl<'a> ReloadablePipeline<'a>
+{
+	fn depends_on(&self, changed: &HashSet<PathBuf>) -> bool { self.sources.iter().any(|&(_, ref p)| changed.contains(p)) }
+	/// Builds a new pipeline with the changed shaders reloaded. The current builder and pipeline are left untouched
+	fn rebuild(&self, engine: &GraphicsInterface, changed: &HashSet<PathBuf>) -> EngineResult<(GraphicsPipelineBuilder<'a>, GraphicsPipeline)>
+	{
+		let mut builder = GraphicsPipelineBuilder::inherit(&self.builder);
+		for &(stage, ref path) in self.sources.iter().filter(|&&(_, ref p)| changed.contains(p))
+		{
+			info!(target: "Interlude::HotReload", "Reloading {:?} Shader from {:?}...", stage, path);
+			builder = builder.reload_shader(engine, stage, path)?;
+		}
+		let pipeline = GraphicsPipelines::new(engine, &[&builder])?.remove(0);
+		Ok((builder, pipeline))
+	}
+}
+
+/// Graphics Pipelines rebuilt when their shader assets are modified(for development).
+/// Pipelines are referred by the index returned from `register`
+pub struct ShaderHotReloader<'a> { watcher: FileWatcher, pipelines: Vec<ReloadablePipeline<'a>> }
+impl<'a> ShaderHotReloader<'a>
+{
+	pub fn new() -> EngineResult<Self>
+	{
+		Ok(ShaderHotReloader { watcher: FileWatcher::new()?, pipelines: Vec::new() })
+	}
+	/// Creates a pipeline and starts watching the shader assets it was built from.
+	/// `sources` are the asset paths of the shaders set to the builder, per stage
+	pub fn register<Engine: AssetProvider + Deref<Target = GraphicsInterface>, P: AssetPath + Clone>(&mut self, engine: &Engine,
+		builder: GraphicsPipelineBuilder<'a>, sources: &[(ShaderStage, P)]) -> EngineResult<usize>
+	{
+		let sources = sources.iter().map(|&(stage, ref p)| (stage, engine.parse_asset(p.clone(), "spv"))).collect::<Vec<_>>();
+		for &(_, ref p) in &sources { self.watcher.watch(p)?; }
+		let pipeline = GraphicsPipelines::new(engine, &[&builder])?.remove(0);
+		self.pipelines.push(ReloadablePipeline { builder, sources, pipeline });
+		Ok(self.pipelines.len() - 1)
+	}
+	pub fn pipeline(&self, index: usize) -> &GraphicsPipeline { &self.pipelines[index].pipeline }
+
+	/// Rebuilds the pipelines whose shaders have been modified since the last call.
+	/// Call this at a safe point between frames: the device is waited for idle before the old pipelines are destroyed.
+	/// Failures are logged and the previous pipelines are kept.
+	/// Returns true if any pipeline has been replaced(command buffers recorded with them must be re-recorded)
+	pub fn update(&mut self, engine: &GraphicsInterface) -> bool
+	{
+		let changed = match self.watcher.changed_files()
+		{
+			Ok(c) => c,
+			Err(e) => { error!(target: "Interlude::HotReload", "Failed to query modified shaders: {:?}", e); return false; }
+		};
+		if changed.is_empty() { return false; }
+
+		let mut rebuilt = Vec::new();
+		for (i, p) in self.pipelines.iter().enumerate().filter(|&(_, p)| p.depends_on(&changed))
+		{
+			match p.rebuild(engine, &changed)
+			{
+				Ok(r) => rebuilt.push((i, r)),
+				Err(e) => error!(target: "Interlude::HotReload", "Failed to rebuild Pipeline #{}, keeping the previous one: {:?}", i, e)
+			}
+		}
+		if rebuilt.is_empty() { return false; }
+		if let Err(e) = engine.wait_device()
+		{
+			error!(target: "Interlude::HotReload", "Failed to wait for the device, discarding rebuilt pipelines: {:?}", e);
+			return false;
+		}
+		for (i, (builder, pipeline)) in rebuilt
+		{
+			let p = &mut self.pipelines[i];
+			p.builder = builder; p.pipeline = pipeline;
+			info!(target: "Interlude::HotReload", "Pipeline #{} has been replaced", i);
+		}
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::fs;
+	use std::io::prelude::*;
+	use std::env;
+	use std::process;
+
+	#[test]
+	fn file_watcher_reports_written_and_renamed_files()
+	{
+		let directory = env::temp_dir().join(format!("interlude-hot-reload-{}", process::id()));
+		fs::create_dir_all(&directory).unwrap();
+		let watched = directory.join("watched.spv");
+		let mut watcher = FileWatcher::new().unwrap();
+		watcher.watch(&watched).unwrap();
+		assert!(watcher.changed_files().unwrap().is_empty());
+
+		// names of various lengths(event records are padded differently), only one of them is watched
+		fs::File::create(directory.join("a-much-longer-name-of-an-unwatched-file.spv")).and_then(|mut f| f.write_all(b"other")).unwrap();
+		fs::File::create(directory.join("watched.spv.tmp")).and_then(|mut f| f.write_all(b"spirv")).unwrap();
+		fs::rename(directory.join("watched.spv.tmp"), &watched).unwrap();
+		fs::File::create(directory.join("x")).unwrap();
+
+		let changed = watcher.changed_files().unwrap();
+		fs::remove_dir_all(&directory).unwrap();
+		assert_eq!(changed.into_iter().collect::<Vec<_>>(), vec![watched]);
+		assert!(watcher.changed_files().unwrap().is_empty());
+	}
+}
@@ -13,6 +13,8 @@ extern crate nalgebra;
 extern crate unicode_normalization;
 extern crate ansi_term;
 extern crate mio;
+extern crate png;
+extern crate json;
 #[cfg(feature = "target_xlib")] extern crate x11;
 #[macro_use] extern crate interlude_vk_defs;
 extern crate interlude_vk_funport;
@@ -25,10 +27,20 @@ mod ginterface;
 mod device;
 mod command;
 mod resource;
+mod memory;
 mod framebuffer;
 mod synchronize;
 mod shading;
+mod pipeline_cache;
+mod hot_reload;
+mod multisample;
+mod reflection;
 mod render_surface;
+mod container;
+mod capture;
+mod texture;
+mod mesh;
+mod golden;
 mod descriptor;
 mod input;
 mod data;
@@ -60,21 +72,24 @@ pub use resource::{ImageAspect, ImageAspectSet};
 // --- Exported APIs --- //
 pub use error::*;
 pub use engine::{EngineBuilder, EmptyInput};
-pub use ginterface::GraphicsInterface;
+pub use ginterface::{GraphicsInterface, AdapterInfo, AdapterType, AdapterPreference, ADAPTER_OVERRIDE_ENV, ValidationConfig, DebugReportHandler};
+pub use memory::{MemoryAllocator, MemoryAllocation, MemoryHeapStatistics, ResourceTiling};
 pub use framebuffer::{AttachmentDesc, AttachmentRef, PassDesc, PassDependency, AttachmentClearValue, PreciseRenderPass};
 pub use command::{
-	MemoryBarrier, BufferMemoryBarrier, ImageMemoryBarrier, IndirectCallParameter, BufferCopyRegion, ImageCopyRegion, ImageBlitRegion,
-	GraphicsCommandBuffersView, TransferCommandBuffersView
+	MemoryBarrier, BufferMemoryBarrier, ImageMemoryBarrier, IndirectCallParameter, IndexedIndirectCallParameter, DispatchIndirectParameter, BufferCopyRegion, ImageCopyRegion, BufferImageCopyRegion, ImageBlitRegion,
+	GraphicsCommandBuffersView, TransferCommandBuffersView, IndexType, IndexBuffer
 };
 pub use resource::{
 	ImageSubresourceRange, ImageSubresourceLayers, BufferContent, BufferOffsets,
-	ImageDescriptor1, ImageDescriptor2, ImageDescriptor3,
-	SamplerState, ComponentSwizzle, ComponentMapping, Filter, ImageLayout
+	ImageDescriptor1, ImageDescriptor2, ImageDescriptor3, SampleCount,
+	SamplerState, MipmapMode, AddressMode, BorderColor, ComponentSwizzle, ComponentMapping, Filter, ImageLayout
 };
 pub use shading::{
 	ConstantEntry, VertexBinding, VertexAttribute, PushConstantDesc,
-	PrimitiveTopology, ViewportWithScissorRect, RasterizerState, AttachmentBlendState,
-	GraphicsPipelineBuilder
+	PrimitiveTopology, ViewportWithScissorRect, RasterizerState, FrontFace, DepthBias, AttachmentBlendState,
+	BlendFactor, BlendOp, BlendEquation, ColorComponent, ColorComponentSet, CustomBlendState, LogicOp,
+	CompareOp, StencilOp, StencilFace, StencilFaceState, DepthStencilState, DynamicState,
+	GraphicsPipelineBuilder, ComputePipelineBuilder
 };
 pub use descriptor::{Descriptor, BufferInfo, ImageInfo, DescriptorSetWriteInfo, DescriptorSetArrayView};
 // pub use debug_info::DebugLine;
@@ -91,19 +106,31 @@ pub use command::{ImmediateGraphicsCommandSubmission, ImmediateTransferCommandSu
 
 // traits
 pub use engine::{AssetProvider, AssetPath, CommandSubmitter};
-pub use command::{PrimaryCommandBuffers, SecondaryCommandBuffers, DrawingCommandRecorder, QueueSyncOperationCommandRecorder};
+pub use command::{PrimaryCommandBuffers, SecondaryCommandBuffers, DrawingCommandRecorder, ComputeCommandRecorder, QueueSyncOperationCommandRecorder};
 pub use command::{PrimaryGraphicsCommandRecorder, PrimaryTransferCommandRecorder, ClosableCommandRecorder, CommandInjection};
 pub use resource::{ImageView, BufferResource, ImageResource, StagingResource};
 pub use shading::Shader;
 // exported objects
 pub use engine::Engine;
 pub use synchronize::{QueueFence, Fence};
+pub use container::OffscreenTargets;
+pub use multisample::MultisampleRenderTarget;
+pub use capture::CapturedImage;
+pub use texture::{Texture, TextureData, TextureSubresource, TextureFileFormat};
+pub use mesh::{Mesh, MeshPrimitive, MeshData, MeshPrimitiveData, MeshFileFormat, MeshVertexLayout, VertexSemantic};
+pub use golden::{GoldenImageTest, GoldenTolerance, GoldenOutcome, ImageDifference, GOLDEN_DIR_ENV, GOLDEN_UPDATE_ENV};
 pub use framebuffer::{RenderPass, Framebuffer};
 pub use command::{GraphicsCommandBuffers, BundledCommandBuffers, TransferCommandBuffers, TransientTransferCommandBuffers, TransientGraphicsCommandBuffers};
 pub use resource::{Image1D, Image2D, Image3D, LinearImage, DeviceBuffer, StagingBuffer, DeviceImages, StagingImages};
-pub use resource::{ImageView1D, ImageView2D, ImageView3D, Sampler, BufferPreallocator, ImagePreallocator, MappedRange};
-pub use shading::{VertexShader, TessellationControlShader, TessellationEvaluationShader, GeometryShader, FragmentShader, ShaderModule};
-pub use shading::{PipelineShaderProgram, PipelineLayout, GraphicsPipelines, GraphicsPipeline};
+pub use resource::{ImageView1D, ImageView2D, ImageView3D, Sampler, BufferPreallocator, ImagePreallocator, MappedRange, MipmapChain, mip_level_count};
+pub use shading::{VertexShader, TessellationControlShader, TessellationEvaluationShader, GeometryShader, FragmentShader, ComputeShader, ShaderModule};
+pub use shading::{PipelineShaderProgram, PipelineLayout, GraphicsPipelines, GraphicsPipeline, ComputePipelines, ComputePipeline};
+pub use pipeline_cache::{PipelineCache, is_compatible_cache};
+pub use hot_reload::ShaderHotReloader;
+pub use reflection::{
+	ShaderReflection, PipelineReflection, ReflectedDescriptorType, ReflectedDescriptor, ReflectedPushConstants, ReflectedVertexInput,
+	ReflectedSpecConstant, SpecConstantType, LayoutMismatch
+};
 pub use descriptor::{DescriptorSetLayout, DescriptorSets};
 
 // For internal exports //
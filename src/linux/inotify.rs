@@ -0,0 +1,59 @@
+// inotify: Filesystem event notifications
+
+use {std, libc};
+use std::io;
+use std::path::Path;
+use std::ffi::{CString, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::io::RawFd;
+
+/// Non-blocking inotify instance
+pub struct Inotify(RawFd);
+impl Inotify
+{
+	pub fn new() -> io::Result<Self>
+	{
+		let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+		if fd < 0 { Err(io::Error::last_os_error()) } else { Ok(Inotify(fd)) }
+	}
+	/// Watches files completely written or moved into the directory. Returns the watch descriptor(same for the same directory)
+	pub fn watch_directory(&self, directory: &Path) -> io::Result<i32>
+	{
+		let path = CString::new(directory.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+		let wd = unsafe { libc::inotify_add_watch(self.0, path.as_ptr(), libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO) };
+		if wd < 0 { Err(io::Error::last_os_error()) } else { Ok(wd) }
+	}
+	/// Pairs of the watch descriptor and the file name, for all events queued since the last call(never blocks)
+	pub fn read_changes(&self) -> io::Result<Vec<(i32, OsString)>>
+	{
+		const HEADER_SIZE: usize = std::mem::size_of::<libc::inotify_event>();
+		// u32 storage to keep events aligned
+		let mut buffer = [0u32; 1024];
+		let mut changes = Vec::new();
+		loop
+		{
+			let read_bytes = unsafe { libc::read(self.0, buffer.as_mut_ptr() as *mut _, std::mem::size_of_val(&buffer)) };
+			if read_bytes < 0
+			{
+				let e = io::Error::last_os_error();
+				return if e.kind() == io::ErrorKind::WouldBlock { Ok(changes) } else { Err(e) };
+			}
+			if read_bytes == 0 { return Ok(changes); }
+			let bytes = unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u8, read_bytes as usize) };
+			let mut offset = 0;
+			while offset + HEADER_SIZE <= bytes.len()
+			{
+				let event: &libc::inotify_event = unsafe { &*(bytes[offset..].as_ptr() as *const _) };
+				let name = &bytes[offset + HEADER_SIZE .. offset + HEADER_SIZE + event.len as usize];
+				// names are padded with NULs
+				let name_len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+				if name_len > 0 { changes.push((event.wd, OsString::from_vec(name[..name_len].to_owned()))); }
+				offset += HEADER_SIZE + event.len as usize;
+			}
+		}
+	}
+}
+impl Drop for Inotify
+{
+	fn drop(&mut self) { unsafe { libc::close(self.0) }; }
+}
@@ -3,4 +3,5 @@
 pub mod evdev;
 pub mod udev;
 pub mod input;
+#[cfg(target_os = "linux")] pub mod inotify;
 pub use self::input::NativeInput;
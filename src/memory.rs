@@ -0,0 +1,305 @@
+//! Interlude: Device Memory Allocator(Sub-allocation from pooled memory blocks)
+
+use interlude_vk_defs::*;
+use interlude_vk_funport::*;
+use device::Device;
+use {EngineResult, EngineError};
+use subsystem_layer::{NativeHandleProvider, NativeResultValueHandler};
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::ptr::{null, null_mut};
+use std::mem::uninitialized as reserved;
+use libc::c_void;
+
+/// Size of a memory block allocated from the device at once: 64MB
+const DEFAULT_BLOCK_SIZE: VkDeviceSize = 64 << 20;
+
+fn align_up(v: VkDeviceSize, a: VkDeviceSize) -> VkDeviceSize { if a <= 1 { v } else { (v + a - 1) / a * a } }
+
+/// Arrangement of the resource contents in memory(Linear: Buffers and Linear Images, Optimal: Optimally-tiled Images)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceTiling { Linear, Optimal }
+
+/// Usage statistics of the memory blocks allocated for a memory type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryHeapStatistics
+{
+	pub memory_type_index: u32, pub heap_index: u32, pub block_count: usize,
+	pub allocated_bytes: VkDeviceSize, pub used_bytes: VkDeviceSize, pub free_bytes: VkDeviceSize
+}
+
+struct MemoryBlock
+{
+	memory: VkDeviceMemory, size: VkDeviceSize, dedicated: bool,
+	/// (offset, size), sorted by offset
+	free_ranges: Vec<(VkDeviceSize, VkDeviceSize)>,
+	mapped_ptr: *mut c_void, map_count: usize
+}
+impl MemoryBlock
+{
+	fn new(device: &Device, memory_type_index: u32, size: VkDeviceSize, dedicated: bool) -> EngineResult<Self>
+	{
+		let mut memory = unsafe { reserved() };
+		unsafe { vkAllocateMemory(device.native(), &VkMemoryAllocateInfo
+		{
+			allocationSize: size, memoryTypeIndex: memory_type_index, .. Default::default()
+		}, null(), &mut memory) }.make_result_with(|| MemoryBlock
+		{
+			memory, size, dedicated, free_ranges: vec![(0, size)], mapped_ptr: null_mut(), map_count: 0
+		})
+	}
+	/// First-fit sub-allocation
+	fn suballocate(&mut self, size: VkDeviceSize, alignment: VkDeviceSize) -> Option<VkDeviceSize>
+	{
+		for i in 0 .. self.free_ranges.len()
+		{
+			let (offset, free_size) = self.free_ranges[i];
+			let aligned = align_up(offset, alignment);
+			if aligned - offset + size > free_size { continue; }
+
+			// split into the padding before and the remaining after the placed range
+			let (head, tail) = ((offset, aligned - offset), (aligned + size, offset + free_size - aligned - size));
+			self.free_ranges.remove(i);
+			let mut insert_at = i;
+			if head.1 > 0 { self.free_ranges.insert(insert_at, head); insert_at += 1; }
+			if tail.1 > 0 { self.free_ranges.insert(insert_at, tail); }
+			return Some(aligned);
+		}
+		None
+	}
+	fn release(&mut self, offset: VkDeviceSize, size: VkDeviceSize)
+	{
+		let pos = self.free_ranges.iter().position(|&(o, _)| o > offset).unwrap_or(self.free_ranges.len());
+		self.free_ranges.insert(pos, (offset, size));
+		// coalesce with the neighbors
+		if pos + 1 < self.free_ranges.len() && offset + size == self.free_ranges[pos + 1].0
+		{
+			self.free_ranges[pos].1 += self.free_ranges[pos + 1].1;
+			self.free_ranges.remove(pos + 1);
+		}
+		if pos > 0 && self.free_ranges[pos - 1].0 + self.free_ranges[pos - 1].1 == offset
+		{
+			self.free_ranges[pos - 1].1 += self.free_ranges[pos].1;
+			self.free_ranges.remove(pos);
+		}
+	}
+	fn free_bytes(&self) -> VkDeviceSize { self.free_ranges.iter().map(|&(_, s)| s).sum() }
+	fn is_unused(&self) -> bool { self.free_ranges.len() == 1 && self.free_ranges[0] == (0, self.size) }
+	fn free(&self, device: &Device) { unsafe { vkFreeMemory(device.native(), self.memory, null()) }; }
+}
+
+/// Blocks for a memory type. Resources of different tiling are placed in separated pools
+/// when the device requires bufferImageGranularity.
+struct MemoryPool { memory_type_index: u32, tiling: Option<ResourceTiling>, blocks: Vec<MemoryBlock> }
+struct AllocatorState
+{
+	device: Rc<Device>, pools: Vec<MemoryPool>, block_size: VkDeviceSize,
+	buffer_image_granularity: VkDeviceSize, non_coherent_atom_size: VkDeviceSize, memory_properties: VkPhysicalDeviceMemoryProperties
+}
+impl AllocatorState
+{
+	fn pool_index(&mut self, memory_type_index: u32, tiling: ResourceTiling) -> usize
+	{
+		let tiling = if self.buffer_image_granularity > 1 { Some(tiling) } else { None };
+		match self.pools.iter().position(|p| p.memory_type_index == memory_type_index && p.tiling == tiling)
+		{
+			Some(p) => p,
+			None => { self.pools.push(MemoryPool { memory_type_index, tiling, blocks: Vec::new() }); self.pools.len() - 1 }
+		}
+	}
+	fn block_mut(&mut self, pool: usize, memory: VkDeviceMemory) -> &mut MemoryBlock
+	{
+		self.pools[pool].blocks.iter_mut().find(|b| b.memory == memory).expect("Allocation from unknown memory block")
+	}
+	/// Empty blocks are freed, except one pooled block kept for reuse
+	fn release(&mut self, pool: usize, memory: VkDeviceMemory, offset: VkDeviceSize, size: VkDeviceSize)
+	{
+		let (unused, dedicated) = { let b = self.block_mut(pool, memory); b.release(offset, size); (b.is_unused(), b.dedicated) };
+		if !unused { return; }
+		let spare_exists = self.pools[pool].blocks.iter().any(|b| b.memory != memory && !b.dedicated && b.is_unused());
+		if dedicated || spare_exists
+		{
+			let n = self.pools[pool].blocks.iter().position(|b| b.memory == memory).unwrap();
+			let b = self.pools[pool].blocks.remove(n);
+			b.free(&self.device);
+		}
+	}
+	fn trim(&mut self) -> VkDeviceSize
+	{
+		let mut freed = 0;
+		for p in &mut self.pools
+		{
+			let (unused, used) = p.blocks.drain(..).partition::<Vec<_>, _>(|b| b.is_unused());
+			p.blocks = used;
+			for b in unused { freed += b.size; b.free(&self.device); }
+		}
+		freed
+	}
+}
+impl Drop for AllocatorState
+{
+	fn drop(&mut self)
+	{
+		for b in self.pools.iter().flat_map(|p| p.blocks.iter()) { b.free(&self.device); }
+	}
+}
+
+/// Pooled Device Memory Allocator: sub-allocates ranges from large memory blocks
+#[derive(Clone)]
+pub struct MemoryAllocator(Rc<RefCell<AllocatorState>>);
+impl MemoryAllocator
+{
+	pub(crate) fn new(device: &Rc<Device>, limits: &VkPhysicalDeviceLimits, memory_properties: VkPhysicalDeviceMemoryProperties) -> Self
+	{
+		MemoryAllocator(Rc::new(RefCell::new(AllocatorState
+		{
+			device: device.clone(), pools: Vec::new(), block_size: DEFAULT_BLOCK_SIZE,
+			buffer_image_granularity: limits.bufferImageGranularity, non_coherent_atom_size: limits.nonCoherentAtomSize, memory_properties
+		})))
+	}
+	/// Sub-allocates a range satisfying `requirements` from the memory type.
+	/// Requirements larger than the block size get a dedicated block.
+	pub fn allocate(&self, memory_type_index: u32, requirements: &VkMemoryRequirements, tiling: ResourceTiling) -> EngineResult<MemoryAllocation>
+	{
+		if (requirements.memoryTypeBits & (1 << memory_type_index)) == 0
+		{
+			return Err(EngineError::GenericError("The memory type is not allowed for the resource"));
+		}
+		let mut state = self.0.borrow_mut();
+		let coherent = (state.memory_properties.memoryTypes[memory_type_index as usize].propertyFlags & VK_MEMORY_PROPERTY_HOST_COHERENT_BIT) != 0;
+		// non-coherent ranges occupy whole atoms so that flush/invalidate never reach into the neighbors
+		let (size, alignment) = if coherent { (requirements.size, requirements.alignment) } else
+		{
+			let atom = state.non_coherent_atom_size.max(1);
+			(align_up(requirements.size, atom), requirements.alignment.max(atom))
+		};
+		let pool = state.pool_index(memory_type_index, tiling);
+		let found = state.pools[pool].blocks.iter_mut().filter(|b| !b.dedicated)
+			.filter_map(|b| b.suballocate(size, alignment).map(|o| (b.memory, o))).next();
+		let (memory, offset) = match found
+		{
+			Some(v) => v,
+			None =>
+			{
+				let dedicated = size > state.block_size;
+				let block_size = if dedicated { size } else { state.block_size };
+				let mut block = MemoryBlock::new(&state.device, memory_type_index, block_size, dedicated)?;
+				info!(target: "Interlude::MemoryAllocator", "Allocated a new {}block for MemoryType #{}: {} bytes",
+					if dedicated { "dedicated " } else { "" }, memory_type_index, block_size);
+				let offset = block.suballocate(size, alignment).unwrap();
+				let memory = block.memory;
+				state.pools[pool].blocks.push(block);
+				(memory, offset)
+			}
+		};
+		Ok(MemoryAllocation { allocator: self.0.clone(), pool, memory, offset, size, coherent })
+	}
+	/// Frees all blocks without allocations. Returns the freed bytes
+	pub fn trim(&self) -> VkDeviceSize
+	{
+		let freed = self.0.borrow_mut().trim();
+		if freed > 0 { info!(target: "Interlude::MemoryAllocator", "Freed unused blocks: {} bytes", freed); }
+		freed
+	}
+	/// Used/Free bytes for each memory type that has any blocks
+	pub fn statistics(&self) -> Vec<MemoryHeapStatistics>
+	{
+		let state = self.0.borrow();
+		let mut stats: Vec<MemoryHeapStatistics> = Vec::new();
+		for p in state.pools.iter().filter(|p| !p.blocks.is_empty())
+		{
+			let allocated = p.blocks.iter().map(|b| b.size).sum::<VkDeviceSize>();
+			let free = p.blocks.iter().map(MemoryBlock::free_bytes).sum::<VkDeviceSize>();
+			if let Some(s) = stats.iter_mut().find(|s| s.memory_type_index == p.memory_type_index)
+			{
+				s.block_count += p.blocks.len(); s.allocated_bytes += allocated; s.used_bytes += allocated - free; s.free_bytes += free;
+				continue;
+			}
+			stats.push(MemoryHeapStatistics
+			{
+				memory_type_index: p.memory_type_index, heap_index: state.memory_properties.memoryTypes[p.memory_type_index as usize].heapIndex,
+				block_count: p.blocks.len(), allocated_bytes: allocated, used_bytes: allocated - free, free_bytes: free
+			});
+		}
+		stats
+	}
+}
+
+/// A range of device memory sub-allocated by MemoryAllocator, returned to the pool on drop
+pub struct MemoryAllocation
+{
+	allocator: Rc<RefCell<AllocatorState>>, pool: usize,
+	memory: VkDeviceMemory, offset: VkDeviceSize, size: VkDeviceSize, coherent: bool
+}
+impl MemoryAllocation
+{
+	pub fn offset(&self) -> VkDeviceSize { self.offset }
+	pub fn size(&self) -> VkDeviceSize { self.size }
+	/// Whether the memory type has VK_MEMORY_PROPERTY_HOST_COHERENT_BIT(flush/invalidate are not required)
+	pub fn is_coherent(&self) -> bool { self.coherent }
+
+	/// The whole allocated range(non-coherent allocations are placed and sized in nonCoherentAtomSize units)
+	fn mapped_range(&self) -> VkMappedMemoryRange
+	{
+		VkMappedMemoryRange { memory: self.memory, offset: self.offset, size: self.size, .. Default::default() }
+	}
+	/// Makes host writes visible to the device. Does nothing for coherent memory.
+	pub fn flush(&self) -> EngineResult<()>
+	{
+		if self.coherent { return Ok(()); }
+		let range = self.mapped_range();
+		unsafe { vkFlushMappedMemoryRanges(self.allocator.borrow().device.native(), 1, &range) }.into_result()
+	}
+	/// Makes device writes visible to the host. Does nothing for coherent memory.
+	pub fn invalidate(&self) -> EngineResult<()>
+	{
+		if self.coherent { return Ok(()); }
+		let range = self.mapped_range();
+		unsafe { vkInvalidateMappedMemoryRanges(self.allocator.borrow().device.native(), 1, &range) }.into_result()
+	}
+
+	/// Maps the range into host memory. The owning block stays mapped while any of its ranges are mapped.
+	/// Non-coherent ranges are invalidated after mapping.
+	pub fn map(&self) -> EngineResult<*mut c_void>
+	{
+		let ptr =
+		{
+			let mut state = self.allocator.borrow_mut();
+			let device = state.device.native();
+			let block = state.block_mut(self.pool, self.memory);
+			if block.map_count == 0
+			{
+				let mut ptr = null_mut();
+				unsafe { vkMapMemory(device, block.memory, 0, VK_WHOLE_SIZE, 0, &mut ptr) }.into_result()?;
+				block.mapped_ptr = ptr;
+			}
+			block.map_count += 1;
+			unsafe { (block.mapped_ptr as *mut u8).offset(self.offset as isize) as *mut c_void }
+		};
+		if let Err(e) = self.invalidate() { self.release_mapping(); return Err(e); }
+		Ok(ptr)
+	}
+	/// Non-coherent ranges are flushed before unmapping(failures are logged, since this is called on drop).
+	pub fn unmap(&self)
+	{
+		if let Err(e) = self.flush() { error!(target: "Interlude::MemoryAllocator", "Failed to flush the mapped range: {:?}", e); }
+		self.release_mapping();
+	}
+	fn release_mapping(&self)
+	{
+		let mut state = self.allocator.borrow_mut();
+		let device = state.device.native();
+		let block = state.block_mut(self.pool, self.memory);
+		block.map_count -= 1;
+		if block.map_count == 0
+		{
+			unsafe { vkUnmapMemory(device, block.memory) };
+			block.mapped_ptr = null_mut();
+		}
+	}
+}
+impl NativeHandleProvider for MemoryAllocation { type NativeT = VkDeviceMemory; fn native(&self) -> VkDeviceMemory { self.memory } }
+impl Drop for MemoryAllocation
+{
+	fn drop(&mut self) { self.allocator.borrow_mut().release(self.pool, self.memory, self.offset, self.size); }
+}
@@ -0,0 +1,560 @@
+//! Interlude: Mesh Assets(Loading Wavefront OBJ and glTF 2.0, Interleaving and Uploading into DeviceBuffer)
+
+use {EngineResult, EngineError, GraphicsInterface, AssetProvider, AssetPath};
+use {Format, FormatType, PackedPixelOrder, VertexAttribute, VertexBinding};
+use {BufferPreallocator, BufferContent, DeviceBuffer, StagingResource, BufferMemoryBarrier, BufferCopyRegion, AccessFlag, PipelineStage};
+use {ImmediateGraphicsCommandSubmission, QueueSyncOperationCommandRecorder, PrimaryTransferCommandRecorder, IndexBuffer};
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::Path;
+use std::io::prelude::*;
+use std::fs::File;
+use std::mem::size_of;
+use json;
+use json::JsonValue;
+
+/// Meaning of a vertex element
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VertexSemantic { Position, Normal, Tangent, TexCoord(u32), Color(u32) }
+
+/// Interleaved vertex layout in a single binding.
+/// Elements are written in 32bit float or 8bit normalized formats with 1 to 4 components(R, RG, RGB or RGBA)
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshVertexLayout { stride: u32, elements: Vec<(VertexSemantic, VertexAttribute)> }
+impl MeshVertexLayout
+{
+	pub fn new(stride: u32) -> Self { MeshVertexLayout { stride, elements: Vec::new() } }
+	/// Adds an element. Attribute locations are assigned in the order of addition
+	pub fn element(mut self, semantic: VertexSemantic, attribute: VertexAttribute) -> Self
+	{
+		self.elements.push((semantic, attribute));
+		self
+	}
+	pub fn stride(&self) -> u32 { self.stride }
+	pub fn binding(&self) -> VertexBinding { VertexBinding::PerVertex(self.stride) }
+	/// Attributes for `VertexShader::from_asset`
+	pub fn attributes(&self) -> Vec<VertexAttribute> { self.elements.iter().map(|&(_, ref a)| a.clone()).collect() }
+}
+
+/// Vertex streams and triangle list indices of a primitive
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshPrimitiveData
+{
+	pub positions: Vec<[f32; 3]>, pub normals: Vec<[f32; 3]>, pub tangents: Vec<[f32; 4]>,
+	pub texcoords: Vec<Vec<[f32; 2]>>, pub colors: Vec<Vec<[f32; 4]>>, pub indices: Vec<u32>
+}
+impl MeshPrimitiveData
+{
+	pub fn vertex_count(&self) -> usize { self.positions.len() }
+	/// Missing elements are filled with (0, 0, 0, 1), except colors with (1, 1, 1, 1)
+	fn element(&self, semantic: VertexSemantic, index: usize) -> [f32; 4]
+	{
+		let (position, normal, tangent) = (self.positions.get(index), self.normals.get(index), self.tangents.get(index));
+		let element = match semantic
+		{
+			VertexSemantic::Position => position.map(|p| [p[0], p[1], p[2], 1.0]),
+			VertexSemantic::Normal => normal.map(|n| [n[0], n[1], n[2], 0.0]),
+			VertexSemantic::Tangent => tangent.cloned(),
+			VertexSemantic::TexCoord(n) => self.texcoords.get(n as usize).and_then(|t| t.get(index)).map(|t| [t[0], t[1], 0.0, 1.0]),
+			VertexSemantic::Color(n) => Some(self.colors.get(n as usize).and_then(|c| c.get(index)).cloned().unwrap_or([1.0; 4]))
+		};
+		element.unwrap_or([0.0, 0.0, 0.0, 1.0])
+	}
+	/// Interleaves vertex elements in the layout
+	pub fn interleave(&self, layout: &MeshVertexLayout) -> EngineResult<Vec<u8>>
+	{
+		let stride = layout.stride as usize;
+		let mut bytes = vec![0u8; stride * self.vertex_count()];
+		for &(semantic, VertexAttribute(_, ref format, offset)) in &layout.elements
+		{
+			let (components, float) = element_format(format).ok_or(EngineError::GenericError("Unsupported vertex attribute format for meshes"))?;
+			if offset as usize + components * if float { 4 } else { 1 } > stride
+			{
+				return Err(EngineError::GenericError("Vertex attribute exceeds the stride"));
+			}
+			for (i, v) in bytes.chunks_mut(stride).enumerate()
+			{
+				let e = self.element(semantic, i);
+				let dst = &mut v[offset as usize ..];
+				for c in 0 .. components
+				{
+					if float
+					{
+						let b = e[c].to_bits();
+						dst[c * 4 .. c * 4 + 4].copy_from_slice(&[b as u8, (b >> 8) as u8, (b >> 16) as u8, (b >> 24) as u8]);
+					}
+					else { dst[c] = (e[c].max(0.0).min(1.0) * 255.0 + 0.5) as u8; }
+				}
+			}
+		}
+		Ok(bytes)
+	}
+}
+/// (component count, is 32bit float)
+fn element_format(format: &Format) -> Option<(usize, bool)>
+{
+	let components = |o| match o
+	{
+		PackedPixelOrder::R => Some(1), PackedPixelOrder::RG => Some(2), PackedPixelOrder::RGB => Some(3), PackedPixelOrder::RGBA => Some(4),
+		_ => None
+	};
+	match *format
+	{
+		Format::Component(32, o, FormatType::Float) => components(o).map(|c| (c, true)),
+		Format::Component(8, o, FormatType::UNormalized) => components(o).map(|c| (c, false)),
+		_ => None
+	}
+}
+
+/// Supported mesh file formats
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshFileFormat { OBJ, GLTF, GLB }
+/// Primitives loaded from a mesh file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshData { pub primitives: Vec<MeshPrimitiveData> }
+impl MeshData
+{
+	/// Detects the format from the extension(obj, gltf or glb). External buffers of glTF are resolved relative to the file
+	pub fn load<P: AsRef<Path>>(path: P) -> EngineResult<Self>
+	{
+		let path = path.as_ref();
+		let file_format = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase())
+		{
+			Some(ref e) if e == "obj" => MeshFileFormat::OBJ,
+			Some(ref e) if e == "gltf" => MeshFileFormat::GLTF,
+			Some(ref e) if e == "glb" => MeshFileFormat::GLB,
+			_ => return Err(EngineError::GenericError("Unknown mesh file format"))
+		};
+		let mut bytes = Vec::new();
+		File::open(path)?.read_to_end(&mut bytes)?;
+		let base_dir = path.parent().unwrap_or(Path::new(""));
+		match file_format
+		{
+			MeshFileFormat::OBJ => Self::parse_obj(::std::str::from_utf8(&bytes)?),
+			MeshFileFormat::GLTF => Self::parse_gltf(::std::str::from_utf8(&bytes)?, None, base_dir),
+			MeshFileFormat::GLB =>
+			{
+				let (json, bin) = split_glb(&bytes)?;
+				Self::parse_gltf(json, bin, base_dir)
+			}
+		}
+	}
+
+	/// Objects, groups and material changes start new primitives. Polygons are triangulated as fans.
+	/// Texture coordinates are flipped vertically to have the top-left origin
+	pub fn parse_obj(source: &str) -> EngineResult<Self>
+	{
+		let (mut positions, mut colors, mut texcoords, mut normals) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+		let mut primitives = Vec::new();
+		let mut current = MeshPrimitiveData::default();
+		let mut vertex_map: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+		fn numbers(args: &[&str]) -> EngineResult<Vec<f32>>
+		{
+			args.iter().map(|s| s.parse().map_err(|_| EngineError::GenericError("Invalid number in OBJ"))).collect()
+		}
+		// 1-based or negative(relative) index
+		fn resolve(s: &str, count: usize) -> EngineResult<usize>
+		{
+			let i: isize = s.parse().map_err(|_| EngineError::GenericError("Invalid index in OBJ"))?;
+			let r = if i < 0 { count as isize + i } else { i - 1 };
+			if r < 0 || r as usize >= count { Err(EngineError::GenericError("Index out of range in OBJ")) } else { Ok(r as usize) }
+		}
+
+		for line in source.lines()
+		{
+			let mut tokens = line.split('#').next().unwrap_or("").split_whitespace();
+			let command = match tokens.next() { Some(c) => c, None => continue };
+			let args = tokens.collect::<Vec<_>>();
+			match command
+			{
+				"v" =>
+				{
+					let v = numbers(&args)?;
+					if v.len() < 3 { return Err(EngineError::GenericError("Too few components for a vertex in OBJ")); }
+					positions.push([v[0], v[1], v[2]]);
+					// vertex color extension: v x y z r g b
+					colors.push(if v.len() >= 6 { Some([v[3], v[4], v[5], 1.0]) } else { None });
+				},
+				"vt" =>
+				{
+					let v = numbers(&args)?;
+					if v.is_empty() { return Err(EngineError::GenericError("Too few components for a texture coordinate in OBJ")); }
+					texcoords.push([v[0], 1.0 - v.get(1).cloned().unwrap_or(0.0)]);
+				},
+				"vn" =>
+				{
+					let v = numbers(&args)?;
+					if v.len() < 3 { return Err(EngineError::GenericError("Too few components for a normal in OBJ")); }
+					normals.push([v[0], v[1], v[2]]);
+				},
+				"f" =>
+				{
+					let mut face = Vec::with_capacity(args.len());
+					for a in &args
+					{
+						let mut refs = a.split('/');
+						let v = resolve(refs.next().unwrap_or(""), positions.len())?;
+						let vt = match refs.next() { Some(s) if !s.is_empty() => Some(resolve(s, texcoords.len())?), _ => None };
+						let vn = match refs.next() { Some(s) if !s.is_empty() => Some(resolve(s, normals.len())?), _ => None };
+						let index = match vertex_map.get(&(v, vt, vn)) { Some(&i) => i, None =>
+						{
+							let i = current.positions.len() as u32;
+							current.positions.push(positions[v]);
+							if let Some(t) = vt { if current.texcoords.is_empty() { current.texcoords.push(Vec::new()); } current.texcoords[0].push(texcoords[t]); }
+							if let Some(n) = vn { current.normals.push(normals[n]); }
+							if let Some(c) = colors[v] { if current.colors.is_empty() { current.colors.push(Vec::new()); } current.colors[0].push(c); }
+							i
+						} };
+						vertex_map.insert((v, vt, vn), index);
+						face.push(index);
+					}
+					if face.len() < 3 { return Err(EngineError::GenericError("Degenerated face in OBJ")); }
+					for i in 1 .. face.len() - 1 { current.indices.extend_from_slice(&[face[0], face[i], face[i + 1]]); }
+				},
+				"o" | "g" | "usemtl" => if !current.indices.is_empty()
+				{
+					primitives.push(::std::mem::replace(&mut current, MeshPrimitiveData::default()));
+					vertex_map.clear();
+				},
+				_ => ()
+			}
+		}
+		if !current.indices.is_empty() { primitives.push(current); }
+		// streams referenced by only some of vertices cannot be interleaved
+		for p in &mut primitives
+		{
+			if p.normals.len() != p.positions.len() { p.normals.clear(); }
+			if p.texcoords.first().map(|t| t.len() != p.positions.len()).unwrap_or(false) { p.texcoords.clear(); }
+			if p.colors.first().map(|c| c.len() != p.positions.len()).unwrap_or(false) { p.colors.clear(); }
+		}
+		Ok(MeshData { primitives })
+	}
+
+	/// Loads all triangle primitives of all meshes(node transforms are not applied).
+	/// `bin` is the binary chunk of GLB, used by the buffer without uri
+	pub fn parse_gltf(source: &str, bin: Option<&[u8]>, base_dir: &Path) -> EngineResult<Self>
+	{
+		let doc = json::parse(source)?;
+		if !doc["asset"]["version"].as_str().map(|v| v.starts_with("2.")).unwrap_or(false)
+		{
+			return Err(EngineError::GenericError("Unsupported glTF version"));
+		}
+		let buffers = doc["buffers"].members().map(|b| match b["uri"].as_str()
+		{
+			Some(uri) if uri.starts_with("data:") =>
+			{
+				let data = uri.splitn(2, ";base64,").nth(1).ok_or(EngineError::GenericError("Unsupported data uri in glTF"))?;
+				decode_base64(data)
+			},
+			Some(uri) =>
+			{
+				let mut bytes = Vec::new();
+				File::open(base_dir.join(uri))?.read_to_end(&mut bytes)?;
+				Ok(bytes)
+			},
+			None => bin.map(|b| b.to_owned()).ok_or(EngineError::GenericError("Missing binary chunk for glTF buffer"))
+		}).collect::<EngineResult<Vec<_>>>()?;
+
+		let mut primitives = Vec::new();
+		for p in doc["meshes"].members().flat_map(|m| m["primitives"].members())
+		{
+			// 4 = TRIANGLES
+			if p["mode"].as_u32().unwrap_or(4) != 4
+			{
+				warn!(target: "Interlude::Mesh", "Skipping a non-triangle primitive in glTF");
+				continue;
+			}
+			let attributes = &p["attributes"];
+			let position = attributes["POSITION"].as_usize().ok_or(EngineError::GenericError("Missing POSITION attribute in glTF"))?;
+			let positions = read_accessor(&doc, &buffers, position)?.into_iter().map(|v| [v[0], v[1], v[2]]).collect::<Vec<_>>();
+			let stream = |name: &str| match attributes[name].as_usize() { Some(a) => read_accessor(&doc, &buffers, a).map(Some), None => Ok(None) };
+			let normals = stream("NORMAL")?.map(|v| v.into_iter().map(|v| [v[0], v[1], v[2]]).collect()).unwrap_or_default();
+			let tangents = stream("TANGENT")?.unwrap_or_default();
+			let (mut texcoords, mut colors) = (Vec::new(), Vec::new());
+			while let Some(t) = stream(&format!("TEXCOORD_{}", texcoords.len()))? { texcoords.push(t.into_iter().map(|v| [v[0], v[1]]).collect()); }
+			// missing alpha of RGB colors is filled with 1
+			while let Some(c) = stream(&format!("COLOR_{}", colors.len()))? { colors.push(c); }
+			let indices = match p["indices"].as_usize()
+			{
+				Some(a) => read_accessor_raw(&doc, &buffers, a)?.2.into_iter().map(|v| v[0]).collect(),
+				None => (0 .. positions.len() as u32).collect()
+			};
+			primitives.push(MeshPrimitiveData { positions, normals, tangents, texcoords, colors, indices });
+		}
+		Ok(MeshData { primitives })
+	}
+}
+
+/// JSON chunk and BIN chunk of a GLB container
+fn split_glb(bytes: &[u8]) -> EngineResult<(&str, Option<&[u8]>)>
+{
+	const TRUNCATED: EngineError = EngineError::GenericError("GLB data is truncated");
+	fn u32le(b: &[u8], offs: usize) -> u32 { b[offs] as u32 | (b[offs + 1] as u32) << 8 | (b[offs + 2] as u32) << 16 | (b[offs + 3] as u32) << 24 }
+	if bytes.len() < 20 || &bytes[0 .. 4] != b"glTF" || u32le(bytes, 4) != 2 { return Err(EngineError::GenericError("Invalid GLB header")); }
+	let (mut json, mut bin, mut p) = (None, None, 12);
+	while p + 8 <= bytes.len()
+	{
+		let (length, chunk_type) = (u32le(bytes, p) as usize, u32le(bytes, p + 4));
+		let data = bytes.get(p + 8 .. p + 8 + length).ok_or(TRUNCATED)?;
+		match chunk_type
+		{
+			0x4e4f_534a => json = Some(::std::str::from_utf8(data)?),
+			0x004e_4942 => bin = Some(data),
+			_ => ()
+		}
+		p += 8 + length;
+	}
+	json.map(|j| (j, bin)).ok_or(EngineError::GenericError("Missing JSON chunk in GLB"))
+}
+
+fn accessor_components(doc: &JsonValue, accessor: usize) -> Option<usize>
+{
+	match doc["accessors"][accessor]["type"].as_str()
+	{
+		Some("SCALAR") => Some(1), Some("VEC2") => Some(2), Some("VEC3") => Some(3), Some("VEC4") => Some(4),
+		_ => None
+	}
+}
+/// Reads elements of the accessor as floats(normalized integers are converted into 0..1 or -1..1).
+/// Missing components are filled with (0, 0, 0, 1)
+fn read_accessor(doc: &JsonValue, buffers: &[Vec<u8>], accessor: usize) -> EngineResult<Vec<[f32; 4]>>
+{
+	let (component_type, components, raw) = read_accessor_raw(doc, buffers, accessor)?;
+	let normalized = doc["accessors"][accessor]["normalized"].as_bool().unwrap_or(false);
+	Ok(raw.into_iter().map(|e|
+	{
+		let mut v = [0.0, 0.0, 0.0, 1.0];
+		for c in 0 .. components
+		{
+			let u = e[c];
+			v[c] = match (component_type, normalized)
+			{
+				(5120, false) => u as u8 as i8 as f32, (5120, true) => (u as u8 as i8 as f32 / 127.0).max(-1.0),
+				(5121, false) => u as f32, (5121, true) => u as f32 / 255.0,
+				(5122, false) => u as u16 as i16 as f32, (5122, true) => (u as u16 as i16 as f32 / 32767.0).max(-1.0),
+				(5123, false) => u as f32, (5123, true) => u as f32 / 65535.0,
+				(5125, _) => u as f32,
+				_ => f32::from_bits(u)
+			};
+		}
+		v
+	}).collect())
+}
+/// (componentType, component count, elements in raw bits)
+fn read_accessor_raw(doc: &JsonValue, buffers: &[Vec<u8>], accessor: usize) -> EngineResult<(u32, usize, Vec<[u32; 4]>)>
+{
+	const OUT_OF_RANGE: EngineError = EngineError::GenericError("glTF accessor exceeds the buffer");
+	let a = &doc["accessors"][accessor];
+	if !a["sparse"].is_null() { return Err(EngineError::GenericError("Sparse accessors in glTF are not supported")); }
+	let count = a["count"].as_usize().ok_or(EngineError::GenericError("Missing count of glTF accessor"))?;
+	let components = accessor_components(doc, accessor).ok_or(EngineError::GenericError("Unsupported glTF accessor type"))?;
+	let component_type = a["componentType"].as_u32().unwrap_or(0);
+	let component_size = match component_type
+	{
+		5120 | 5121 => 1, 5122 | 5123 => 2, 5125 | 5126 => 4,
+		_ => return Err(EngineError::GenericError("Unsupported glTF component type"))
+	};
+	let view = match a["bufferView"].as_usize()
+	{
+		Some(v) => &doc["bufferViews"][v],
+		// accessors without buffer views are initialized with zeros
+		None => return Ok((component_type, components, vec![[0; 4]; count]))
+	};
+	let buffer = buffers.get(view["buffer"].as_usize().unwrap_or(0)).ok_or(OUT_OF_RANGE)?;
+	let offset = view["byteOffset"].as_usize().unwrap_or(0) + a["byteOffset"].as_usize().unwrap_or(0);
+	let stride = view["byteStride"].as_usize().unwrap_or(components * component_size);
+
+	let mut elements = Vec::with_capacity(count);
+	for i in 0 .. count
+	{
+		let e = buffer.get(offset + i * stride .. offset + i * stride + components * component_size).ok_or(OUT_OF_RANGE)?;
+		let mut v = [0; 4];
+		for (c, b) in e.chunks(component_size).enumerate() { v[c] = b.iter().rev().fold(0u32, |x, &b| x << 8 | b as u32); }
+		elements.push(v);
+	}
+	Ok((component_type, components, elements))
+}
+fn decode_base64(s: &str) -> EngineResult<Vec<u8>>
+{
+	let mut bytes = Vec::with_capacity(s.len() / 4 * 3);
+	let (mut acc, mut bits) = (0u32, 0);
+	for c in s.bytes().take_while(|&c| c != b'=')
+	{
+		let v = match c
+		{
+			b'A'..=b'Z' => c - b'A', b'a'..=b'z' => c - b'a' + 26, b'0'..=b'9' => c - b'0' + 52, b'+' => 62, b'/' => 63,
+			_ => return Err(EngineError::GenericError("Invalid base64 data in glTF"))
+		};
+		acc = acc << 6 | v as u32;
+		bits += 6;
+		if bits >= 8 { bits -= 8; bytes.push((acc >> bits) as u8); }
+	}
+	Ok(bytes)
+}
+
+/// Placement of a primitive in `Mesh::buffer`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MeshPrimitive { pub vertex_offset: usize, pub vertex_count: u32, pub index_offset: usize, pub index_count: u32 }
+/// Vertices and indices of all primitives in a DeviceBuffer, ready for reading as vertex/index buffers.
+/// Indices are stored in 16bit, or in 32bit if any primitive has more than 65536 vertices
+pub struct Mesh { buffer: DeviceBuffer, primitives: Vec<MeshPrimitive>, wide_indices: bool }
+impl Mesh
+{
+	pub fn new(engine: &GraphicsInterface, data: &MeshData, layout: &MeshVertexLayout) -> EngineResult<Self>
+	{
+		let vertices = data.primitives.iter().map(|p| p.interleave(layout)).collect::<EngineResult<Vec<_>>>()?;
+		let wide_indices = data.primitives.iter().any(|p| p.vertex_count() > 0x10000);
+		let index_size = if wide_indices { size_of::<u32>() } else { size_of::<u16>() };
+		let indices = data.primitives.iter().map(|p| &p.indices[..]).collect::<Vec<_>>();
+		// vertex blocks are padded to keep index blocks aligned
+		let contents = vertices.iter().zip(indices.iter()).flat_map(|(v, i)|
+			vec![BufferContent::Vertex((v.len() + 3) & !3), BufferContent::Index(index_size * i.len())]).collect::<Vec<_>>();
+		if contents.is_empty() { return Err(EngineError::GenericError("Empty mesh")); }
+		let bp = BufferPreallocator::new(engine, &contents);
+		let (buffer, stg) = bp.instantiate()?;
+		stg.map().map(|m| for (n, (v, i)) in vertices.iter().zip(indices.iter()).enumerate()
+		{
+			m.range_mut::<u8>(bp.offset(n * 2), v.len()).copy_from_slice(v);
+			if wide_indices { m.range_mut::<u32>(bp.offset(n * 2 + 1), i.len()).copy_from_slice(i); }
+			else { for (d, &s) in m.range_mut::<u16>(bp.offset(n * 2 + 1), i.len()).iter_mut().zip(i.iter()) { *d = s as u16; } }
+		})?;
+		let primitives = vertices.iter().zip(indices.iter()).enumerate().map(|(n, (v, i))| MeshPrimitive
+		{
+			vertex_offset: bp.offset(n * 2), vertex_count: (v.len() / layout.stride as usize) as _,
+			index_offset: bp.offset(n * 2 + 1), index_count: i.len() as _
+		}).collect();
+
+		// recorded on the graphics queue: vertex input stages are not available on transfer-only queue families
+		ImmediateGraphicsCommandSubmission::begin(engine)?
+			.pipeline_barrier(PipelineStage::Host, PipelineStage::Transfer, false, &[], &[
+				BufferMemoryBarrier
+				{
+					buffer: &stg, range: 0 .. bp.total_size(), src_access: AccessFlag::HostWrite.into(), dst_access: AccessFlag::TransferRead.into(),
+					.. Default::default()
+				},
+				BufferMemoryBarrier { buffer: &buffer, range: 0 .. bp.total_size(), dst_access: AccessFlag::TransferWrite.into(), .. Default::default() }
+			], &[])
+			.copy_buffer(&stg, &buffer, &[BufferCopyRegion(0, 0, bp.total_size())])
+			.pipeline_barrier(PipelineStage::Transfer, PipelineStage::VertexInput, false, &[], &[BufferMemoryBarrier
+			{
+				buffer: &buffer, range: 0 .. bp.total_size(),
+				src_access: AccessFlag::TransferWrite.into(), dst_access: AccessFlag::VertexAttributeRead | AccessFlag::IndexRead,
+				.. Default::default()
+			}], &[]);
+		info!(target: "Interlude::Mesh", "Uploaded {} primitives({} bytes)", data.primitives.len(), bp.total_size());
+		Ok(Mesh { buffer, primitives, wide_indices })
+	}
+	/// Loads the mesh asset with the extension(obj, gltf or glb)
+	pub fn from_asset<Engine: AssetProvider + Deref<Target = GraphicsInterface>, P: AssetPath>(engine: &Engine, path: P, extension: &str,
+		layout: &MeshVertexLayout) -> EngineResult<Self>
+	{
+		let path = engine.parse_asset(path, extension);
+		info!(target: "Interlude::Mesh", "Loading Mesh from {:?}...", path);
+		MeshData::load(&path).and_then(|d| Self::new(engine, &d, layout))
+	}
+
+	pub fn buffer(&self) -> &DeviceBuffer { &self.buffer }
+	pub fn primitives(&self) -> &[MeshPrimitive] { &self.primitives }
+	pub fn has_wide_indices(&self) -> bool { self.wide_indices }
+	/// Indices of the primitive, if stored in 16bit
+	pub fn indices16(&self, primitive: usize) -> Option<IndexBuffer<u16>>
+	{
+		// index blocks are always aligned to their element size in `new`
+		if self.wide_indices { None } else { IndexBuffer::new(&self.buffer, self.primitives[primitive].index_offset).ok() }
+	}
+	/// Indices of the primitive, if stored in 32bit
+	pub fn indices32(&self, primitive: usize) -> Option<IndexBuffer<u32>>
+	{
+		if self.wide_indices { IndexBuffer::new(&self.buffer, self.primitives[primitive].index_offset).ok() } else { None }
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn obj_fan_triangulation_and_vertex_dedup()
+	{
+		let source = "# quad\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n\
+			f 1/1/1 2/2/1 3/3/1 4/4/1\nf 1/1/1 3/3/1 -1/4/1\n";
+		let mesh = MeshData::parse_obj(source).unwrap();
+		assert_eq!(mesh.primitives.len(), 1);
+		let p = &mesh.primitives[0];
+		assert_eq!(p.positions, vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]);
+		assert_eq!(p.indices, vec![0, 1, 2, 0, 2, 3, 0, 2, 3]);
+		// flipped vertically
+		assert_eq!(p.texcoords, vec![vec![[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]]);
+		assert_eq!(p.normals, vec![[0.0, 0.0, 1.0]; 4]);
+		assert!(p.colors.is_empty());
+	}
+
+	#[test]
+	fn obj_groups_and_vertex_colors()
+	{
+		let source = "v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0 0 0 1\no a\nf 1 2 3\ng b\nf 3 2 1\n";
+		let mesh = MeshData::parse_obj(source).unwrap();
+		assert_eq!(mesh.primitives.len(), 2);
+		let p = &mesh.primitives[1];
+		assert_eq!(p.positions, vec![[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 0.0]]);
+		assert_eq!(p.indices, vec![0, 1, 2]);
+		assert_eq!(p.colors, vec![vec![[0.0, 0.0, 1.0, 1.0], [0.0, 1.0, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]]]);
+		assert!(p.normals.is_empty() && p.texcoords.is_empty());
+	}
+
+	#[test]
+	fn obj_invalid_faces()
+	{
+		assert!(MeshData::parse_obj("v 0 0 0\nv 1 0 0\nf 1 2\n").is_err());
+		assert!(MeshData::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n").is_err());
+		assert!(MeshData::parse_obj("v 0 0\n").is_err());
+	}
+
+	/// A triangle: float positions at 0, 16bit indices at 36 and normalized 8bit RGB colors at 42
+	const TRIANGLE_GLTF: &str = r#"{
+		"asset": { "version": "2.0" },
+		"buffers": [{ "byteLength": 51, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIA/wAAAP8AAAD/" }],
+		"bufferViews": [
+			{ "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+			{ "buffer": 0, "byteOffset": 36, "byteLength": 6 },
+			{ "buffer": 0, "byteOffset": 42, "byteLength": 9 }
+		],
+		"accessors": [
+			{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+			{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" },
+			{ "bufferView": 2, "componentType": 5121, "normalized": true, "count": 3, "type": "VEC3" }
+		],
+		"meshes": [{ "primitives": [
+			{ "attributes": { "POSITION": 0, "COLOR_0": 2 }, "indices": 1 },
+			{ "attributes": { "POSITION": 0 }, "mode": 1 }
+		] }]
+	}"#;
+
+	#[test]
+	fn gltf_embedded_buffer_and_accessors()
+	{
+		let mesh = MeshData::parse_gltf(TRIANGLE_GLTF, None, Path::new("")).unwrap();
+		// the line primitive is skipped
+		assert_eq!(mesh.primitives.len(), 1);
+		let p = &mesh.primitives[0];
+		assert_eq!(p.positions, vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+		assert_eq!(p.indices, vec![0, 1, 2]);
+		assert_eq!(p.colors, vec![vec![[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]]]);
+		assert!(p.normals.is_empty() && p.texcoords.is_empty());
+	}
+
+	#[test]
+	fn gltf_rejects_other_versions()
+	{
+		assert!(MeshData::parse_gltf(&TRIANGLE_GLTF.replace("\"2.0\"", "\"1.0\""), None, Path::new("")).is_err());
+	}
+
+	#[test]
+	fn base64_without_padding()
+	{
+		assert_eq!(decode_base64("TWFuIGlz").unwrap(), b"Man is".to_vec());
+		assert_eq!(decode_base64("YWI=").unwrap(), b"ab".to_vec());
+		assert!(decode_base64("YW*=").is_err());
+	}
+}
@@ -0,0 +1,111 @@
+//! Interlude: Multisampled render targets resolved into presentable images
+
+use interlude_vk_defs::*;
+use {EngineResult, GraphicsInterface, ImageView, ImageDescriptor2, ImagePreallocator, DeviceImages, ImageView2D};
+use {ImageSubresourceRange, ImageAspect, ImageAspectSet, ComponentMapping, SampleCount, GraphicsPipelineBuilder};
+use {AttachmentDesc, AttachmentRef, PassDesc, AttachmentClearValue, RenderPass, Framebuffer, PreciseRenderPass};
+use render_surface::RenderTargetProvider;
+
+/// Multisampled color(and depth) attachments rendered in a render pass that resolves the color into each render target.
+/// The resolved targets are left in PresentSrc layout, as `presenting_renderpass` does.
+/// Falls back to rendering into the targets directly if the device does not support multisampling.
+/// Recreate this when the render targets are recreated
+pub struct MultisampleRenderTarget
+{
+	samples: SampleCount, render_pass: RenderPass, framebuffers: Vec<Framebuffer>,
+	color_view: Option<ImageView2D>, depth_view: Option<ImageView2D>,
+	// dropped last: the views and framebuffers above refer to the images
+	#[allow(dead_code)] images: Option<DeviceImages>
+}
+impl MultisampleRenderTarget
+{
+	/// `requested_samples`(one of VK_SAMPLE_COUNT_*_BIT) is lowered to the highest count supported for the attachments
+	pub fn new(engine: &GraphicsInterface, targets: &RenderTargetProvider, requested_samples: SampleCount, with_depth: bool) -> EngineResult<Self>
+	{
+		let (format, size) = (targets.format(), targets.size());
+		let samples = engine.max_sample_count(requested_samples, with_depth);
+		if samples != requested_samples
+		{
+			warn!(target: "Interlude::Multisample", "{} samples are not supported, using {} samples instead", requested_samples, samples);
+		}
+		let depth_format = if with_depth { Some(engine.depth_format(false)?) } else { None };
+		let resolving = samples != VK_SAMPLE_COUNT_1_BIT;
+
+		let mut descs = Vec::new();
+		if resolving
+		{
+			descs.push(ImageDescriptor2::new(format, size.clone(), VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT | VK_IMAGE_USAGE_TRANSIENT_ATTACHMENT_BIT)
+				.sample_flags(samples).device_local());
+		}
+		if let Some(f) = depth_format
+		{
+			descs.push(ImageDescriptor2::new(f, size.clone(), VK_IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT | VK_IMAGE_USAGE_TRANSIENT_ATTACHMENT_BIT)
+				.sample_flags(samples).device_local());
+		}
+		let images = if descs.is_empty() { None } else { Some(ImagePreallocator::new(engine, Vec::new(), descs.iter().collect(), Vec::new()).instantiate()?.0) };
+		let view = |index: usize, format, aspect: ImageAspectSet| ImageView2D::make(&images.as_ref().unwrap().dim2()[index], format, ComponentMapping::straight(),
+			&ImageSubresourceRange { aspect, .. Default::default() });
+		let color_view = if resolving { Some(view(0, format, ImageAspect::Color.into())?) } else { None };
+		let depth_view = match depth_format
+		{
+			// attachment views of combined formats must contain both aspects
+			Some(f @ VK_FORMAT_D16_UNORM_S8_UINT) | Some(f @ VK_FORMAT_D24_UNORM_S8_UINT) | Some(f @ VK_FORMAT_D32_SFLOAT_S8_UINT) =>
+				Some(view(descs.len() - 1, f, ImageAspect::Depth | ImageAspect::Stencil)?),
+			Some(f) => Some(view(descs.len() - 1, f, ImageAspect::Depth.into())?),
+			None => None
+		};
+
+		// Attachments: [multisampled color], target, [depth]
+		let target_desc = AttachmentDesc { clear_on_load: if resolving { None } else { Some(true) }, .. AttachmentDesc::swapchain_buffer(format) };
+		let mut attachments = Vec::new();
+		if resolving
+		{
+			attachments.push(AttachmentDesc
+			{
+				format, samples, clear_on_load: Some(true),
+				initial_layout: VK_IMAGE_LAYOUT_UNDEFINED, final_layout: VK_IMAGE_LAYOUT_COLOR_ATTACHMENT_OPTIMAL, .. Default::default()
+			});
+		}
+		attachments.push(target_desc);
+		if let Some(f) = depth_format { attachments.push(AttachmentDesc { samples, .. AttachmentDesc::depth_buffer(f) }); }
+		let pass = PassDesc
+		{
+			color_attachment_indices: vec![AttachmentRef::color(0)],
+			resolved_attachment_indices: if resolving { vec![AttachmentRef::color(1)] } else { Vec::new() },
+			depth_stencil_attachment_index: depth_format.map(|_| AttachmentRef::depth_stencil(attachments.len() as u32 - 1)),
+			.. Default::default()
+		};
+		let render_pass = RenderPass::new(engine, &attachments, &[pass], &[])?;
+
+		let framebuffers = targets.render_targets().iter().map(|t|
+		{
+			let mut views: Vec<&ImageView> = Vec::with_capacity(3);
+			if let Some(ref v) = color_view { views.push(v); }
+			views.push(t);
+			if let Some(ref v) = depth_view { views.push(v); }
+			Framebuffer::new(engine, &render_pass, &views, &size, 1)
+		}).collect::<EngineResult<Vec<_>>>()?;
+		info!(target: "Interlude::Multisample", "Created multisampled render targets: {:?}, {} samples", size, samples);
+
+		Ok(MultisampleRenderTarget { samples, render_pass, framebuffers, color_view, depth_view, images })
+	}
+
+	/// Actual sample count of the attachments
+	pub fn samples(&self) -> SampleCount { self.samples }
+	pub fn render_pass(&self) -> PreciseRenderPass { PreciseRenderPass(&self.render_pass, 0) }
+	/// Framebuffers for each render target, in the same order
+	pub fn framebuffers(&self) -> &[Framebuffer] { &self.framebuffers }
+	/// Sets the rasterization samples of the pipeline. `min_sample_shading` enables sample shading(requires the sampleRateShading feature)
+	pub fn configure_pipeline<'a>(&self, builder: GraphicsPipelineBuilder<'a>, min_sample_shading: Option<f32>) -> GraphicsPipelineBuilder<'a>
+	{
+		let builder = builder.multisample(self.samples);
+		match min_sample_shading { Some(f) => builder.sample_shading(f), None => builder }
+	}
+	/// Clear values for `begin_render_pass`(the depth is cleared to 1.0)
+	pub fn clear_values(&self, color: AttachmentClearValue) -> Vec<AttachmentClearValue>
+	{
+		let mut values = if self.color_view.is_some() { vec![color.clone(), color] } else { vec![color] };
+		if self.depth_view.is_some() { values.push(AttachmentClearValue::DepthStencil(1.0, 0)); }
+		values
+	}
+}
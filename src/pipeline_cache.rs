@@ -0,0 +1,100 @@
+//! Interlude: Pipeline Cache(Reusing compiled pipelines, persisted on disk)
+
+use interlude_vk_defs::*;
+use interlude_vk_funport::*;
+use {EngineResult, AdapterInfo};
+use device::Device;
+use subsystem_layer::{NativeHandleProvider, NativeResultValueHandler};
+use std::rc::Rc;
+use std::ptr::{null, null_mut};
+use std::mem::uninitialized as reserved;
+use std::io::prelude::*;
+use std::fs::{self, File};
+use std::path::Path;
+
+/// Pipeline Cache used by all pipeline creations through GraphicsInterface
+pub struct PipelineCache(VkPipelineCache, Rc<Device>);
+impl PipelineCache
+{
+	pub(crate) fn new(device: &Rc<Device>, initial_data: &[u8]) -> EngineResult<Self>
+	{
+		let mut c = unsafe { reserved() };
+		unsafe { vkCreatePipelineCache(device.native(), &VkPipelineCacheCreateInfo
+		{
+			initialDataSize: initial_data.len() as _, pInitialData: initial_data.as_ptr() as *const _, .. Default::default()
+		}, null(), &mut c) }.make_result_with(|| PipelineCache(c, device.clone()))
+	}
+	/// Serialized data including the header
+	pub fn data(&self) -> EngineResult<Vec<u8>>
+	{
+		let mut size = 0;
+		unsafe { vkGetPipelineCacheData(self.1.native(), self.0, &mut size, null_mut()) }.into_result()?;
+		let mut data = vec![0u8; size as _];
+		unsafe { vkGetPipelineCacheData(self.1.native(), self.0, &mut size, data.as_mut_ptr() as *mut _) }.into_result()?;
+		data.truncate(size as _);
+		Ok(data)
+	}
+	/// Merges the cache stored in the file. Returns false if the file does not exist or was made by another adapter/driver(the file is left as is).
+	/// A cache is only an optimization, so failures on reading or merging are logged and also result in false
+	pub fn merge_file<P: AsRef<Path>>(&self, adapter: &AdapterInfo, path: P) -> EngineResult<bool>
+	{
+		let path = path.as_ref();
+		if !path.exists() { return Ok(false); }
+		match self.merge_file_content(adapter, path)
+		{
+			Ok(merged) => Ok(merged),
+			Err(e) =>
+			{
+				warn!(target: "Interlude::PipelineCache", "Failed to load the pipeline cache from {}: {:?}", path.display(), e);
+				Ok(false)
+			}
+		}
+	}
+	fn merge_file_content(&self, adapter: &AdapterInfo, path: &Path) -> EngineResult<bool>
+	{
+		let mut data = Vec::new();
+		File::open(path)?.read_to_end(&mut data)?;
+		if !is_compatible_cache(&data, adapter)
+		{
+			warn!(target: "Interlude::PipelineCache", "Discarding stale or corrupted pipeline cache: {}", path.display());
+			return Ok(false);
+		}
+		let loaded = PipelineCache::new(&self.1, &data)?;
+		unsafe { vkMergePipelineCaches(self.1.native(), self.0, 1, &loaded.0) }.into_result()?;
+		info!(target: "Interlude::PipelineCache", "Loaded pipeline cache({} bytes) from {}", data.len(), path.display());
+		Ok(true)
+	}
+	/// Writes the data through a temporary file, so that interrupted writes never leave broken caches
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> EngineResult<()>
+	{
+		let path = path.as_ref();
+		if let Some(d) = path.parent() { fs::create_dir_all(d)?; }
+		let data = self.data()?;
+		let temporary = path.with_extension("tmp");
+		File::create(&temporary)?.write_all(&data)?;
+		fs::rename(&temporary, path)?;
+		info!(target: "Interlude::PipelineCache", "Saved pipeline cache({} bytes) into {}", data.len(), path.display());
+		Ok(())
+	}
+}
+impl NativeHandleProvider for PipelineCache
+{
+	type NativeT = VkPipelineCache;
+	fn native(&self) -> VkPipelineCache { self.0 }
+}
+impl Drop for PipelineCache
+{
+	fn drop(&mut self) { unsafe { vkDestroyPipelineCache(self.1.native(), self.0, null()) }; }
+}
+
+/// Validates the header(VK_PIPELINE_CACHE_HEADER_VERSION_ONE, written in little-endian) against the adapter
+pub fn is_compatible_cache(data: &[u8], adapter: &AdapterInfo) -> bool
+{
+	fn u32le(b: &[u8], offs: usize) -> u32 { b[offs] as u32 | (b[offs + 1] as u32) << 8 | (b[offs + 2] as u32) << 16 | (b[offs + 3] as u32) << 24 }
+	if data.len() < 16 + VK_UUID_SIZE { return false; }
+	let header_length = u32le(data, 0) as usize;
+	header_length >= 16 + VK_UUID_SIZE && header_length <= data.len()
+		&& u32le(data, 4) == VK_PIPELINE_CACHE_HEADER_VERSION_ONE as u32
+		&& u32le(data, 8) == adapter.vendor_id && u32le(data, 12) == adapter.device_id
+		&& data[16 .. 16 + VK_UUID_SIZE] == adapter.pipeline_cache_uuid[..]
+}
@@ -0,0 +1,727 @@
+//! Interlude: SPIR-V Reflection(Deriving descriptor/pipeline layouts from shader code, and validating hand-written ones)
+
+use {EngineResult, EngineError, GraphicsInterface, ShaderStage, ShaderStageSet, Descriptor, DescriptorSetLayout, PipelineLayout, PushConstantDesc};
+use {VertexBinding, VertexAttribute, Format, FormatType, PackedPixelOrder};
+use std::collections::{HashMap, BTreeMap};
+use std::borrow::Cow;
+use std::ops::Range;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+/// Types nested deeper than this or containing themselves are treated as empty(malformed code may contain cyclic type references)
+const MAX_TYPE_DEPTH: u32 = 32;
+
+// Opcodes
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_SPEC_CONSTANT_TRUE: u32 = 48;
+const OP_SPEC_CONSTANT_FALSE: u32 = 49;
+const OP_SPEC_CONSTANT: u32 = 50;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+// Decorations
+const DECORATION_SPEC_ID: u32 = 1;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILTIN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+// Storage Classes
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+// Image Dimensions
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// Type of a descriptor declared in shaders
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReflectedDescriptorType
+{
+	Sampler, CombinedImageSampler, SampledImage, StorageImage, UniformTexelBuffer, StorageTexelBuffer,
+	UniformBuffer, StorageBuffer, InputAttachment
+}
+impl ReflectedDescriptorType
+{
+	/// Descriptor for the layout(None if the type is not supported by `Descriptor`)
+	pub fn into_descriptor(self, count: u32, stages: ShaderStageSet) -> Option<Descriptor>
+	{
+		match self
+		{
+			ReflectedDescriptorType::UniformBuffer => Some(Descriptor::Uniform(count, stages)),
+			ReflectedDescriptorType::StorageBuffer => Some(Descriptor::Storage(count, stages)),
+			ReflectedDescriptorType::CombinedImageSampler => Some(Descriptor::CombinedSampler(count, stages)),
+			ReflectedDescriptorType::InputAttachment => Some(Descriptor::InputAttachment(count, stages)),
+			_ => None
+		}
+	}
+	fn matches(self, desc: &Descriptor) -> bool
+	{
+		match (self, *desc)
+		{
+			(ReflectedDescriptorType::UniformBuffer, Descriptor::Uniform(_, _)) | (ReflectedDescriptorType::StorageBuffer, Descriptor::Storage(_, _)) |
+			(ReflectedDescriptorType::CombinedImageSampler, Descriptor::CombinedSampler(_, _)) |
+			(ReflectedDescriptorType::InputAttachment, Descriptor::InputAttachment(_, _)) => true,
+			_ => false
+		}
+	}
+}
+/// A resource variable bound through descriptor sets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReflectedDescriptor
+{
+	pub set: u32, pub binding: u32, pub ty: ReflectedDescriptorType,
+	/// Number of array elements(0 for runtime-sized arrays)
+	pub count: u32,
+	pub name: Option<String>
+}
+/// The push constant block, in bytes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReflectedPushConstants { pub range: Range<u32>, pub name: Option<String> }
+/// A vertex shader input. Matrices and arrays consume a location per column/element
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReflectedVertexInput { pub location: u32, pub format: Format, pub name: Option<String> }
+/// Scalar types allowed for specialization constants
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SpecConstantType { Bool, Int, Uint, Float, Double }
+impl SpecConstantType
+{
+	/// Size of the value in specialization data(booleans are passed as VkBool32)
+	pub fn size(&self) -> usize { match *self { SpecConstantType::Double => 8, _ => 4 } }
+}
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReflectedSpecConstant { pub id: u32, pub ty: SpecConstantType, pub name: Option<String> }
+
+/// Interface of a shader module, read from its SPIR-V code
+#[derive(Clone, Debug)]
+pub struct ShaderReflection
+{
+	/// Stages of all the entry points
+	pub stages: ShaderStageSet,
+	pub entry_points: Vec<(ShaderStage, String)>,
+	pub descriptors: Vec<ReflectedDescriptor>,
+	pub push_constants: Option<ReflectedPushConstants>,
+	/// Inputs of the vertex entry point, ordered by location
+	pub vertex_inputs: Vec<ReflectedVertexInput>,
+	pub specialization_constants: Vec<ReflectedSpecConstant>
+}
+
+#[derive(Clone)]
+enum TypeInfo
+{
+	Bool, Int(u32, bool), Float(u32), Vector(u32, u32), Matrix(u32, u32), Image { dim: u32, sampled: u32 },
+	Sampler, SampledImage, Array(u32, u32), RuntimeArray(u32), Struct(Vec<u32>), Pointer(u32, u32)
+}
+struct EntryPoint { model: u32, name: String, interface: Vec<u32> }
+/// Instructions collected from the module
+#[derive(Default)]
+struct ModuleInfo
+{
+	names: HashMap<u32, String>, decorations: HashMap<u32, Vec<(u32, u32)>>, member_decorations: HashMap<(u32, u32), Vec<(u32, u32)>>,
+	types: HashMap<u32, TypeInfo>, constants: HashMap<u32, u32>, spec_constants: Vec<(u32, u32)>,
+	variables: Vec<(u32, u32, u32)>, entry_points: Vec<EntryPoint>
+}
+
+impl ShaderReflection
+{
+	pub fn parse(code: &[u32]) -> EngineResult<Self>
+	{
+		const INVALID: EngineError = EngineError::GenericError("Invalid SPIR-V code");
+		if code.len() < 5 || code[0] != SPIRV_MAGIC { return Err(INVALID); }
+		let mut module = ModuleInfo::default();
+		let mut offset = 5;
+		while offset < code.len()
+		{
+			let count = (code[offset] >> 16) as usize;
+			if count == 0 || offset + count > code.len() { return Err(INVALID); }
+			module.instruction(code[offset] & 0xffff, &code[offset .. offset + count]);
+			offset += count;
+		}
+		Ok(module.into_reflection())
+	}
+	/// Parses SPIR-V code stored in little-endian
+	pub fn from_bytes(bytes: &[u8]) -> EngineResult<Self>
+	{
+		if bytes.len() % 4 != 0 { return Err(EngineError::GenericError("SPIR-V code size must be a multiple of 4")); }
+		Self::parse(&bytes.chunks(4).map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24).collect::<Vec<_>>())
+	}
+
+	pub fn specialization_constant(&self, id: u32) -> Option<&ReflectedSpecConstant> { self.specialization_constants.iter().find(|c| c.id == id) }
+	pub fn specialization_constant_by_name(&self, name: &str) -> Option<&ReflectedSpecConstant>
+	{
+		self.specialization_constants.iter().find(|c| c.name.as_ref().map_or(false, |n| n == name))
+	}
+
+	/// Attributes for vertices stored interleaved in a single binding, in location order.
+	/// Locations must be contiguous from 0, since attributes are located by their index
+	pub fn vertex_attributes(&self) -> EngineResult<(VertexBinding, Vec<VertexAttribute>)>
+	{
+		let mut offset = 0;
+		let mut attributes = Vec::with_capacity(self.vertex_inputs.len());
+		for (i, input) in self.vertex_inputs.iter().enumerate()
+		{
+			if input.location != i as u32 { return Err(EngineError::GenericError("Vertex input locations are not contiguous")); }
+			attributes.push(VertexAttribute(0, input.format.clone(), offset));
+			offset += format_size(&input.format);
+		}
+		Ok((VertexBinding::PerVertex(offset), attributes))
+	}
+	/// Checks that each input has an attribute with the same numeric type(float, signed or unsigned integer)
+	pub fn validate_vertex_attributes(&self, attributes: &[VertexAttribute]) -> Vec<LayoutMismatch>
+	{
+		self.vertex_inputs.iter().filter_map(|input| match attributes.get(input.location as usize)
+		{
+			None => Some(LayoutMismatch::MissingVertexInput(input.location)),
+			Some(&VertexAttribute(_, ref f, _)) if numeric_class(f) != numeric_class(&input.format) =>
+				Some(LayoutMismatch::VertexInputType { location: input.location, shader: input.format.clone(), pipeline: f.clone() }),
+			_ => None
+		}).collect()
+	}
+}
+
+impl ModuleInfo
+{
+	fn instruction(&mut self, opcode: u32, ins: &[u32])
+	{
+		let w = |i: usize| ins.get(i).cloned().unwrap_or(0);
+		match opcode
+		{
+			OP_NAME if ins.len() >= 3 => { self.names.insert(w(1), literal_string(&ins[2..]).0); },
+			OP_ENTRY_POINT if ins.len() >= 4 =>
+			{
+				let (name, words) = literal_string(&ins[3..]);
+				self.entry_points.push(EntryPoint { model: w(1), name, interface: ins[3 + words ..].to_owned() });
+			},
+			OP_DECORATE => self.decorations.entry(w(1)).or_insert_with(Vec::new).push((w(2), w(3))),
+			OP_MEMBER_DECORATE => self.member_decorations.entry((w(1), w(2))).or_insert_with(Vec::new).push((w(3), w(4))),
+			OP_TYPE_BOOL => { self.types.insert(w(1), TypeInfo::Bool); },
+			OP_TYPE_INT => { self.types.insert(w(1), TypeInfo::Int(w(2), w(3) != 0)); },
+			OP_TYPE_FLOAT => { self.types.insert(w(1), TypeInfo::Float(w(2))); },
+			OP_TYPE_VECTOR => { self.types.insert(w(1), TypeInfo::Vector(w(2), w(3))); },
+			OP_TYPE_MATRIX => { self.types.insert(w(1), TypeInfo::Matrix(w(2), w(3))); },
+			OP_TYPE_IMAGE => { self.types.insert(w(1), TypeInfo::Image { dim: w(3), sampled: w(7) }); },
+			OP_TYPE_SAMPLER => { self.types.insert(w(1), TypeInfo::Sampler); },
+			OP_TYPE_SAMPLED_IMAGE => { self.types.insert(w(1), TypeInfo::SampledImage); },
+			OP_TYPE_ARRAY => { self.types.insert(w(1), TypeInfo::Array(w(2), w(3))); },
+			OP_TYPE_RUNTIME_ARRAY => { self.types.insert(w(1), TypeInfo::RuntimeArray(w(2))); },
+			OP_TYPE_STRUCT => { self.types.insert(w(1), TypeInfo::Struct(ins[2..].to_owned())); },
+			OP_TYPE_POINTER => { self.types.insert(w(1), TypeInfo::Pointer(w(2), w(3))); },
+			OP_CONSTANT => { self.constants.insert(w(2), w(3)); },
+			OP_SPEC_CONSTANT_TRUE | OP_SPEC_CONSTANT_FALSE | OP_SPEC_CONSTANT => self.spec_constants.push((w(2), w(1))),
+			OP_VARIABLE => self.variables.push((w(2), w(1), w(3))),
+			_ => ()
+		}
+	}
+	fn decoration(&self, id: u32, decoration: u32) -> Option<u32>
+	{
+		self.decorations.get(&id).and_then(|ds| ds.iter().find(|&&(d, _)| d == decoration)).map(|&(_, v)| v)
+	}
+	fn member_decoration(&self, id: u32, member: u32, decoration: u32) -> Option<u32>
+	{
+		self.member_decorations.get(&(id, member)).and_then(|ds| ds.iter().find(|&&(d, _)| d == decoration)).map(|&(_, v)| v)
+	}
+	fn name(&self, id: u32) -> Option<String> { self.names.get(&id).and_then(|n| if n.is_empty() { None } else { Some(n.clone()) }) }
+
+	/// Size in bytes, with explicit layout decorations if available
+	fn type_size(&self, ty: u32) -> u32 { self.nested_type_size(ty, &[]) }
+	fn nested_type_size(&self, ty: u32, outer: &[u32]) -> u32
+	{
+		if outer.len() > MAX_TYPE_DEPTH as usize || outer.contains(&ty) { return 0; }
+		let outer = [outer, &[ty][..]].concat();
+		match self.types.get(&ty)
+		{
+			Some(&TypeInfo::Bool) => 4,
+			Some(&TypeInfo::Int(width, _)) | Some(&TypeInfo::Float(width)) => width / 8,
+			Some(&TypeInfo::Vector(component, count)) => self.nested_type_size(component, &outer).saturating_mul(count),
+			Some(&TypeInfo::Matrix(column, count)) => self.nested_type_size(column, &outer).saturating_mul(count),
+			Some(&TypeInfo::Array(element, length)) =>
+			{
+				let stride = self.decoration(ty, DECORATION_ARRAY_STRIDE).unwrap_or_else(|| self.nested_type_size(element, &outer));
+				stride.saturating_mul(self.constants.get(&length).cloned().unwrap_or(0))
+			},
+			Some(&TypeInfo::Struct(ref members)) => members.iter().enumerate().map(|(i, &m)|
+			{
+				let offset = self.member_decoration(ty, i as u32, DECORATION_OFFSET).unwrap_or(0);
+				let size = match (self.types.get(&m), self.member_decoration(ty, i as u32, DECORATION_MATRIX_STRIDE))
+				{
+					(Some(&TypeInfo::Matrix(_, count)), Some(stride)) => stride.saturating_mul(count),
+					_ => self.nested_type_size(m, &outer)
+				};
+				offset.saturating_add(size)
+			}).max().unwrap_or(0),
+			_ => 0
+		}
+	}
+	/// Byte range of the members
+	fn struct_range(&self, ty: u32) -> Range<u32>
+	{
+		let members = match self.types.get(&ty) { Some(&TypeInfo::Struct(ref m)) => m.len() as u32, _ => 0 };
+		let start = (0 .. members).filter_map(|i| self.member_decoration(ty, i, DECORATION_OFFSET)).min().unwrap_or(0);
+		start .. self.type_size(ty)
+	}
+	/// Element type and count of (possibly nested) arrays
+	fn unwrap_array(&self, ty: u32) -> (u32, u32) { self.unwrap_nested_array(ty, &[]) }
+	fn unwrap_nested_array(&self, ty: u32, outer: &[u32]) -> (u32, u32)
+	{
+		if outer.len() > MAX_TYPE_DEPTH as usize || outer.contains(&ty) { return (ty, 1); }
+		let outer = [outer, &[ty][..]].concat();
+		match self.types.get(&ty)
+		{
+			Some(&TypeInfo::Array(element, length)) =>
+			{
+				let (t, c) = self.unwrap_nested_array(element, &outer);
+				(t, c.saturating_mul(self.constants.get(&length).cloned().unwrap_or(1)))
+			},
+			Some(&TypeInfo::RuntimeArray(element)) => (self.unwrap_nested_array(element, &outer).0, 0),
+			_ => (ty, 1)
+		}
+	}
+	fn descriptor_type(&self, storage: u32, ty: u32) -> Option<ReflectedDescriptorType>
+	{
+		match (storage, self.types.get(&ty))
+		{
+			(STORAGE_UNIFORM_CONSTANT, Some(&TypeInfo::SampledImage)) => Some(ReflectedDescriptorType::CombinedImageSampler),
+			(STORAGE_UNIFORM_CONSTANT, Some(&TypeInfo::Sampler)) => Some(ReflectedDescriptorType::Sampler),
+			(STORAGE_UNIFORM_CONSTANT, Some(&TypeInfo::Image { dim, sampled })) => Some(match (dim, sampled)
+			{
+				(DIM_SUBPASS_DATA, _) => ReflectedDescriptorType::InputAttachment,
+				(DIM_BUFFER, 2) => ReflectedDescriptorType::StorageTexelBuffer,
+				(DIM_BUFFER, _) => ReflectedDescriptorType::UniformTexelBuffer,
+				(_, 2) => ReflectedDescriptorType::StorageImage,
+				_ => ReflectedDescriptorType::SampledImage
+			}),
+			(STORAGE_UNIFORM, Some(&TypeInfo::Struct(_))) => Some(if self.decoration(ty, DECORATION_BUFFER_BLOCK).is_some()
+				{ ReflectedDescriptorType::StorageBuffer } else { ReflectedDescriptorType::UniformBuffer }),
+			(STORAGE_STORAGE_BUFFER, _) => Some(ReflectedDescriptorType::StorageBuffer),
+			_ => None
+		}
+	}
+	/// Formats per location
+	fn input_formats(&self, ty: u32) -> Vec<Format> { self.nested_input_formats(ty, &[]) }
+	fn nested_input_formats(&self, ty: u32, outer: &[u32]) -> Vec<Format>
+	{
+		if outer.len() > MAX_TYPE_DEPTH as usize || outer.contains(&ty) { return Vec::new(); }
+		let outer = [outer, &[ty][..]].concat();
+		let scalar = |t: u32, count: usize| match self.types.get(&t)
+		{
+			Some(&TypeInfo::Int(width, signed)) => Some((width as usize, if signed { FormatType::Int } else { FormatType::UInt }, count)),
+			Some(&TypeInfo::Float(width)) => Some((width as usize, FormatType::Float, count)),
+			_ => None
+		};
+		let order = |count: usize| [PackedPixelOrder::R, PackedPixelOrder::RG, PackedPixelOrder::RGB, PackedPixelOrder::RGBA][count.max(1).min(4) - 1];
+		match self.types.get(&ty)
+		{
+			Some(&TypeInfo::Vector(component, count)) => scalar(component, count as usize).map(|(w, t, c)| vec![Format::Component(w, order(c), t)]).unwrap_or_default(),
+			Some(&TypeInfo::Matrix(column, count)) =>
+			{
+				let columns = self.nested_input_formats(column, &outer);
+				(0 .. count).flat_map(|_| columns.iter().cloned()).collect()
+			},
+			Some(&TypeInfo::Array(element, length)) =>
+			{
+				let elements = self.nested_input_formats(element, &outer);
+				(0 .. self.constants.get(&length).cloned().unwrap_or(0)).flat_map(|_| elements.iter().cloned()).collect()
+			},
+			_ => scalar(ty, 1).map(|(w, t, c)| vec![Format::Component(w, order(c), t)]).unwrap_or_default()
+		}
+	}
+
+	fn into_reflection(self) -> ShaderReflection
+	{
+		let mut stages = ShaderStageSet::empty();
+		let mut entry_points = Vec::new();
+		for e in &self.entry_points
+		{
+			let stage = match e.model
+			{
+				0 => ShaderStage::Vertex, 1 => ShaderStage::TessControl, 2 => ShaderStage::TessEvaluation,
+				3 => ShaderStage::Geometry, 4 => ShaderStage::Fragment, 5 => ShaderStage::Compute, _ => continue
+			};
+			stages |= stage;
+			entry_points.push((stage, e.name.clone()));
+		}
+
+		let mut descriptors = Vec::new();
+		let mut push_constants = None;
+		for &(id, ptr, storage) in &self.variables
+		{
+			let pointee = match self.types.get(&ptr) { Some(&TypeInfo::Pointer(_, t)) => t, _ => continue };
+			if storage == STORAGE_PUSH_CONSTANT
+			{
+				push_constants = Some(ReflectedPushConstants { range: self.struct_range(pointee), name: self.name(id).or_else(|| self.name(pointee)) });
+				continue;
+			}
+			let (ty, count) = self.unwrap_array(pointee);
+			if let (Some(binding), Some(ty)) = (self.decoration(id, DECORATION_BINDING), self.descriptor_type(storage, ty))
+			{
+				descriptors.push(ReflectedDescriptor
+				{
+					set: self.decoration(id, DECORATION_DESCRIPTOR_SET).unwrap_or(0), binding, ty, count,
+					name: self.name(id).or_else(|| self.name(pointee))
+				});
+			}
+		}
+		descriptors.sort_by_key(|d| (d.set, d.binding));
+
+		let mut vertex_inputs = Vec::new();
+		for e in self.entry_points.iter().filter(|e| e.model == 0)
+		{
+			for &(id, ptr, storage) in self.variables.iter().filter(|&&(id, _, _)| e.interface.contains(&id))
+			{
+				if storage != STORAGE_INPUT || self.decoration(id, DECORATION_BUILTIN).is_some() { continue; }
+				let (location, pointee) = match (self.decoration(id, DECORATION_LOCATION), self.types.get(&ptr))
+				{
+					(Some(l), Some(&TypeInfo::Pointer(_, t))) => (l, t), _ => continue
+				};
+				for (i, format) in self.input_formats(pointee).into_iter().enumerate()
+				{
+					vertex_inputs.push(ReflectedVertexInput { location: location + i as u32, format, name: self.name(id) });
+				}
+			}
+		}
+		vertex_inputs.sort_by_key(|v| v.location);
+		vertex_inputs.dedup_by_key(|v| v.location);
+
+		let specialization_constants = self.spec_constants.iter().filter_map(|&(id, ty)|
+		{
+			let sc_type = match self.types.get(&ty)
+			{
+				Some(&TypeInfo::Bool) => SpecConstantType::Bool,
+				Some(&TypeInfo::Int(32, true)) => SpecConstantType::Int,
+				Some(&TypeInfo::Int(32, false)) => SpecConstantType::Uint,
+				Some(&TypeInfo::Float(32)) => SpecConstantType::Float,
+				Some(&TypeInfo::Float(64)) => SpecConstantType::Double,
+				_ => return None
+			};
+			self.decoration(id, DECORATION_SPEC_ID).map(|sid| ReflectedSpecConstant { id: sid, ty: sc_type, name: self.name(id) })
+		}).collect();
+
+		ShaderReflection { stages, entry_points, descriptors, push_constants, vertex_inputs, specialization_constants }
+	}
+}
+
+/// Decodes a nul-terminated UTF-8 string packed in words. Returns the string and the number of consumed words
+fn literal_string(words: &[u32]) -> (String, usize)
+{
+	let mut bytes = Vec::new();
+	for (i, &w) in words.iter().enumerate()
+	{
+		for b in 0 .. 4
+		{
+			let c = (w >> (b * 8)) as u8;
+			if c == 0 { return (String::from_utf8_lossy(&bytes).into_owned(), i + 1); }
+			bytes.push(c);
+		}
+	}
+	(String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+fn format_size(format: &Format) -> u32
+{
+	match *format
+	{
+		Format::Component(bits, order, _) => (bits / 8) as u32 * match order
+		{
+			PackedPixelOrder::R => 1, PackedPixelOrder::RG => 2, PackedPixelOrder::RGB | PackedPixelOrder::BGR => 3, _ => 4
+		},
+		_ => 0
+	}
+}
+/// 0: floating-point(including normalized and scaled), 1: signed integer, 2: unsigned integer
+fn numeric_class(format: &Format) -> u8
+{
+	let ty = match *format
+	{
+		Format::Component(_, _, t) | Format::Data(_, t) | Format::A2RGB30(t) | Format::A2BGR30(t) => t,
+		_ => FormatType::Float
+	};
+	match ty { FormatType::Int => 1, FormatType::UInt => 2, _ => 0 }
+}
+
+/// Difference between a shader interface and hand-written layouts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LayoutMismatch
+{
+	MissingSet(u32),
+	MissingBinding { set: u32, binding: u32 },
+	DescriptorType { set: u32, binding: u32, shader: ReflectedDescriptorType, layout: Descriptor },
+	DescriptorCount { set: u32, binding: u32, shader: u32, layout: u32 },
+	/// The descriptor is not visible to all of the `required` stages
+	StageVisibility { set: u32, binding: u32, required: ShaderStageSet },
+	/// No push constant range for the stages covers the block
+	PushConstantRange { stages: ShaderStageSet, range: Range<u32> },
+	MissingVertexInput(u32),
+	VertexInputType { location: u32, shader: Format, pipeline: Format }
+}
+
+/// Interface of all the shader stages in a pipeline
+pub struct PipelineReflection
+{
+	/// set -> binding -> (type, count, visible stages)
+	descriptors: BTreeMap<u32, BTreeMap<u32, (ReflectedDescriptorType, u32, ShaderStageSet)>>,
+	push_constants: Vec<(ShaderStageSet, Range<u32>)>
+}
+impl PipelineReflection
+{
+	/// Merges descriptors declared in multiple stages. Fails if their types or counts differ between stages
+	pub fn new(shaders: &[&ShaderReflection]) -> EngineResult<Self>
+	{
+		let mut descriptors = BTreeMap::new();
+		for s in shaders
+		{
+			for d in &s.descriptors
+			{
+				let set = descriptors.entry(d.set).or_insert_with(BTreeMap::new);
+				let entry = set.entry(d.binding).or_insert((d.ty, d.count, ShaderStageSet::empty()));
+				if entry.0 != d.ty || entry.1 != d.count
+				{
+					return Err(EngineError::GenericError("Conflicting declarations of a descriptor between shader stages"));
+				}
+				entry.2 |= s.stages;
+			}
+		}
+		let push_constants = shaders.iter().filter_map(|s| s.push_constants.as_ref().map(|p| (s.stages, p.range.clone()))).collect();
+		Ok(PipelineReflection { descriptors, push_constants })
+	}
+
+	/// Descriptors per set, indexed by binding. Unused bindings are filled with empty(0 count) descriptors
+	pub fn descriptor_sets(&self) -> EngineResult<Vec<Vec<Descriptor>>>
+	{
+		let set_count = self.descriptors.keys().next_back().map_or(0, |&s| s + 1);
+		(0 .. set_count).map(|set| match self.descriptors.get(&set)
+		{
+			None => Ok(Vec::new()),
+			Some(bindings) =>
+			{
+				let binding_count = bindings.keys().next_back().map_or(0, |&b| b + 1);
+				(0 .. binding_count).map(|b| match bindings.get(&b)
+				{
+					None => Ok(Descriptor::Uniform(0, ShaderStageSet::empty())),
+					Some(&(_, 0, _)) => Err(EngineError::GenericError("Runtime-sized descriptor arrays are not supported for generating layouts")),
+					Some(&(ty, count, stages)) => ty.into_descriptor(count, stages)
+						.ok_or(EngineError::GenericError("The descriptor type is not supported for generating layouts"))
+				}).collect::<EngineResult<Vec<_>>>()
+			}
+		}).collect()
+	}
+	/// A range covering the push constant blocks of all stages
+	pub fn push_constant_range(&self) -> Option<PushConstantDesc>
+	{
+		self.push_constants.iter().fold(None, |acc: Option<PushConstantDesc>, &(stages, ref r)| Some(match acc
+		{
+			None => PushConstantDesc(stages, r.clone()),
+			Some(PushConstantDesc(s, a)) => PushConstantDesc(s | stages, a.start.min(r.start) .. a.end.max(r.end))
+		}))
+	}
+	pub fn descriptor_set_layouts(&self, engine: &GraphicsInterface) -> EngineResult<Vec<DescriptorSetLayout>>
+	{
+		self.descriptor_sets()?.into_iter().map(|ds| DescriptorSetLayout::new(engine, Cow::Owned(ds))).collect()
+	}
+	/// Creates the descriptor set layouts and the pipeline layout using them
+	pub fn pipeline_layout(&self, engine: &GraphicsInterface) -> EngineResult<(Vec<DescriptorSetLayout>, PipelineLayout)>
+	{
+		let set_layouts = self.descriptor_set_layouts(engine)?;
+		let push_constants = self.push_constant_range();
+		let layout = PipelineLayout::new(engine, &set_layouts.iter().collect::<Vec<_>>(), &push_constants.iter().collect::<Vec<_>>())?;
+		Ok((set_layouts, layout))
+	}
+
+	/// Checks hand-written layouts against the shaders. Bindings not used by the shaders are not checked
+	pub fn validate(&self, set_layouts: &[&DescriptorSetLayout], push_constants: &[&PushConstantDesc]) -> Vec<LayoutMismatch>
+	{
+		let mut mismatches = Vec::new();
+		for (&set, bindings) in &self.descriptors
+		{
+			let layout = match set_layouts.get(set as usize) { Some(l) => l.descriptors(), None => { mismatches.push(LayoutMismatch::MissingSet(set)); continue; } };
+			for (&binding, &(ty, count, stages)) in bindings
+			{
+				let desc = match layout.get(binding as usize) { Some(d) => *d, None => { mismatches.push(LayoutMismatch::MissingBinding { set, binding }); continue; } };
+				if !ty.matches(&desc) { mismatches.push(LayoutMismatch::DescriptorType { set, binding, shader: ty, layout: desc }); continue; }
+				let (layout_count, layout_stages) = match desc
+				{
+					Descriptor::Uniform(c, s) | Descriptor::Storage(c, s) | Descriptor::CombinedSampler(c, s) | Descriptor::InputAttachment(c, s) => (c, s)
+				};
+				if layout_count < count { mismatches.push(LayoutMismatch::DescriptorCount { set, binding, shader: count, layout: layout_count }); }
+				if !layout_stages.contains(stages) { mismatches.push(LayoutMismatch::StageVisibility { set, binding, required: stages }); }
+			}
+		}
+		for &(stages, ref range) in &self.push_constants
+		{
+			let covered = push_constants.iter().any(|&&PushConstantDesc(s, ref r)| s.contains(stages) && r.start <= range.start && range.end <= r.end);
+			if !covered { mismatches.push(LayoutMismatch::PushConstantRange { stages, range: range.clone() }); }
+		}
+		mismatches
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use {ShaderStage, VertexBinding, VertexAttribute, Format, FormatType, PackedPixelOrder};
+
+	/// An instruction with the word count packed into the opcode word
+	fn op(opcode: u32, operands: &[u32]) -> Vec<u32>
+	{
+		let mut words = vec![(operands.len() as u32 + 1) << 16 | opcode];
+		words.extend_from_slice(operands);
+		words
+	}
+	/// A nul-terminated literal string padded to words
+	fn string(s: &str) -> Vec<u32>
+	{
+		let mut bytes = s.as_bytes().to_owned();
+		bytes.push(0);
+		while bytes.len() % 4 != 0 { bytes.push(0); }
+		bytes.chunks(4).map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24).collect()
+	}
+	fn module(instructions: Vec<Vec<u32>>) -> Vec<u32>
+	{
+		let mut code = vec![SPIRV_MAGIC, 0x0001_0000, 0, 64, 0];
+		for i in instructions { code.extend(i); }
+		code
+	}
+	fn name(id: u32, n: &str) -> Vec<u32> { op(OP_NAME, &[&[id][..], &string(n)[..]].concat()) }
+
+	#[test]
+	fn descriptors_with_set_and_binding()
+	{
+		let code = module(vec![
+			name(4, "ubo"),
+			op(OP_DECORATE, &[4, DECORATION_DESCRIPTOR_SET, 1]), op(OP_DECORATE, &[4, DECORATION_BINDING, 2]),
+			op(OP_DECORATE, &[10, DECORATION_DESCRIPTOR_SET, 0]), op(OP_DECORATE, &[10, DECORATION_BINDING, 0]),
+			op(OP_TYPE_FLOAT, &[1, 32]),
+			op(OP_TYPE_STRUCT, &[2, 1]),
+			op(OP_TYPE_POINTER, &[3, STORAGE_UNIFORM, 2]),
+			op(OP_VARIABLE, &[3, 4, STORAGE_UNIFORM]),
+			// sampler2D textures[4]
+			op(OP_TYPE_IMAGE, &[5, 1, 1, 0, 0, 0, 1, 0]),
+			op(OP_TYPE_SAMPLED_IMAGE, &[6, 5]),
+			op(OP_TYPE_INT, &[11, 32, 0]),
+			op(OP_CONSTANT, &[11, 7, 4]),
+			op(OP_TYPE_ARRAY, &[8, 6, 7]),
+			op(OP_TYPE_POINTER, &[9, STORAGE_UNIFORM_CONSTANT, 8]),
+			op(OP_VARIABLE, &[9, 10, STORAGE_UNIFORM_CONSTANT])
+		]);
+		let r = ShaderReflection::parse(&code).unwrap();
+		assert_eq!(r.descriptors, vec![
+			ReflectedDescriptor { set: 0, binding: 0, ty: ReflectedDescriptorType::CombinedImageSampler, count: 4, name: None },
+			ReflectedDescriptor { set: 1, binding: 2, ty: ReflectedDescriptorType::UniformBuffer, count: 1, name: Some("ubo".to_owned()) }
+		]);
+	}
+
+	#[test]
+	fn push_constant_block()
+	{
+		let code = module(vec![
+			name(3, "Params"),
+			op(OP_MEMBER_DECORATE, &[3, 0, DECORATION_OFFSET, 16]), op(OP_MEMBER_DECORATE, &[3, 1, DECORATION_OFFSET, 32]),
+			op(OP_TYPE_FLOAT, &[1, 32]),
+			op(OP_TYPE_VECTOR, &[2, 1, 4]),
+			op(OP_TYPE_STRUCT, &[3, 2, 1]),
+			op(OP_TYPE_POINTER, &[4, STORAGE_PUSH_CONSTANT, 3]),
+			op(OP_VARIABLE, &[4, 5, STORAGE_PUSH_CONSTANT])
+		]);
+		let r = ShaderReflection::parse(&code).unwrap();
+		assert_eq!(r.push_constants, Some(ReflectedPushConstants { range: 16 .. 36, name: Some("Params".to_owned()) }));
+		assert!(r.descriptors.is_empty());
+	}
+
+	#[test]
+	fn vertex_inputs_skip_builtins()
+	{
+		let code = module(vec![
+			op(OP_ENTRY_POINT, &[&[0, 20][..], &string("main")[..], &[8, 9, 10][..]].concat()),
+			name(8, "pos"),
+			op(OP_DECORATE, &[8, DECORATION_LOCATION, 0]), op(OP_DECORATE, &[9, DECORATION_LOCATION, 1]),
+			op(OP_DECORATE, &[10, DECORATION_BUILTIN, 42]),
+			op(OP_TYPE_FLOAT, &[1, 32]),
+			op(OP_TYPE_VECTOR, &[2, 1, 3]),
+			op(OP_TYPE_VECTOR, &[3, 1, 2]),
+			op(OP_TYPE_INT, &[4, 32, 1]),
+			op(OP_TYPE_POINTER, &[5, STORAGE_INPUT, 2]),
+			op(OP_TYPE_POINTER, &[6, STORAGE_INPUT, 3]),
+			op(OP_TYPE_POINTER, &[7, STORAGE_INPUT, 4]),
+			op(OP_VARIABLE, &[5, 8, STORAGE_INPUT]),
+			op(OP_VARIABLE, &[6, 9, STORAGE_INPUT]),
+			op(OP_VARIABLE, &[7, 10, STORAGE_INPUT])
+		]);
+		let r = ShaderReflection::parse(&code).unwrap();
+		assert_eq!(r.entry_points, vec![(ShaderStage::Vertex, "main".to_owned())]);
+		let (vec3, vec2) = (Format::Component(32, PackedPixelOrder::RGB, FormatType::Float), Format::Component(32, PackedPixelOrder::RG, FormatType::Float));
+		assert_eq!(r.vertex_inputs, vec![
+			ReflectedVertexInput { location: 0, format: vec3.clone(), name: Some("pos".to_owned()) },
+			ReflectedVertexInput { location: 1, format: vec2.clone(), name: None }
+		]);
+		let (binding, attributes) = r.vertex_attributes().unwrap();
+		assert_eq!(binding, VertexBinding::PerVertex(20));
+		assert_eq!(attributes, vec![VertexAttribute(0, vec3, 0), VertexAttribute(0, vec2, 12)]);
+	}
+
+	#[test]
+	fn specialization_constants()
+	{
+		let code = module(vec![
+			name(4, "count"),
+			op(OP_DECORATE, &[3, DECORATION_SPEC_ID, 5]), op(OP_DECORATE, &[4, DECORATION_SPEC_ID, 7]),
+			op(OP_TYPE_BOOL, &[1]),
+			op(OP_TYPE_INT, &[2, 32, 0]),
+			op(OP_SPEC_CONSTANT_TRUE, &[1, 3]),
+			op(OP_SPEC_CONSTANT, &[2, 4, 16])
+		]);
+		let r = ShaderReflection::parse(&code).unwrap();
+		assert_eq!(r.specialization_constants, vec![
+			ReflectedSpecConstant { id: 5, ty: SpecConstantType::Bool, name: None },
+			ReflectedSpecConstant { id: 7, ty: SpecConstantType::Uint, name: Some("count".to_owned()) }
+		]);
+		assert_eq!(r.specialization_constant_by_name("count").map(|c| c.id), Some(7));
+	}
+
+	#[test]
+	fn cyclic_types_terminate()
+	{
+		let code = module(vec![
+			op(OP_ENTRY_POINT, &[&[0, 20][..], &string("main")[..], &[6][..]].concat()),
+			op(OP_DECORATE, &[4, DECORATION_BINDING, 0]), op(OP_DECORATE, &[6, DECORATION_LOCATION, 0]),
+			// a struct containing itself, an array of itself and a matrix of itself
+			op(OP_TYPE_STRUCT, &[1, 1]),
+			op(OP_TYPE_POINTER, &[2, STORAGE_PUSH_CONSTANT, 1]),
+			op(OP_VARIABLE, &[2, 3, STORAGE_PUSH_CONSTANT]),
+			op(OP_TYPE_ARRAY, &[7, 7, 7]),
+			op(OP_TYPE_POINTER, &[8, STORAGE_UNIFORM_CONSTANT, 7]),
+			op(OP_VARIABLE, &[8, 4, STORAGE_UNIFORM_CONSTANT]),
+			op(OP_TYPE_MATRIX, &[9, 9, 2]),
+			op(OP_TYPE_POINTER, &[5, STORAGE_INPUT, 9]),
+			op(OP_VARIABLE, &[5, 6, STORAGE_INPUT])
+		]);
+		let r = ShaderReflection::parse(&code).unwrap();
+		assert_eq!(r.push_constants.map(|p| p.range), Some(0 .. 0));
+		assert!(r.descriptors.is_empty());
+		assert!(r.vertex_inputs.is_empty());
+	}
+
+	#[test]
+	fn rejects_invalid_code()
+	{
+		assert!(ShaderReflection::parse(&[0, 0, 0, 0, 0]).is_err());
+		// instruction overrunning the code
+		assert!(ShaderReflection::parse(&module(vec![vec![4 << 16 | OP_NAME, 1]])).is_err());
+	}
+}
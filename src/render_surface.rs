@@ -1,12 +1,13 @@
 // Interlude: Window and RenderWindow(Traits Provider)
 
 use std::rc::Rc;
-use {EngineResult, EngineError, GraphicsInterface, QueueFence, ImageView, ImageResource};
+use {EngineResult, EngineError, GraphicsInterface, QueueFence, ImageView, ImageResource, DeviceImages};
 use interlude_vk_defs::*;
 use interlude_vk_funport::*;
 use data::Size2;
 use std::ops::Deref;
 use std::mem::uninitialized as reserved;
+use std::cell::RefCell;
 use std::ptr::{null, null_mut};
 use std::cmp::max;
 use std::{u32, u64};
@@ -14,7 +15,7 @@ use subsystem_layer::{NativeInstance, NativeHandleProvider, NativeResultValueHan
 use device::Device;
 use wsi::{NativeWindowBase, PlatformWindowType};
 
-pub struct WindowRenderTargetView(VkImage, VkImageView, VkFormat);
+pub struct WindowRenderTargetView(pub(crate) VkImage, pub(crate) VkImageView, pub(crate) VkFormat);
 impl ImageResource for WindowRenderTargetView
 {
 	fn internal(&self) -> u64 { self.0 as _ }
@@ -24,127 +25,244 @@ impl ImageView for WindowRenderTargetView
 	fn internal(&self) -> u64 { self.1 as _ }
 	fn format(&self) -> VkFormat { self.2 }
 }
+/// State of the presentation surface, reported by acquiring and presenting operations
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SurfaceState
+{
+	/// The swapchain matches the surface properties exactly
+	Optimal,
+	/// The swapchain can still be used for presentation, but no longer matches the surface properties exactly
+	Suboptimal,
+	/// The swapchain and its render targets were rebuilt; objects that depend on them(e.g. Framebuffers) must be rebuilt too
+	Recreated
+}
+/// Presentation surface, destroyed after all swapchains made on it
+pub(crate) struct Surface(VkSurfaceKHR, Rc<NativeInstance>);
+impl Drop for Surface
+{
+	fn drop(&mut self) { unsafe { vkDestroySurfaceKHR(self.1.native(), self.0, null()) }; }
+}
+/// Images the render targets are made of
+pub(crate) enum RenderTargetImages { Swapchain(VkSwapchainKHR, Rc<Surface>), Offscreen(DeviceImages) }
+/// Views of the render targets, shared with users.
+/// The views and their images are destroyed after the last reference has been dropped,
+/// so references obtained before the swapchain is recreated stay valid(but are no longer presented)
+pub struct RenderTargets { views: Vec<WindowRenderTargetView>, images: RenderTargetImages, parent: Rc<Device> }
+impl RenderTargets
+{
+	pub(crate) fn new(device: &Rc<Device>, views: Vec<WindowRenderTargetView>, images: RenderTargetImages) -> Self
+	{
+		RenderTargets { views, images, parent: device.clone() }
+	}
+	pub(crate) fn push_view(&mut self, view: WindowRenderTargetView) { self.views.push(view); }
+	fn swapchain(&self) -> VkSwapchainKHR
+	{
+		match self.images { RenderTargetImages::Swapchain(s, _) => s, RenderTargetImages::Offscreen(_) => 0 as _ }
+	}
+}
+impl Deref for RenderTargets { type Target = [WindowRenderTargetView]; fn deref(&self) -> &Self::Target { &self.views } }
+impl Drop for RenderTargets
+{
+	fn drop(&mut self)
+	{
+		for &WindowRenderTargetView(_, v, _) in &self.views { unsafe { vkDestroyImageView(self.parent.native(), v, null()) }; }
+		if let RenderTargetImages::Swapchain(s, _) = self.images { unsafe { vkDestroySwapchainKHR(self.parent.native(), s, null()) }; }
+	}
+}
+/// Set of render targets that can be acquired and presented(implemented by RenderWindow and headless OffscreenTargets)
+pub trait RenderTargetProvider
+{
+	/// Current render targets(replaced when the swapchain is recreated)
+	fn render_targets(&self) -> Rc<RenderTargets>;
+	fn format(&self) -> VkFormat;
+	fn size(&self) -> Size2;
+	fn acquire_next_target_index(&self, wait_semaphore: &QueueFence) -> EngineResult<(u32, SurfaceState)>;
+	fn present(&self, engine: &GraphicsInterface, index: u32, wait_semaphore: Option<&QueueFence>) -> EngineResult<SurfaceState>;
+}
+/// Swapchain and its render targets(recreated when the surface has been changed)
+struct SwapchainTargets { render_targets: Rc<RenderTargets>, extent: Size2 }
 pub struct RenderWindow
 {
-	underlying: PlatformWindowType, parent: Rc<Device>, apiroot: Rc<NativeInstance>,
-	swapchain: VkSwapchainKHR, surface: VkSurfaceKHR, render_targets: Vec<WindowRenderTargetView>,
-	format: VkFormat, extent: Size2, #[allow(dead_code)] has_vsync: bool
+	parent: Rc<Device>, targets: RefCell<SwapchainTargets>, surface: Rc<Surface>,
+	format: VkFormat, color_space: VkColorSpaceKHR, present_mode: VkPresentModeKHR, #[allow(dead_code)] has_vsync: bool,
+	// dropped last: the surface must be destroyed before the window
+	underlying: PlatformWindowType
 }
-struct SupportedSurface<'a>(VkSurfaceKHR, &'a GraphicsInterface);
+struct SupportedSurface<'a>(VkSurfaceKHR, &'a Device);
 impl<'a> SupportedSurface<'a>
 {
-	fn ensure(s: VkSurfaceKHR, g: &'a GraphicsInterface) -> EngineResult<Self>
+	fn ensure(s: VkSurfaceKHR, d: &'a Device) -> EngineResult<Self>
 	{
-		if g.surface_support(s)? { Ok(SupportedSurface(s, g)) }
+		if d.has_surface_support(s)? { Ok(SupportedSurface(s, d)) }
 		else { Err(EngineError::GenericError("Surface is not supported by device")) }
 	}
 	fn caps(&self) -> EngineResult<VkSurfaceCapabilitiesKHR>
 	{
 		let mut caps = unsafe { reserved() };
-		unsafe { vkGetPhysicalDeviceSurfaceCapabilitiesKHR(self.1.device().adapter(), self.0, &mut caps) }.make_result(caps)
+		unsafe { vkGetPhysicalDeviceSurfaceCapabilitiesKHR(self.1.adapter(), self.0, &mut caps) }.make_result(caps)
 	}
 	fn supported_formats(&self) -> EngineResult<Vec<VkSurfaceFormatKHR>>
 	{
 		let mut format_count = 0;
-		unsafe { vkGetPhysicalDeviceSurfaceFormatsKHR(self.1.device().adapter(), self.0, &mut format_count, null_mut()) }.into_result()?;
+		unsafe { vkGetPhysicalDeviceSurfaceFormatsKHR(self.1.adapter(), self.0, &mut format_count, null_mut()) }.into_result()?;
 		let mut formats = vec![unsafe { reserved() }; format_count as usize];
-		unsafe { vkGetPhysicalDeviceSurfaceFormatsKHR(self.1.device().adapter(), self.0, &mut format_count, formats.as_mut_ptr()) }
+		unsafe { vkGetPhysicalDeviceSurfaceFormatsKHR(self.1.adapter(), self.0, &mut format_count, formats.as_mut_ptr()) }
 			.make_result(formats)
 	}
 	fn supported_present_modes(&self) -> EngineResult<Vec<VkPresentModeKHR>>
 	{
 		let mut mode_count = 0;
-		unsafe { vkGetPhysicalDeviceSurfacePresentModesKHR(self.1.device().adapter(), self.0, &mut mode_count, null_mut()) }.into_result()?;
+		unsafe { vkGetPhysicalDeviceSurfacePresentModesKHR(self.1.adapter(), self.0, &mut mode_count, null_mut()) }.into_result()?;
 		let mut modes = vec![unsafe { reserved() }; mode_count as usize];
-		unsafe { vkGetPhysicalDeviceSurfacePresentModesKHR(self.1.device().adapter(), self.0, &mut mode_count, modes.as_mut_ptr()) }
+		unsafe { vkGetPhysicalDeviceSurfacePresentModesKHR(self.1.adapter(), self.0, &mut mode_count, modes.as_mut_ptr()) }
 			.make_result(modes)
 	}
 }
-impl RenderWindow
+impl SwapchainTargets
 {
-	pub(crate) fn new(g: &GraphicsInterface, size: &Size2, caption: &str, resizable: bool) -> Result<Self, EngineError>
+	fn new(device: &Rc<Device>, surface: &SupportedSurface, surface_owner: &Rc<Surface>, format: &VkSurfaceFormatKHR, present_mode: VkPresentModeKHR,
+		default_extent: &Size2, old_swapchain: VkSwapchainKHR) -> EngineResult<Self>
 	{
-		let underlying = PlatformWindowType::new(size, caption, resizable)?;
-		if !underlying.can_vk_present(g.device().adapter(), g.device().graphics_qf_index)
-		{
-			return Err(EngineError::GenericError("Vulkan Presentation is not supported by this platform"));
-		}
-		let surface = underlying.make_vk_surface(g.apicontext())?;
-		let surface_ref = SupportedSurface::ensure(surface, g)?;
-		let surface_caps = surface_ref.caps()?;
-
-		// autodetect parameters //
-		let format = surface_ref.supported_formats()?.into_iter().find(|x| x.format == VK_FORMAT_R8G8B8A8_SRGB || x.format == VK_FORMAT_B8G8R8A8_SRGB)
-			.ok_or(EngineError::GenericError("Desired Format(32bpp SRGB) is not supported"))?;
-		let present_mode =
-		{
-			let modes = surface_ref.supported_present_modes()?;
-			modes.iter().find(|&&x| x == VK_PRESENT_MODE_FIFO_KHR).or_else(|| modes.iter().find(|&&x| x == VK_PRESENT_MODE_MAILBOX_KHR)).map(|&x| x)
-				.ok_or(EngineError::GenericError("Desired Present Mode is not supported"))?
-		};
-		let extent = if surface_caps.currentExtent.width == u32::MAX || surface_caps.currentExtent.height == u32::MAX { size.as_ref() }
+		let surface_caps = surface.caps()?;
+		let extent = if surface_caps.currentExtent.width == u32::MAX || surface_caps.currentExtent.height == u32::MAX { default_extent.as_ref() }
 		else { &surface_caps.currentExtent };
 
 		// Set information and Create //
-		let queue_family_indices = [g.device().graphics_qf_index];
+		let queue_family_indices = [device.graphics_qf_index];
 		let mut swapchain = unsafe { reserved() };
-		unsafe { vkCreateSwapchainKHR(g.device().native(), &VkSwapchainCreateInfoKHR
+		unsafe { vkCreateSwapchainKHR(device.native(), &VkSwapchainCreateInfoKHR
 		{
 			minImageCount: max(surface_caps.minImageCount, 2), imageFormat: format.format, imageColorSpace: format.colorSpace,
 			imageExtent: extent.clone(), imageArrayLayers: 1, imageUsage: VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT, compositeAlpha: VK_COMPOSITE_ALPHA_OPAQUE_BIT_KHR,
 			preTransform: VK_SURFACE_TRANSFORM_IDENTITY_BIT_KHR, presentMode: present_mode, clipped: true as VkBool32,
-			queueFamilyIndexCount: queue_family_indices.len() as _, pQueueFamilyIndices: queue_family_indices.as_ptr(), surface, .. Default::default()
+			queueFamilyIndexCount: queue_family_indices.len() as _, pQueueFamilyIndices: queue_family_indices.as_ptr(), surface: surface.0,
+			oldSwapchain: old_swapchain, .. Default::default()
 		}, null(), &mut swapchain) }.into_result()?;
+		// destroys the swapchain(and views created so far) on failures below
+		let mut render_targets = RenderTargets::new(device, Vec::new(), RenderTargetImages::Swapchain(swapchain, surface_owner.clone()));
 		let mut bi_count = 0;
-		unsafe { vkGetSwapchainImagesKHR(g.device().native(), swapchain, &mut bi_count, null_mut()) }.into_result()?;
+		unsafe { vkGetSwapchainImagesKHR(device.native(), swapchain, &mut bi_count, null_mut()) }.into_result()?;
 		let mut back_images = vec![unsafe { reserved() }; bi_count as _];
-		unsafe { vkGetSwapchainImagesKHR(g.device().native(), swapchain, &mut bi_count, back_images.as_mut_ptr()) }.into_result()?;
-		let back_views = back_images.into_iter().map(|res| unsafe
+		unsafe { vkGetSwapchainImagesKHR(device.native(), swapchain, &mut bi_count, back_images.as_mut_ptr()) }.into_result()?;
+		for res in back_images
 		{
-			let mut iv = reserved();
-			vkCreateImageView(g.device().native(), &VkImageViewCreateInfo
+			let mut iv = unsafe { reserved() };
+			unsafe { vkCreateImageView(device.native(), &VkImageViewCreateInfo
 			{
 				image: res, subresourceRange: VkImageSubresourceRange { aspectMask: VK_IMAGE_ASPECT_COLOR_BIT, .. Default::default() },
 				format: format.format, viewType: VK_IMAGE_VIEW_TYPE_2D, components: Default::default(), .. Default::default()
-			}, null(), &mut iv).make_result_with(|| WindowRenderTargetView(res, iv, format.format))
-		}).collect::<EngineResult<Vec<_>>>()?;
+			}, null(), &mut iv) }.into_result()?;
+			render_targets.push_view(WindowRenderTargetView(res, iv, format.format));
+		}
+
+		Ok(SwapchainTargets { render_targets: Rc::new(render_targets), extent: extent.as_ref().clone() })
+	}
+}
+impl RenderWindow
+{
+	pub(crate) fn new(g: &GraphicsInterface, underlying: PlatformWindowType, size: &Size2) -> Result<Self, EngineError>
+	{
+		if !underlying.can_vk_present(g.device().adapter(), g.device().graphics_qf_index)
+		{
+			return Err(EngineError::GenericError("Vulkan Presentation is not supported by this platform"));
+		}
+		let surface = underlying.make_vk_surface(g.apicontext()).map(|s| Rc::new(Surface(s, g.apicontext().clone())))?;
+		let surface_ref = SupportedSurface::ensure(surface.0, g.device())?;
+
+		// autodetect parameters //
+		let format = surface_ref.supported_formats()?.into_iter().find(|x| x.format == VK_FORMAT_R8G8B8A8_SRGB || x.format == VK_FORMAT_B8G8R8A8_SRGB)
+			.ok_or(EngineError::GenericError("Desired Format(32bpp SRGB) is not supported"))?;
+		let present_mode =
+		{
+			let modes = surface_ref.supported_present_modes()?;
+			modes.iter().find(|&&x| x == VK_PRESENT_MODE_FIFO_KHR).or_else(|| modes.iter().find(|&&x| x == VK_PRESENT_MODE_MAILBOX_KHR)).map(|&x| x)
+				.ok_or(EngineError::GenericError("Desired Present Mode is not supported"))?
+		};
+		let targets = SwapchainTargets::new(g.device(), &surface_ref, &surface, &format, present_mode, size, 0 as _)?;
 
 		Ok(RenderWindow
 		{
-			underlying, swapchain, surface, parent: g.device().clone(), apiroot: g.apicontext().clone(),
-			render_targets: back_views, format: format.format, extent: extent.as_ref().clone(), has_vsync: present_mode == VK_PRESENT_MODE_FIFO_KHR
+			underlying, surface, parent: g.device().clone(), targets: RefCell::new(targets),
+			format: format.format, color_space: format.colorSpace, present_mode, has_vsync: present_mode == VK_PRESENT_MODE_FIFO_KHR
 		})
 	}
+	fn swapchain(&self) -> VkSwapchainKHR { self.targets.borrow().render_targets.swapchain() }
 
 	// Old RenderWindow implementations //
-	pub fn render_targets(&self) -> &[WindowRenderTargetView] { &self.render_targets }
+	/// Render targets in the swapchain. Recreating the swapchain replaces them(the returned ones are kept alive but no longer presented)
+	pub fn render_targets(&self) -> Rc<RenderTargets> { self.targets.borrow().render_targets.clone() }
 	pub fn format(&self) -> VkFormat { self.format }
-	pub fn size(&self) -> &Size2 { &self.extent }
-	pub fn acquire_next_target_index(&self, wait_semaphore: &QueueFence) -> EngineResult<u32>
+	pub fn size(&self) -> Size2 { self.targets.borrow().extent.clone() }
+	/// Acquire next render target.
+	/// The swapchain is recreated before acquiring if it is out of date; `SurfaceState::Recreated` is returned in that case.
+	pub fn acquire_next_target_index(&self, wait_semaphore: &QueueFence) -> EngineResult<(u32, SurfaceState)>
 	{
 		let mut next_index = 0;
-		unsafe { vkAcquireNextImageKHR(self.parent.native(), self.swapchain, u64::MAX, wait_semaphore.native(), 0 as _, &mut next_index) }
-			.make_result(next_index)
+		match unsafe { vkAcquireNextImageKHR(self.parent.native(), self.swapchain(), u64::MAX, wait_semaphore.native(), 0 as _, &mut next_index) }
+		{
+			VK_SUCCESS => Ok((next_index, SurfaceState::Optimal)),
+			VK_SUBOPTIMAL_KHR => Ok((next_index, SurfaceState::Suboptimal)),
+			VK_ERROR_OUT_OF_DATE_KHR =>
+			{
+				self.recreate_targets()?;
+				match unsafe { vkAcquireNextImageKHR(self.parent.native(), self.swapchain(), u64::MAX, wait_semaphore.native(), 0 as _, &mut next_index) }
+				{
+					VK_SUCCESS | VK_SUBOPTIMAL_KHR => Ok((next_index, SurfaceState::Recreated)),
+					e => Err(EngineError::DeviceError(e))
+				}
+			},
+			e => Err(EngineError::DeviceError(e))
+		}
 	}
-	pub fn present(&self, engine: &GraphicsInterface, index: u32, wait_semaphore: Option<&QueueFence>) -> EngineResult<()>
+	/// Present a render target.
+	/// The swapchain is recreated after presenting if it is suboptimal or out of date; `SurfaceState::Recreated` is returned in that case.
+	pub fn present(&self, engine: &GraphicsInterface, index: u32, wait_semaphore: Option<&QueueFence>) -> EngineResult<SurfaceState>
 	{
 		let sem = wait_semaphore.map(NativeHandleProvider::native).into_iter().collect::<Vec<_>>();
-		unsafe { vkQueuePresentKHR(engine.device().graphics_queue, &VkPresentInfoKHR
+		let swapchain = self.swapchain();
+		match unsafe { vkQueuePresentKHR(engine.device().graphics_queue, &VkPresentInfoKHR
 		{
 			waitSemaphoreCount: sem.len() as _, pWaitSemaphores: sem.as_ptr(),
-			swapchainCount: 1, pSwapchains: &self.swapchain, pImageIndices: &index, .. Default::default()
-		}) }.into_result()
+			swapchainCount: 1, pSwapchains: &swapchain, pImageIndices: &index, .. Default::default()
+		}) }
+		{
+			VK_SUCCESS => Ok(SurfaceState::Optimal),
+			VK_SUBOPTIMAL_KHR | VK_ERROR_OUT_OF_DATE_KHR => self.recreate_targets().map(|_| SurfaceState::Recreated),
+			e => Err(EngineError::DeviceError(e))
+		}
+	}
+	/// Rebuild the swapchain and its render targets to match the current surface properties.
+	/// Render targets obtained before calling this are retired: they stay valid while referenced, but are no longer presented.
+	pub fn recreate_targets(&self) -> EngineResult<()>
+	{
+		self.parent.wait_for_idle()?;
+		let surface_ref = SupportedSurface::ensure(self.surface.0, &self.parent)?;
+		let format = VkSurfaceFormatKHR { format: self.format, colorSpace: self.color_space };
+		let new_targets =
+		{
+			let current = self.targets.borrow();
+			SwapchainTargets::new(&self.parent, &surface_ref, &self.surface, &format, self.present_mode, &current.extent, current.render_targets.swapchain())?
+		};
+		info!(target: "Interlude::RenderWindow", "Swapchain has been recreated: {:?}", new_targets.extent);
+		// the old swapchain is destroyed when the last reference to its render targets is dropped
+		*self.targets.borrow_mut() = new_targets;
+		Ok(())
 	}
 }
-impl Deref for RenderWindow { type Target = PlatformWindowType; fn deref(&self) -> &PlatformWindowType { &self.underlying } }
-unsafe impl Send for RenderWindow {}
-unsafe impl Sync for RenderWindow {}
-impl Drop for RenderWindow
+impl RenderTargetProvider for RenderWindow
 {
-	fn drop(&mut self)
+	fn render_targets(&self) -> Rc<RenderTargets> { RenderWindow::render_targets(self) }
+	fn format(&self) -> VkFormat { RenderWindow::format(self) }
+	fn size(&self) -> Size2 { RenderWindow::size(self) }
+	fn acquire_next_target_index(&self, wait_semaphore: &QueueFence) -> EngineResult<(u32, SurfaceState)>
+	{
+		RenderWindow::acquire_next_target_index(self, wait_semaphore)
+	}
+	fn present(&self, engine: &GraphicsInterface, index: u32, wait_semaphore: Option<&QueueFence>) -> EngineResult<SurfaceState>
 	{
-		for WindowRenderTargetView(_, v, _) in self.render_targets.drain(..) { unsafe { vkDestroyImageView(self.parent.native(), v, null()) }; }
-		unsafe { vkDestroySwapchainKHR(self.parent.native(), self.swapchain, null()) };
-		unsafe { vkDestroySurfaceKHR(self.apiroot.native(), self.surface, null()) };
+		RenderWindow::present(self, engine, index, wait_semaphore)
 	}
 }
+impl Deref for RenderWindow { type Target = PlatformWindowType; fn deref(&self) -> &PlatformWindowType { &self.underlying } }
+unsafe impl Send for RenderWindow {}
+unsafe impl Sync for RenderWindow {}
@@ -4,17 +4,21 @@ use interlude_vk_defs::*;
 use interlude_vk_funport::*;
 use data::*;
 use device::Device;
-use ginterface::MemoryIndexType;
-use {EngineResult, EngineError, GraphicsInterface};
+use memory::{MemoryAllocation, ResourceTiling};
+use {EngineResult, EngineError, GraphicsInterface, CompareOp};
+use command::BufferImageCopyRegion;
 use std::slice::from_raw_parts_mut;
 use std::ops::{BitOr, BitOrAssign, Deref, Range};
 use std::rc::Rc;
+use std::hash::{Hash, Hasher};
 use std::ptr::{null, null_mut};
 use std::mem::{replace, transmute, uninitialized as reserved};
 use libc::c_void;
 use subsystem_layer::{NativeHandleProvider, NativeResultValueHandler, NativeImage};
 
 fn alignment(v: usize, a: usize) -> usize { (v as f64 / a as f64).ceil() as usize * a }
+fn gcd(a: usize, b: usize) -> usize { if b == 0 { a } else { gcd(b, a % b) } }
+fn lcm(a: usize, b: usize) -> usize { a / gcd(a, b) * b }
 
 // Resource DataType //
 #[derive(Clone)] pub struct Image1D(Rc<NativeImage<u32>>);
@@ -77,6 +81,27 @@ impl Image2D
 		unsafe { vkCreateImage(engine.device().native(), desc, null(), &mut img) }
 			.make_result_with(|| Image2D(Rc::new(NativeImage(img, engine.device().clone(), Size2(desc.extent.width, desc.extent.height)))))
 	}
+	pub fn size(&self) -> &Size2 { &self.0 .2 }
+}
+/// Number of levels in the full mip chain of the size
+pub fn mip_level_count(size: &Size2) -> u32 { 32 - size.0.max(size.1).max(1).leading_zeros() }
+/// Format features(optimal tiling) required for generating mipmaps by blitting
+pub(crate) const MIPMAP_BLIT_FORMAT_FEATURES: VkFormatFeatureFlags = VK_FORMAT_FEATURE_BLIT_SRC_BIT | VK_FORMAT_FEATURE_BLIT_DST_BIT | VK_FORMAT_FEATURE_SAMPLED_IMAGE_FILTER_LINEAR_BIT;
+/// Mip chain of an Image2D to be filled from level 0 by `generate_mipmaps`
+pub struct MipmapChain<'a> { pub(crate) image: &'a Image2D, pub(crate) levels: u32, pub(crate) layers: u32 }
+impl<'a> MipmapChain<'a>
+{
+	/// Fails if the format does not support linear-filtered blitting in optimal tiling
+	pub fn new(engine: &GraphicsInterface, image: &'a Image2D, format: VkFormat, levels: u32, layers: u32) -> EngineResult<Self>
+	{
+		if (engine.format_properties(format).optimalTilingFeatures & MIPMAP_BLIT_FORMAT_FEATURES) != MIPMAP_BLIT_FORMAT_FEATURES
+		{
+			return Err(EngineError::GenericError("The format does not support linear-filtered blitting for generating mipmaps"));
+		}
+		if levels == 0 || levels > mip_level_count(image.size()) { return Err(EngineError::GenericError("Invalid mip level count")); }
+		Ok(MipmapChain { image, levels, layers })
+	}
+	pub fn levels(&self) -> u32 { self.levels }
 }
 impl Image3D
 {
@@ -137,19 +162,38 @@ impl ImageDescriptor2
 			usage, initialLayout: VK_IMAGE_LAYOUT_PREINITIALIZED, .. Default::default()
 		}, false)
 	}
+	/// Device-local depth(/stencil) attachment in a supported format(`GraphicsInterface::depth_format`)
+	pub fn depth_buffer(engine: &GraphicsInterface, extent: Size2, require_stencil: bool, extra_usage: VkImageUsageFlags) -> EngineResult<Self>
+	{
+		engine.depth_format(require_stencil)
+			.map(|f| Self::new(f, extent, VK_IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT | extra_usage).device_local())
+	}
 	pub fn device_local(mut self) -> Self { self.1 = true; self }
 	pub fn is_device_local(&self) -> bool { self.1 }
+	pub fn format(&self) -> VkFormat { self.0.format }
 	
 	pub fn mip_levels(mut self, levels: u32) -> Self
 	{
 		self.0.mipLevels = levels;
 		self
 	}
+	/// Allocates all levels down to 1x1
+	pub fn full_mip_chain(mut self) -> Self
+	{
+		self.0.mipLevels = mip_level_count(&Size2(self.0.extent.width, self.0.extent.height));
+		self
+	}
 	pub fn array_layers(mut self, layers: u32) -> Self
 	{
 		self.0.arrayLayers = layers;
 		self
 	}
+	/// Allows cube(array) views of each 6 layers
+	pub fn cube_compatible(mut self) -> Self
+	{
+		self.0.flags |= VK_IMAGE_CREATE_CUBE_COMPATIBLE_BIT;
+		self
+	}
 	pub fn sample_flags(mut self, samples: SampleCount) -> Self
 	{
 		self.0.samples = samples;
@@ -201,6 +245,19 @@ impl BitOr<ImageAspect> for ImageAspectSet { type Output = Self; fn bitor(self,
 impl BitOrAssign for ImageAspectSet { fn bitor_assign(&mut self, rhs: Self) { self.0 |= rhs.0; } }
 impl BitOrAssign<ImageAspect> for ImageAspectSet { fn bitor_assign(&mut self, rhs: ImageAspect) { self.0 |= rhs as VkFlags; } }
 impl Into<VkImageAspectFlags> for ImageAspectSet { fn into(self) -> VkImageAspectFlags { self.0 } }
+impl ImageAspectSet
+{
+	/// Depth and Stencil aspects present in the depth/stencil format
+	pub fn of_depth_format(format: VkFormat) -> Self
+	{
+		match format
+		{
+			VK_FORMAT_S8_UINT => ImageAspect::Stencil.into(),
+			VK_FORMAT_D16_UNORM_S8_UINT | VK_FORMAT_D24_UNORM_S8_UINT | VK_FORMAT_D32_SFLOAT_S8_UINT => ImageAspect::Depth | ImageAspect::Stencil,
+			_ => ImageAspect::Depth.into()
+		}
+	}
+}
 
 /// Image Subresource Parameters //
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -314,14 +371,21 @@ impl BufferOffsets
 pub struct ImagePreallocator<'a>
 {
 	engine: &'a GraphicsInterface,
-	dim1_images: Vec<&'a ImageDescriptor1>, dim2_images: Vec<&'a ImageDescriptor2>, dim3_images: Vec<&'a ImageDescriptor3>
+	dim1_images: Vec<&'a ImageDescriptor1>, dim2_images: Vec<&'a ImageDescriptor2>, dim3_images: Vec<&'a ImageDescriptor3>,
+	buffer_staging: bool
 }
 impl<'a> ImagePreallocator<'a>
 {
 	pub fn new(engine: &'a GraphicsInterface,
 		dim1_images: Vec<&'a ImageDescriptor1>, dim2_images: Vec<&'a ImageDescriptor2>, dim3_images: Vec<&'a ImageDescriptor3>) -> Self
 	{
-		ImagePreallocator { engine: engine, dim1_images: dim1_images, dim2_images: dim2_images, dim3_images: dim3_images }
+		ImagePreallocator { engine: engine, dim1_images: dim1_images, dim2_images: dim2_images, dim3_images: dim3_images, buffer_staging: false }
+	}
+	/// Stage host-visible images into a plain buffer(copied with `copy_buffer_to_image`) instead of linear images
+	pub fn stage_into_buffer(mut self) -> Self
+	{
+		self.buffer_staging = true;
+		self
 	}
 	pub fn instantiate(&self) -> EngineResult<(DeviceImages, Option<StagingImages>)>
 	{
@@ -330,116 +394,111 @@ impl<'a> ImagePreallocator<'a>
 		let i3 = self.dim3_images.iter().map(|&d3| Image3D::new(self.engine, &d3.0)).collect::<EngineResult<_>>()?;
 		let si1 = self.dim1_images.iter().filter(|&&&ImageDescriptor1(_, dl)| !dl).map(|&&ImageDescriptor1(ref d, _)| (Size2(d.extent.width, d.extent.height), d.format));
 		let si2 = self.dim2_images.iter().filter(|&&&ImageDescriptor2(_, dl)| !dl).map(|&&ImageDescriptor2(ref d, _)| (Size2(d.extent.width, d.extent.height), d.format));
-		let si = si1.chain(si2).map(|(x, f)| LinearImage::new(self.engine, &x, f)).collect::<EngineResult<Vec<_>>>()?;
 		
 		let d = DeviceImages::new(self.engine, i1, i2, i3);
-		let s = if si.is_empty() { None } else { StagingImages::new(self.engine, si).map(Some)? };
+		let s = if self.buffer_staging
+		{
+			let si = si1.chain(si2).collect::<Vec<_>>();
+			if si.is_empty() { None } else { StagingImages::new_buffer(self.engine, si).map(Some)? }
+		}
+		else
+		{
+			let si = si1.chain(si2).map(|(x, f)| LinearImage::new(self.engine, &x, f)).collect::<EngineResult<Vec<_>>>()?;
+			if si.is_empty() { None } else { StagingImages::new(self.engine, si).map(Some)? }
+		};
 		d.map(|d| (d, s))
 	}
 }
 
-pub struct DeviceBuffer { buffer: VkBuffer, memory: VkDeviceMemory, size: VkDeviceSize, parent: Rc<Device> }
-pub struct StagingBuffer { buffer: VkBuffer, memory: VkDeviceMemory, size: VkDeviceSize, parent: Rc<Device> }
+pub struct DeviceBuffer { buffer: VkBuffer, memory: MemoryAllocation, size: VkDeviceSize, parent: Rc<Device> }
+pub struct StagingBuffer { buffer: VkBuffer, memory: MemoryAllocation, size: VkDeviceSize, parent: Rc<Device> }
 impl DeviceBuffer
 {
 	fn new(engine: &GraphicsInterface, size: VkDeviceSize, usage: VkBufferUsageFlags) -> EngineResult<Self>
 	{
-		let (mut buffer, mut mreq, mut memory) = unsafe { reserved() };
-		unsafe
+		let (mut buffer, mut mreq) = unsafe { reserved() };
+		unsafe { vkCreateBuffer(engine.device().native(), &VkBufferCreateInfo
 		{
-			vkCreateBuffer(engine.device().native(), &VkBufferCreateInfo
-			{
-				size, usage: usage | VK_BUFFER_USAGE_TRANSFER_DST_BIT, .. Default::default()
-			}, null(), &mut buffer).into_result()?;
-			vkGetBufferMemoryRequirements(engine.device().native(), buffer, &mut mreq);
-			vkAllocateMemory(engine.device().native(), &VkMemoryAllocateInfo
-			{
-				allocationSize: mreq.size, memoryTypeIndex: engine.memindex(MemoryIndexType::DeviceLocal), .. Default::default()
-			}, null(), &mut memory).into_result()?;
-			vkBindBufferMemory(engine.device().native(), buffer, memory, 0)
-		}.make_result_with(|| DeviceBuffer { buffer, memory, size, parent: engine.device().clone() })
+			size, usage: usage | VK_BUFFER_USAGE_TRANSFER_DST_BIT, .. Default::default()
+		}, null(), &mut buffer) }.into_result()?;
+		unsafe { vkGetBufferMemoryRequirements(engine.device().native(), buffer, &mut mreq) };
+		let memory_type = engine.memory_type_index(mreq.memoryTypeBits, VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT, 0)?;
+		let memory = engine.allocator().allocate(memory_type, &mreq, ResourceTiling::Linear)?;
+		unsafe { vkBindBufferMemory(engine.device().native(), buffer, memory.native(), memory.offset()) }
+			.make_result_with(|| DeviceBuffer { buffer, memory, size, parent: engine.device().clone() })
 	}
 }
 impl StagingBuffer
 {
-	fn new(engine: &GraphicsInterface, size: VkDeviceSize, usage: VkBufferUsageFlags) -> EngineResult<Self>
+	pub(crate) fn new(engine: &GraphicsInterface, size: VkDeviceSize, usage: VkBufferUsageFlags) -> EngineResult<Self>
 	{
-		let (mut buffer, mut mreq, mut memory) = unsafe { reserved() };
-		unsafe
+		let (mut buffer, mut mreq) = unsafe { reserved() };
+		unsafe { vkCreateBuffer(engine.device().native(), &VkBufferCreateInfo
 		{
-			vkCreateBuffer(engine.device().native(), &VkBufferCreateInfo
-			{
-				size, usage: usage | VK_BUFFER_USAGE_TRANSFER_SRC_BIT, .. Default::default()
-			}, null(), &mut buffer).into_result()?;
-			vkGetBufferMemoryRequirements(engine.device().native(), buffer, &mut mreq);
-			vkAllocateMemory(engine.device().native(), &VkMemoryAllocateInfo
-			{
-				allocationSize: mreq.size, memoryTypeIndex: engine.memindex(MemoryIndexType::HostVisible), .. Default::default()
-			}, null(), &mut memory).into_result()?;
-			vkBindBufferMemory(engine.device().native(), buffer, memory, 0)
-		}.make_result_with(|| StagingBuffer { buffer, memory, size, parent: engine.device().clone() })
+			size, usage: usage | VK_BUFFER_USAGE_TRANSFER_SRC_BIT, .. Default::default()
+		}, null(), &mut buffer) }.into_result()?;
+		unsafe { vkGetBufferMemoryRequirements(engine.device().native(), buffer, &mut mreq) };
+		let memory_type = engine.memory_type_index(mreq.memoryTypeBits, VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT, VK_MEMORY_PROPERTY_HOST_COHERENT_BIT)?;
+		let memory = engine.allocator().allocate(memory_type, &mreq, ResourceTiling::Linear)?;
+		unsafe { vkBindBufferMemory(engine.device().native(), buffer, memory.native(), memory.offset()) }
+			.make_result_with(|| StagingBuffer { buffer, memory, size, parent: engine.device().clone() })
 	}
 }
 // impl NativeHandleProvider for DeviceBuffer { type NativeT = VkBuffer; fn native(&self) -> VkBuffer { self.buffer } }
 // impl NativeHandleProvider for StagingBuffer { type NativeT = VkBuffer; fn native(&self) -> VkBuffer { self.buffer } }
+// memory ranges are returned to the allocator after the buffers are destroyed
 impl Drop for DeviceBuffer
 {
-	fn drop(&mut self)
-	{
-		unsafe { vkFreeMemory(self.parent.native(), self.memory, null()) };
-		unsafe { vkDestroyBuffer(self.parent.native(), self.buffer, null()) };
-	}
+	fn drop(&mut self) { unsafe { vkDestroyBuffer(self.parent.native(), self.buffer, null()) }; }
 }
 impl Drop for StagingBuffer
 {
-	fn drop(&mut self)
-	{
-		unsafe { vkFreeMemory(self.parent.native(), self.memory, null()) };
-		unsafe { vkDestroyBuffer(self.parent.native(), self.buffer, null()) };
-	}
+	fn drop(&mut self) { unsafe { vkDestroyBuffer(self.parent.native(), self.buffer, null()) }; }
 }
 impl BufferResource for StagingBuffer { fn internal(&self) -> u64 { self.buffer as _ } fn size(&self) -> VkDeviceSize { self.size } }
 impl BufferResource for DeviceBuffer { fn internal(&self) -> u64 { self.buffer as _ } fn size(&self) -> VkDeviceSize { self.size } }
 
+/// Aggregated requirements for placing multiple images in a single memory range: (offsets, requirements for whole range)
+fn place_images<I: Iterator<Item = VkMemoryRequirements>>(requirements: I) -> EngineResult<(Vec<VkDeviceSize>, VkMemoryRequirements)>
+{
+	let (mut current_offset, mut max_alignment, mut type_bits, mut offsets) = (0, 1 as VkDeviceSize, !0u32, Vec::new());
+	for ireq in requirements
+	{
+		let offset = alignment(current_offset as _, ireq.alignment as _) as _;
+		offsets.push(offset);
+		current_offset = offset + ireq.size;
+		max_alignment = max_alignment.max(ireq.alignment);
+		type_bits &= ireq.memoryTypeBits;
+	}
+	if current_offset == 0 { Err(EngineError::AllocateMemoryWithEmptyResources) }
+	else { Ok((offsets, VkMemoryRequirements { size: current_offset, alignment: max_alignment, memoryTypeBits: type_bits })) }
+}
+
 pub struct DeviceImages
 {
-	memory: VkDeviceMemory, parent: Rc<Device>,
-	#[allow(dead_code)] size: VkDeviceSize, resources: (Vec<Image1D>, Vec<Image2D>, Vec<Image3D>)
+	#[allow(dead_code)] size: VkDeviceSize, resources: (Vec<Image1D>, Vec<Image2D>, Vec<Image3D>),
+	// released after the images bound to it are destroyed
+	#[allow(dead_code)] memory: MemoryAllocation
 }
 impl DeviceImages
 {
-	fn placement(images: (&[Image1D], &[Image2D], &[Image3D])) -> EngineResult<(Vec<VkDeviceSize>, VkDeviceSize)>
-	{
-		let (mut current_offset, mut offsets) = (0, Vec::with_capacity(images.0.len() + images.1.len() + images.2.len()));
-		for ireq in images.0.into_iter().map(RequireMemory::memory_requirements)
-			.chain(images.1.into_iter().map(RequireMemory::memory_requirements))
-			.chain(images.2.into_iter().map(RequireMemory::memory_requirements))
-		{
-			let offset = alignment(current_offset as _, ireq.alignment as _) as _;
-			offsets.push(offset);
-			current_offset += offset + ireq.size;
-		}
-		if current_offset == 0 { Err(EngineError::AllocateMemoryWithEmptyResources) }
-		else { Ok((offsets, current_offset)) }
-	}
 	fn new(engine: &GraphicsInterface, d1_images: Vec<Image1D>, d2_images: Vec<Image2D>, d3_images: Vec<Image3D>)
 		-> Result<Self, EngineError>
 	{
-		let (offsets, size) = Self::placement((&d1_images, &d2_images, &d3_images))?;
-		info!(target: "Interlude::Resource", "Going to allocate images for device: {} bytes total", size);
+		let (offsets, mreq) = place_images(d1_images.iter().map(RequireMemory::memory_requirements)
+			.chain(d2_images.iter().map(RequireMemory::memory_requirements))
+			.chain(d3_images.iter().map(RequireMemory::memory_requirements)))?;
+		info!(target: "Interlude::Resource", "Going to allocate images for device: {} bytes total", mreq.size);
 
-		let mut memory = unsafe { reserved() };
-		unsafe { vkAllocateMemory(engine.device().native(), &VkMemoryAllocateInfo
-		{
-			allocationSize: size, memoryTypeIndex: engine.memindex(MemoryIndexType::DeviceLocal), .. Default::default()
-		}, null(), &mut memory) }.into_result()?;
+		let memory_type = engine.memory_type_index(mreq.memoryTypeBits, VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT, 0)?;
+		let memory = engine.allocator().allocate(memory_type, &mreq, ResourceTiling::Optimal)?;
 		for (&offs, res) in offsets.iter().zip(d1_images.iter().map(|&Image1D(ref o)| o.native())
 			.chain(d2_images.iter().map(|&Image2D(ref o)| o.native()))
 			.chain(d3_images.iter().map(|&Image3D(ref o)| o.native())))
 		{
-			unsafe { vkBindImageMemory(engine.device().native(), res, memory, offs) }.into_result()?;
+			unsafe { vkBindImageMemory(engine.device().native(), res, memory.native(), memory.offset() + offs) }.into_result()?;
 		}
-		Ok(DeviceImages { memory, size, resources: (d1_images, d2_images, d3_images), parent: engine.device().clone() })
+		Ok(DeviceImages { memory, size: mreq.size, resources: (d1_images, d2_images, d3_images) })
 	}
 
 	pub fn dim1(&self) -> &[Image1D] { &self.resources.0 }
@@ -447,74 +506,156 @@ impl DeviceImages
 	pub fn dim3(&self) -> &[Image3D] { &self.resources.2 }
 }
 
+/// Block extent and size in bytes of the format(texels are 1x1 blocks) for tightly packed image data
+pub(crate) fn format_block(format: VkFormat) -> Option<(u32, u32, usize)>
+{
+	match format
+	{
+		VK_FORMAT_R8_UNORM | VK_FORMAT_R8_SNORM | VK_FORMAT_R8_UINT | VK_FORMAT_R8_SINT | VK_FORMAT_R8_SRGB | VK_FORMAT_S8_UINT => Some((1, 1, 1)),
+		VK_FORMAT_R8G8_UNORM | VK_FORMAT_R8G8_SNORM | VK_FORMAT_R8G8_UINT | VK_FORMAT_R8G8_SINT | VK_FORMAT_R8G8_SRGB |
+		VK_FORMAT_R16_UNORM | VK_FORMAT_R16_SNORM | VK_FORMAT_R16_UINT | VK_FORMAT_R16_SINT | VK_FORMAT_R16_SFLOAT | VK_FORMAT_D16_UNORM |
+		VK_FORMAT_R5G6B5_UNORM_PACK16 | VK_FORMAT_B5G6R5_UNORM_PACK16 | VK_FORMAT_R4G4B4A4_UNORM_PACK16 | VK_FORMAT_B4G4R4A4_UNORM_PACK16 |
+		VK_FORMAT_R5G5B5A1_UNORM_PACK16 | VK_FORMAT_B5G5R5A1_UNORM_PACK16 | VK_FORMAT_A1R5G5B5_UNORM_PACK16 => Some((1, 1, 2)),
+		VK_FORMAT_R8G8B8_UNORM | VK_FORMAT_R8G8B8_SRGB | VK_FORMAT_B8G8R8_UNORM | VK_FORMAT_B8G8R8_SRGB => Some((1, 1, 3)),
+		VK_FORMAT_R8G8B8A8_UNORM | VK_FORMAT_R8G8B8A8_SNORM | VK_FORMAT_R8G8B8A8_UINT | VK_FORMAT_R8G8B8A8_SINT | VK_FORMAT_R8G8B8A8_SRGB |
+		VK_FORMAT_B8G8R8A8_UNORM | VK_FORMAT_B8G8R8A8_SRGB | VK_FORMAT_A8B8G8R8_UNORM_PACK32 | VK_FORMAT_A8B8G8R8_SRGB_PACK32 |
+		VK_FORMAT_A2B10G10R10_UNORM_PACK32 | VK_FORMAT_A2R10G10B10_UNORM_PACK32 | VK_FORMAT_B10G11R11_UFLOAT_PACK32 | VK_FORMAT_E5B9G9R9_UFLOAT_PACK32 |
+		VK_FORMAT_R16G16_UNORM | VK_FORMAT_R16G16_SFLOAT | VK_FORMAT_R32_UINT | VK_FORMAT_R32_SINT | VK_FORMAT_R32_SFLOAT |
+		VK_FORMAT_D32_SFLOAT | VK_FORMAT_X8_D24_UNORM_PACK32 => Some((1, 1, 4)),
+		VK_FORMAT_R16G16B16A16_UNORM | VK_FORMAT_R16G16B16A16_UINT | VK_FORMAT_R16G16B16A16_SFLOAT |
+		VK_FORMAT_R32G32_UINT | VK_FORMAT_R32G32_SINT | VK_FORMAT_R32G32_SFLOAT => Some((1, 1, 8)),
+		VK_FORMAT_R32G32B32_SFLOAT => Some((1, 1, 12)),
+		VK_FORMAT_R32G32B32A32_UINT | VK_FORMAT_R32G32B32A32_SINT | VK_FORMAT_R32G32B32A32_SFLOAT => Some((1, 1, 16)),
+		VK_FORMAT_BC1_RGB_UNORM_BLOCK | VK_FORMAT_BC1_RGB_SRGB_BLOCK | VK_FORMAT_BC1_RGBA_UNORM_BLOCK | VK_FORMAT_BC1_RGBA_SRGB_BLOCK |
+		VK_FORMAT_BC4_UNORM_BLOCK | VK_FORMAT_BC4_SNORM_BLOCK | VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK | VK_FORMAT_ETC2_R8G8B8_SRGB_BLOCK |
+		VK_FORMAT_ETC2_R8G8B8A1_UNORM_BLOCK | VK_FORMAT_ETC2_R8G8B8A1_SRGB_BLOCK | VK_FORMAT_EAC_R11_UNORM_BLOCK | VK_FORMAT_EAC_R11_SNORM_BLOCK
+			=> Some((4, 4, 8)),
+		VK_FORMAT_BC2_UNORM_BLOCK | VK_FORMAT_BC2_SRGB_BLOCK | VK_FORMAT_BC3_UNORM_BLOCK | VK_FORMAT_BC3_SRGB_BLOCK |
+		VK_FORMAT_BC5_UNORM_BLOCK | VK_FORMAT_BC5_SNORM_BLOCK | VK_FORMAT_BC6H_UFLOAT_BLOCK | VK_FORMAT_BC6H_SFLOAT_BLOCK |
+		VK_FORMAT_BC7_UNORM_BLOCK | VK_FORMAT_BC7_SRGB_BLOCK | VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK | VK_FORMAT_ETC2_R8G8B8A8_SRGB_BLOCK |
+		VK_FORMAT_EAC_R11G11_UNORM_BLOCK | VK_FORMAT_EAC_R11G11_SNORM_BLOCK => Some((4, 4, 16)),
+		VK_FORMAT_ASTC_4x4_UNORM_BLOCK | VK_FORMAT_ASTC_4x4_SRGB_BLOCK => Some((4, 4, 16)),
+		VK_FORMAT_ASTC_5x4_UNORM_BLOCK | VK_FORMAT_ASTC_5x4_SRGB_BLOCK => Some((5, 4, 16)),
+		VK_FORMAT_ASTC_5x5_UNORM_BLOCK | VK_FORMAT_ASTC_5x5_SRGB_BLOCK => Some((5, 5, 16)),
+		VK_FORMAT_ASTC_6x5_UNORM_BLOCK | VK_FORMAT_ASTC_6x5_SRGB_BLOCK => Some((6, 5, 16)),
+		VK_FORMAT_ASTC_6x6_UNORM_BLOCK | VK_FORMAT_ASTC_6x6_SRGB_BLOCK => Some((6, 6, 16)),
+		VK_FORMAT_ASTC_8x5_UNORM_BLOCK | VK_FORMAT_ASTC_8x5_SRGB_BLOCK => Some((8, 5, 16)),
+		VK_FORMAT_ASTC_8x6_UNORM_BLOCK | VK_FORMAT_ASTC_8x6_SRGB_BLOCK => Some((8, 6, 16)),
+		VK_FORMAT_ASTC_8x8_UNORM_BLOCK | VK_FORMAT_ASTC_8x8_SRGB_BLOCK => Some((8, 8, 16)),
+		VK_FORMAT_ASTC_10x5_UNORM_BLOCK | VK_FORMAT_ASTC_10x5_SRGB_BLOCK => Some((10, 5, 16)),
+		VK_FORMAT_ASTC_10x6_UNORM_BLOCK | VK_FORMAT_ASTC_10x6_SRGB_BLOCK => Some((10, 6, 16)),
+		VK_FORMAT_ASTC_10x8_UNORM_BLOCK | VK_FORMAT_ASTC_10x8_SRGB_BLOCK => Some((10, 8, 16)),
+		VK_FORMAT_ASTC_10x10_UNORM_BLOCK | VK_FORMAT_ASTC_10x10_SRGB_BLOCK => Some((10, 10, 16)),
+		VK_FORMAT_ASTC_12x10_UNORM_BLOCK | VK_FORMAT_ASTC_12x10_SRGB_BLOCK => Some((12, 10, 16)),
+		VK_FORMAT_ASTC_12x12_UNORM_BLOCK | VK_FORMAT_ASTC_12x12_SRGB_BLOCK => Some((12, 12, 16)),
+		_ => None
+	}
+}
+/// Size in bytes of tightly packed image data
+/// The aspect that tightly packed data of the format(in `format_block`) is copied into
+pub(crate) fn format_aspect(format: VkFormat) -> ImageAspect
+{
+	match format
+	{
+		VK_FORMAT_D16_UNORM | VK_FORMAT_X8_D24_UNORM_PACK32 | VK_FORMAT_D32_SFLOAT => ImageAspect::Depth,
+		VK_FORMAT_S8_UINT => ImageAspect::Stencil,
+		_ => ImageAspect::Color
+	}
+}
+pub(crate) fn packed_image_size(format: VkFormat, size: &Size3) -> Option<usize>
+{
+	format_block(format).map(|(bw, bh, bytes)|
+		((size.0 + bw - 1) / bw) as usize * ((size.1 + bh - 1) / bh) as usize * size.2 as usize * bytes)
+}
+
+// images are destroyed before the memory bound to them
+enum StagingBacking { Images(Vec<LinearImage>, MemoryAllocation), Buffer(StagingBuffer, Vec<(Size2, VkFormat)>) }
+/// Host-visible images for staging: linear images, or tightly packed regions of a plain buffer
 pub struct StagingImages
 {
-	memory: VkDeviceMemory, parent: Rc<Device>, resources: Vec<LinearImage>,
-	placement_offsets: Vec<VkDeviceSize>, size: VkDeviceSize
+	backing: StagingBacking, placement_offsets: Vec<VkDeviceSize>, size: VkDeviceSize
 }
 impl StagingImages
 {
-	fn placement(resources: &[LinearImage]) -> EngineResult<(Vec<VkDeviceSize>, VkDeviceSize)>
+	fn new(engine: &GraphicsInterface, resources: Vec<LinearImage>) -> EngineResult<Self>
 	{
-		let (mut current_offset, mut offsets) = (0, Vec::with_capacity(resources.len()));
-		for ireq in resources.iter().map(RequireMemory::memory_requirements)
+		let (offsets, mreq) = place_images(resources.iter().map(RequireMemory::memory_requirements))?;
+		info!(target: "Interlude::Resource", "Going to allocate images for host: {} bytes total", mreq.size);
+
+		let memory_type = engine.memory_type_index(mreq.memoryTypeBits, VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT, VK_MEMORY_PROPERTY_HOST_COHERENT_BIT)?;
+		let memory = engine.allocator().allocate(memory_type, &mreq, ResourceTiling::Linear)?;
+		for (&offs, &LinearImage(ref o)) in offsets.iter().zip(resources.iter())
 		{
-			let offset = alignment(current_offset as _, ireq.alignment as _) as _;
-			offsets.push(offset);
-			current_offset = offset + ireq.size;
+			unsafe { vkBindImageMemory(engine.device().native(), o.native(), memory.native(), memory.offset() + offs) }.into_result()?;
 		}
-		if current_offset == 0 { Err(EngineError::AllocateMemoryWithEmptyResources) }
-		else { Ok((offsets, current_offset)) }
+		Ok(StagingImages { backing: StagingBacking::Images(resources, memory), placement_offsets: offsets, size: mreq.size })
 	}
-	fn new(engine: &GraphicsInterface, resources: Vec<LinearImage>) -> EngineResult<Self>
+	fn new_buffer(engine: &GraphicsInterface, images: Vec<(Size2, VkFormat)>) -> EngineResult<Self>
 	{
-		let (offsets, size) = Self::placement(&resources)?;
-		info!(target: "Interlude::Resource", "Going to allocate images for host: {} bytes total", size);
-
-		let mut memory = unsafe { reserved() };
-		unsafe { vkAllocateMemory(engine.device().native(), &VkMemoryAllocateInfo
+		// offsets must be multiples of the texel block size and 4
+		let optimal_align = (engine.device_limits.optimalBufferCopyOffsetAlignment as usize).max(1);
+		let (mut current_offset, mut offsets) = (0, Vec::with_capacity(images.len()));
+		for &(ref size, format) in &images
 		{
-			allocationSize: size, memoryTypeIndex: engine.memindex(MemoryIndexType::HostVisible), .. Default::default()
-		}, null(), &mut memory) }.into_result()?;
-		for (&offs, &LinearImage(ref o)) in offsets.iter().zip(resources.iter())
-		{
-			unsafe { vkBindImageMemory(engine.device().native(), o.native(), memory, offs) }.into_result()?;
+			const UNSUPPORTED: EngineError = EngineError::GenericError("Unsupported format for buffer staging");
+			let bytes = packed_image_size(format, &size.clone().into()).ok_or(UNSUPPORTED)?;
+			let block_bytes = format_block(format).ok_or(UNSUPPORTED)?.2;
+			let offset = alignment(current_offset, lcm(lcm(block_bytes, 4), optimal_align));
+			offsets.push(offset as VkDeviceSize);
+			current_offset = offset + bytes;
 		}
-		Ok(StagingImages { memory, parent: engine.device().clone(), resources, placement_offsets: offsets, size })
+		info!(target: "Interlude::Resource", "Going to allocate staging buffer for images: {} bytes total", current_offset);
+
+		let buffer = StagingBuffer::new(engine, current_offset as _, 0)?;
+		Ok(StagingImages
+		{
+			backing: StagingBacking::Buffer(buffer, images),
+			placement_offsets: offsets, size: current_offset as _
+		})
+	}
+
+	fn memory(&self) -> &MemoryAllocation
+	{
+		match self.backing { StagingBacking::Images(_, ref m) => m, StagingBacking::Buffer(ref b, _) => &b.memory }
 	}
 
 	pub fn offsets(&self) -> &[VkDeviceSize] { &self.placement_offsets }
 	pub fn size(&self) -> VkDeviceSize { self.size }
+	/// The staging buffer if staged into a plain buffer
+	pub fn buffer(&self) -> Option<&StagingBuffer>
+	{
+		match self.backing { StagingBacking::Buffer(ref b, _) => Some(b), _ => None }
+	}
+	/// Copy region for the `index`-th image staged into a plain buffer(base mip level and array layer of the aspect of the format)
+	pub fn buffer_copy_region(&self, index: usize) -> Option<BufferImageCopyRegion>
+	{
+		match self.backing
+		{
+			StagingBacking::Buffer(_, ref images) => Some(BufferImageCopyRegion::packed(self.placement_offsets[index] as _,
+				ImageSubresourceLayers { aspect: format_aspect(images[index].1).into(), .. Default::default() }, &images[index].0.clone().into())),
+			_ => None
+		}
+	}
 }
-impl Deref for StagingImages { type Target = [LinearImage]; fn deref(&self) -> &[LinearImage] { &self.resources } }
-impl Drop for DeviceImages
-{
-	fn drop(&mut self) { unsafe { vkFreeMemory(self.parent.native(), self.memory, null()) }; }
-}
-impl Drop for StagingImages
+/// Linear images(empty if staged into a plain buffer)
+impl Deref for StagingImages
 {
-	fn drop(&mut self) { unsafe { vkFreeMemory(self.parent.native(), self.memory, null()) }; }
+	type Target = [LinearImage];
+	fn deref(&self) -> &[LinearImage] { match self.backing { StagingBacking::Images(ref r, _) => r, _ => &[] } }
 }
 
 /// Accessible Resource from Host, Supports map into host memory and unmap from host memory operations
+/// (ranges in non-coherent memory are invalidated on map and flushed on unmap automatically)
 pub trait StagingResource { fn map(&self) -> EngineResult<MappedRange>; fn unmap(&self); }
 impl StagingResource for StagingBuffer
 {
-	fn map(&self) -> EngineResult<MappedRange>
-	{
-		let mut ptr = null_mut();
-		unsafe { vkMapMemory(self.parent.native(), self.memory, 0, self.size, 0, &mut ptr) }.make_result_with(|| MappedRange { caller: self, ptr })
-	}
-	fn unmap(&self) { unsafe { vkUnmapMemory(self.parent.native(), self.memory) }; }
+	fn map(&self) -> EngineResult<MappedRange> { self.memory.map().map(|ptr| MappedRange { caller: self, ptr }) }
+	fn unmap(&self) { self.memory.unmap(); }
 }
 impl StagingResource for StagingImages
 {
-	fn map(&self) -> EngineResult<MappedRange>
-	{
-		let mut ptr = null_mut();
-		unsafe { vkMapMemory(self.parent.native(), self.memory, 0, self.size, 0, &mut ptr) }.make_result_with(|| MappedRange { caller: self, ptr })
-	}
-	fn unmap(&self) { unsafe { vkUnmapMemory(self.parent.native(), self.memory) }; }
+	fn map(&self) -> EngineResult<MappedRange> { self.memory().map().map(|ptr| MappedRange { caller: self, ptr }) }
+	fn unmap(&self) { self.memory().unmap(); }
 }
 
 pub struct MappedRange<'a> { caller: &'a StagingResource, ptr: *mut c_void }
@@ -536,22 +677,52 @@ impl<'a> Drop for MappedRange<'a>
 	fn drop(&mut self) { if !self.ptr.is_null() { self.caller.unmap(); } }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)] #[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] #[repr(u8)]
 pub enum Filter
 {
 	Nearest = VK_FILTER_NEAREST as u8, Linear = VK_FILTER_LINEAR as u8
 }
-#[derive(Clone, Copy, Debug, PartialEq, Eq)] #[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] #[repr(u8)]
+pub enum MipmapMode
+{
+	Nearest = VK_SAMPLER_MIPMAP_MODE_NEAREST as u8, Linear = VK_SAMPLER_MIPMAP_MODE_LINEAR as u8
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] #[repr(u8)]
 pub enum AddressMode
 {
+	/// Clamps to the border color
 	Clamp = VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_BORDER as u8, Repeat = VK_SAMPLER_ADDRESS_MODE_REPEAT as u8,
-	Mirror = VK_SAMPLER_ADDRESS_MODE_MIRRORED_REPEAT as u8
-}
+	Mirror = VK_SAMPLER_ADDRESS_MODE_MIRRORED_REPEAT as u8, ClampToEdge = VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_EDGE as u8,
+	/// Requires VK_KHR_sampler_mirror_clamp_to_edge(enabled automatically if supported)
+	MirrorClampToEdge = VK_SAMPLER_ADDRESS_MODE_MIRROR_CLAMP_TO_EDGE as u8
+}
+/// Color for texels outside the image with `AddressMode::Clamp`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] #[repr(u8)]
+pub enum BorderColor
+{
+	TransparentBlack = VK_BORDER_COLOR_FLOAT_TRANSPARENT_BLACK as u8, OpaqueBlack = VK_BORDER_COLOR_FLOAT_OPAQUE_BLACK as u8,
+	OpaqueWhite = VK_BORDER_COLOR_FLOAT_OPAQUE_WHITE as u8,
+	/// Variants for integer formats
+	IntTransparentBlack = VK_BORDER_COLOR_INT_TRANSPARENT_BLACK as u8, IntOpaqueBlack = VK_BORDER_COLOR_INT_OPAQUE_BLACK as u8,
+	IntOpaqueWhite = VK_BORDER_COLOR_INT_OPAQUE_WHITE as u8
+}
+/// Parameters of Sampler. Floating-point values are compared by their bits, so that states can be keys of caches
+#[derive(Clone, Debug)]
 pub struct SamplerState
 {
-	pub mag_filter: Filter, pub min_filter: Filter,
+	pub mag_filter: Filter, pub min_filter: Filter, pub mipmap_mode: MipmapMode,
 	pub address_mode: (AddressMode, AddressMode, AddressMode),
-	pub max_anisotropy: Option<f32>
+	pub max_anisotropy: Option<f32>,
+	/// Bias added to the computed level of detail
+	pub mip_lod_bias: f32,
+	/// Range of the level of detail to be clamped into. The end is `VK_LOD_CLAMP_NONE` to use all levels
+	pub lod: Range<f32>,
+	pub border_color: BorderColor,
+	/// Depth-compare operation for shadow maps(the sampler must be used with `samplerShadow` types in shaders)
+	pub compare_op: Option<CompareOp>,
+	/// Addresses in texels instead of [0, 1]. Requires equal mag/min filters, Nearest mipmap mode, zero LOD range,
+	/// Clamp/ClampToEdge address modes, and no anisotropy or compare operation
+	pub unnormalized_coordinates: bool
 }
 impl Default for SamplerState
 {
@@ -559,22 +730,74 @@ impl Default for SamplerState
 	{
 		SamplerState
 		{
-			mag_filter: Filter::Nearest, min_filter: Filter::Nearest,
+			mag_filter: Filter::Nearest, min_filter: Filter::Nearest, mipmap_mode: MipmapMode::Linear,
 			address_mode: (AddressMode::Clamp, AddressMode::Clamp, AddressMode::Clamp),
-			max_anisotropy: None
+			max_anisotropy: None, mip_lod_bias: 0.0, lod: 0.0 .. VK_LOD_CLAMP_NONE, border_color: BorderColor::TransparentBlack,
+			compare_op: None, unnormalized_coordinates: false
 		}
 	}
 }
+impl SamplerState
+{
+	/// Bilinear/trilinear filtering in all directions
+	pub fn linear(address_mode: AddressMode) -> Self
+	{
+		SamplerState
+		{
+			mag_filter: Filter::Linear, min_filter: Filter::Linear, mipmap_mode: MipmapMode::Linear,
+			address_mode: (address_mode, address_mode, address_mode), .. Default::default()
+		}
+	}
+	/// Hardware PCF sampler for shadow maps: linear filtering of comparison results, white(lit) outside the map
+	pub fn shadow_map(op: CompareOp) -> Self
+	{
+		SamplerState
+		{
+			mag_filter: Filter::Linear, min_filter: Filter::Linear, mipmap_mode: MipmapMode::Nearest,
+			border_color: BorderColor::OpaqueWhite, compare_op: Some(op), .. Default::default()
+		}
+	}
+	fn key(&self) -> (Filter, Filter, MipmapMode, (AddressMode, AddressMode, AddressMode), Option<u32>, u32, (u32, u32), BorderColor, Option<CompareOp>, bool)
+	{
+		(self.mag_filter, self.min_filter, self.mipmap_mode, self.address_mode, self.max_anisotropy.map(f32::to_bits), self.mip_lod_bias.to_bits(),
+			(self.lod.start.to_bits(), self.lod.end.to_bits()), self.border_color, self.compare_op, self.unnormalized_coordinates)
+	}
+	fn address_modes(&self) -> [AddressMode; 3] { [self.address_mode.0, self.address_mode.1, self.address_mode.2] }
+	fn validate(&self, engine: &GraphicsInterface) -> EngineResult<()>
+	{
+		if self.address_modes().iter().any(|&m| m == AddressMode::MirrorClampToEdge) && !engine.device_extension_enabled("VK_KHR_sampler_mirror_clamp_to_edge")
+		{
+			return Err(EngineError::GenericError("MirrorClampToEdge address mode requires VK_KHR_sampler_mirror_clamp_to_edge"));
+		}
+		if self.max_anisotropy.map_or(false, |a| a > engine.device_limits.maxSamplerAnisotropy)
+		{
+			warn!(target: "Interlude::Sampler", "Max anisotropy {:?} exceeds the device limit {}", self.max_anisotropy, engine.device_limits.maxSamplerAnisotropy);
+		}
+		if self.unnormalized_coordinates && (self.mag_filter != self.min_filter || self.mipmap_mode != MipmapMode::Nearest || self.lod != (0.0 .. 0.0)
+			|| self.address_modes().iter().any(|&m| m != AddressMode::Clamp && m != AddressMode::ClampToEdge)
+			|| self.max_anisotropy.is_some() || self.compare_op.is_some())
+		{
+			return Err(EngineError::GenericError("Invalid sampler state for unnormalized coordinates"));
+		}
+		Ok(())
+	}
+}
+impl PartialEq for SamplerState { fn eq(&self, other: &Self) -> bool { self.key() == other.key() } }
+impl Eq for SamplerState {}
+impl Hash for SamplerState { fn hash<H: Hasher>(&self, state: &mut H) { self.key().hash(state); } }
 impl<'a> Into<VkSamplerCreateInfo> for &'a SamplerState
 {
 	fn into(self) -> VkSamplerCreateInfo
 	{
 		VkSamplerCreateInfo
 		{
-			magFilter: self.mag_filter as _, minFilter: self.min_filter as _, mipmapMode: VK_SAMPLER_MIPMAP_MODE_LINEAR,
+			magFilter: self.mag_filter as _, minFilter: self.min_filter as _, mipmapMode: self.mipmap_mode as _,
 			addressModeU: self.address_mode.0 as _, addressModeV: self.address_mode.1 as _, addressModeW: self.address_mode.2 as _,
+			mipLoadBias: self.mip_lod_bias,
 			anisotropyEnable: self.max_anisotropy.is_some() as _, maxAnisotropy: self.max_anisotropy.unwrap_or(0.0),
-			minLod: 0.0f32, maxLod: 1.0f32, borderColor: VK_BORDER_COLOR_FLOAT_TRANSPARENT_BLACK, .. Default::default()
+			compareEnable: self.compare_op.is_some() as _, compareOp: self.compare_op.unwrap_or(CompareOp::Never) as _,
+			minLod: self.lod.start, maxLod: self.lod.end, borderColor: self.border_color as _,
+			unnormalizedCoordinates: self.unnormalized_coordinates as _, .. Default::default()
 		}
 	}
 }
@@ -584,6 +807,7 @@ impl Sampler
 {
 	pub fn new(engine: &GraphicsInterface, info: &SamplerState) -> EngineResult<Self>
 	{
+		info.validate(engine)?;
 		let mut smp = 0 as _;
 		unsafe { vkCreateSampler(engine.device().native(), &info.into(), null(), &mut smp) }
 			.make_result_with(|| Sampler(smp, engine.device().clone()))
@@ -646,11 +870,18 @@ impl ImageView1D
 impl ImageView2D
 {
 	pub fn make(res: &Image2D, format: VkFormat, cm: ComponentMapping, subrange: &ImageSubresourceRange) -> EngineResult<Self>
+	{
+		Self::make_typed(res, format, cm, subrange, VK_IMAGE_VIEW_TYPE_2D)
+	}
+	/// `view_type` is one of VK_IMAGE_VIEW_TYPE_2D, 2D_ARRAY, CUBE or CUBE_ARRAY.
+	/// Cube views require the image to be created with `ImageDescriptor2::cube_compatible`
+	pub fn make_typed(res: &Image2D, format: VkFormat, cm: ComponentMapping, subrange: &ImageSubresourceRange, view_type: VkImageViewType)
+		-> EngineResult<Self>
 	{
 		let mut iv = unsafe { reserved() };
 		unsafe { vkCreateImageView((res.0).1.native(), &VkImageViewCreateInfo
 		{
-			image: transmute(res.internal()), viewType: VK_IMAGE_VIEW_TYPE_2D, format: format,
+			image: transmute(res.internal()), viewType: view_type, format: format,
 			components: cm.into(), subresourceRange: subrange.into(), .. Default::default()
 		}, null(), &mut iv) }.make_result_with(|| ImageView2D { internal: iv, parent: res.clone(), format })
 	}
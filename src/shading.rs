@@ -3,8 +3,8 @@
 use interlude_vk_defs::*;
 use interlude_vk_funport::*;
 use {
-	EngineResult, GraphicsInterface, PreciseRenderPass, AssetProvider, AssetPath, RenderPass, DescriptorSetLayout,
-	Format, FormatType, PackedPixelOrder
+	EngineResult, EngineError, GraphicsInterface, PreciseRenderPass, AssetProvider, AssetPath, RenderPass, DescriptorSetLayout,
+	Format, FormatType, PackedPixelOrder, ShaderReflection, SpecConstantType, SampleCount
 };
 use device::Device;
 use std::ffi::CString;
@@ -12,6 +12,7 @@ use std::ops::{Deref, DerefMut, Range, BitOr, BitOrAssign};
 use std::io::prelude::*;
 use std::fs::File;
 use std::rc::Rc;
+use std::cell::RefCell;
 use std::path::Path;
 use std::mem::{size_of, transmute, zeroed};
 use std::ptr::null;
@@ -28,7 +29,8 @@ pub enum ShaderStage
 	TessControl = VK_SHADER_STAGE_TESSELLATION_CONTROL_BIT as u8,
 	TessEvaluation = VK_SHADER_STAGE_TESSELLATION_EVALUATION_BIT as u8,
 	Geometry = VK_SHADER_STAGE_GEOMETRY_BIT as u8,
-	Fragment = VK_SHADER_STAGE_FRAGMENT_BIT as u8
+	Fragment = VK_SHADER_STAGE_FRAGMENT_BIT as u8,
+	Compute = VK_SHADER_STAGE_COMPUTE_BIT as u8
 }
 /// Set of Shader Stage bitflags
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
@@ -52,6 +54,10 @@ impl ShaderStageSet
 	pub fn has_tessellation_evaluation_bit(&self) -> bool { (self.0 & ShaderStage::TessEvaluation as VkFlags) != 0 }
 	pub fn has_geometry_bit(&self) -> bool { (self.0 & ShaderStage::Geometry as VkFlags) != 0 }
 	pub fn has_fragment_bit(&self) -> bool { (self.0 & ShaderStage::Fragment as VkFlags) != 0 }
+	pub fn has_compute_bit(&self) -> bool { (self.0 & ShaderStage::Compute as VkFlags) != 0 }
+	pub fn empty() -> Self { ShaderStageSet(0) }
+	/// True if all the stages in `other` are included
+	pub fn contains<S: Into<ShaderStageSet>>(&self, other: S) -> bool { let o = other.into().0; (self.0 & o) == o }
 	pub(crate) fn unwrap(self) -> VkShaderStageFlags { self.0 as _ }
 }
 BitCombinationWrapper!(for ShaderStageSet(ShaderStage));
@@ -62,38 +68,50 @@ impl Into<VkShaderStageFlags> for ShaderStageSet { fn into(self) -> VkShaderStag
 pub enum VertexBinding { PerVertex(u32), PerInstance(u32) }
 #[derive(Clone, Debug, PartialEq)]
 pub struct VertexAttribute(pub u32, pub Format, pub u32);
+#[derive(Clone)]
 pub struct IntoNativeVertexInputState
 {
 	bindings: Vec<VkVertexInputBindingDescription>,
 	attributes: Vec<VkVertexInputAttributeDescription>
 }
 
-/// Discrete Shader Module that holds shader program
-pub struct ShaderModule(VkShaderModule, Rc<Device>);
+/// Discrete Shader Module that holds shader program(and its SPIR-V code for reflection, parsed once on first use)
+pub struct ShaderModule(VkShaderModule, Rc<Device>, Vec<u32>, RefCell<Option<Rc<ShaderReflection>>>);
 struct VertexProcessing { module: ShaderModule, entry_point: CString, input_state: IntoNativeVertexInputState }
 struct ShaderModuleWithEntryPoint { module: ShaderModule, entry_point: CString }
 impl ShaderModule
 {
-	fn load_module(engine: &GraphicsInterface, path: &Path) -> EngineResult<VkShaderModule>
+	fn load_module(engine: &GraphicsInterface, path: &Path) -> EngineResult<Self>
 	{
 		let content = File::open(path).and_then(|mut fp| { let mut vb = Vec::new(); fp.read_to_end(&mut vb).map(|_| vb) })?;
+		if content.len() % 4 != 0 { return Err(EngineError::GenericError("SPIR-V code size must be a multiple of 4")); }
+		let code = content.chunks(4).map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24).collect::<Vec<_>>();
 		let mut smod = unsafe { zeroed() };
 		unsafe { vkCreateShaderModule(engine.device().native(), &VkShaderModuleCreateInfo
 		{
-			codeSize: content.len() as _, pCode: content.as_ptr() as _, .. Default::default()
-		}, null(), &mut smod) }.make_result(smod)
+			codeSize: (code.len() * 4) as _, pCode: code.as_ptr(), .. Default::default()
+		}, null(), &mut smod) }.make_result_with(|| ShaderModule(smod, engine.device().clone(), code, RefCell::new(None)))
 	}
 	pub fn from_asset<Engine: AssetProvider + Deref<Target = GraphicsInterface>, P: AssetPath>(engine: &Engine, path: P) -> EngineResult<Self>
 	{
 		let path = engine.parse_asset(path, "spv");
 		info!(target: "Interlude::ShaderProgram", "Loading Shader from {:?}...", path);
-		Self::load_module(engine, &path).map(|m| ShaderModule(m, engine.device().clone()))
+		Self::load_module(engine, &path)
 	}
 	fn from_asset_msg<Engine: AssetProvider + Deref<Target = GraphicsInterface>, P: AssetPath>(engine: &Engine, path: P, shader_msg: &str) -> EngineResult<Self>
 	{
 		let path = engine.parse_asset(path, "spv");
 		info!(target: "Interlude::ShaderProgram", "Loading {} from {:?}...", shader_msg, path);
-		Self::load_module(engine, &path).map(|m| ShaderModule(m, engine.device().clone()))
+		Self::load_module(engine, &path)
+	}
+	/// SPIR-V code in words
+	pub fn code(&self) -> &[u32] { &self.2 }
+	pub fn reflect(&self) -> EngineResult<Rc<ShaderReflection>>
+	{
+		if let Some(ref r) = *self.3.borrow() { return Ok(r.clone()); }
+		let r = Rc::new(ShaderReflection::parse(&self.2)?);
+		*self.3.borrow_mut() = Some(r.clone());
+		Ok(r)
 	}
 
 	pub fn into_vertex_shader(self, entry_point: &str, bindings: &[VertexBinding], attributes: &[VertexAttribute]) -> EngineResult<VertexShader>
@@ -141,6 +159,7 @@ pub trait Shader
 	// TODO: to be associated constant
 	fn stage(&self) -> ShaderStage;
 	fn entry_point_ptr(&self) -> *const c_char;
+	fn module(&self) -> &ShaderModule;
 }
 /// Shader Program for Vertex Processing Stage
 #[derive(Clone)] pub struct VertexShader(Rc<VertexProcessing>);
@@ -164,10 +183,28 @@ macro_rules! ImplShaderModule
 		{
 			fn stage(&self) -> ShaderStage { ShaderStage::$stg }
 			fn entry_point_ptr(&self) -> *const c_char { self.0.entry_point.as_ptr() }
+			fn module(&self) -> &ShaderModule { &self.0.module }
 		}
 	)* }
 }
-ImplShaderModule!(for VertexShader[Vertex], FragmentShader[Fragment], GeometryShader[Geometry], TessellationControlShader[TessControl], TessellationEvaluationShader[TessEvaluation]);
+ImplShaderModule!(for VertexShader[Vertex], FragmentShader[Fragment], GeometryShader[Geometry], TessellationControlShader[TessControl], TessellationEvaluationShader[TessEvaluation],
+	ComputeShader[Compute]);
+macro_rules! ImplShaderReload
+{
+	(for $($t: ident),*) =>
+	{ $(
+		impl $t
+		{
+			/// Recreates the module from the file, with the same entry point(for hot-reloading)
+			pub(crate) fn reload(&self, engine: &GraphicsInterface, path: &Path) -> EngineResult<Self>
+			{
+				let module = ShaderModule::load_module(engine, path)?;
+				Ok($t(Rc::new(ShaderModuleWithEntryPoint { module, entry_point: self.0.entry_point.clone() })))
+			}
+		}
+	)* }
+}
+ImplShaderReload!(for FragmentShader, GeometryShader, TessellationControlShader, TessellationEvaluationShader);
 impl VertexShader
 {
 	pub fn from_asset<Engine: AssetProvider + Deref<Target = GraphicsInterface>, P: AssetPath>(engine: &Engine, path: P, entry_point: &str,
@@ -182,6 +219,12 @@ impl VertexShader
 		Self::from_asset(engine, path, entry_point, &[VertexBinding::PerVertex(size_of::<PosUV>() as u32)],
 			&[VertexAttribute(0, Format::Component(32, PackedPixelOrder::RGBA, FormatType::Float), 0)])
 	}
+	/// Recreates the module from the file, with the same entry point and vertex inputs(for hot-reloading)
+	pub(crate) fn reload(&self, engine: &GraphicsInterface, path: &Path) -> EngineResult<Self>
+	{
+		let module = ShaderModule::load_module(engine, path)?;
+		Ok(VertexShader(Rc::new(VertexProcessing { module, entry_point: self.0.entry_point.clone(), input_state: self.0.input_state.clone() })))
+	}
 }
 impl TessellationControlShader
 {
@@ -281,38 +324,330 @@ pub enum CullingSide
 {
 	Front = VK_CULL_MODE_FRONT_BIT as u8, Back = VK_CULL_MODE_BACK_BIT as u8
 }
+/// Winding order of front-facing polygons(in framebuffer coordinates)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrontFace
+{
+	CounterClockwise = VK_FRONT_FACE_COUNTER_CLOCKWISE as _, Clockwise = VK_FRONT_FACE_CLOCKWISE as _
+}
+/// Depth Bias: constant_factor * (minimum resolvable difference) + slope_factor * (max depth slope), clamped to `clamp` if nonzero
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthBias { pub constant_factor: f32, pub slope_factor: f32, pub clamp: f32 }
 #[derive(Clone, Debug, PartialEq)]
 pub struct RasterizerState
 {
-	pub wired_render: bool, pub cull_side: Option<CullingSide>
+	pub wired_render: bool, pub cull_side: Option<CullingSide>, pub front_face: FrontFace,
+	pub depth_bias: Option<DepthBias>, pub line_width: f32, pub depth_clamp: bool, pub discard: bool
 }
-#[derive(Clone, Copy, Debug, PartialEq)]
+impl Default for RasterizerState
+{
+	fn default() -> Self
+	{
+		RasterizerState
+		{
+			wired_render: false, cull_side: None, front_face: FrontFace::CounterClockwise,
+			depth_bias: None, line_width: 1.0, depth_clamp: false, discard: false
+		}
+	}
+}
+/// Comparison Operator for depth/stencil tests
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CompareOp
+{
+	Never = VK_COMPARE_OP_NEVER as _, Less = VK_COMPARE_OP_LESS as _, Equal = VK_COMPARE_OP_EQUAL as _,
+	LessOrEqual = VK_COMPARE_OP_LESS_OR_EQUAL as _, Greater = VK_COMPARE_OP_GREATER as _, NotEqual = VK_COMPARE_OP_NOT_EQUAL as _,
+	GreaterOrEqual = VK_COMPARE_OP_GREATER_OR_EQUAL as _, Always = VK_COMPARE_OP_ALWAYS as _
+}
+/// Operation on stencil values
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StencilOp
+{
+	Keep = VK_STENCIL_OP_KEEP as _, Zero = VK_STENCIL_OP_ZERO as _, Replace = VK_STENCIL_OP_REPLACE as _,
+	IncrementAndClamp = VK_STENCIL_OP_INCREMENT_AND_CLAMP as _, DecrementAndClamp = VK_STENCIL_OP_DECREMENT_AND_CLAMP as _,
+	Invert = VK_STENCIL_OP_INVERT as _, IncrementAndWrap = VK_STENCIL_OP_INCREMENT_AND_WRAP as _, DecrementAndWrap = VK_STENCIL_OP_DECREMENT_AND_WRAP as _
+}
+/// Faces whose stencil states are updated by commands
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StencilFace
+{
+	Front = VK_STENCIL_FACE_FRONT_BIT as _, Back = VK_STENCIL_FACE_BACK_BIT as _, Both = VK_STENCIL_FRONT_AND_BACK as _
+}
+/// Stencil test and update parameters for front or back facing primitives
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StencilFaceState
+{
+	pub fail_op: StencilOp, pub pass_op: StencilOp, pub depth_fail_op: StencilOp,
+	pub compare_op: CompareOp, pub compare_mask: u32, pub write_mask: u32, pub reference: u32
+}
+impl Default for StencilFaceState
+{
+	/// Always passes, keeps stored values
+	fn default() -> Self
+	{
+		StencilFaceState
+		{
+			fail_op: StencilOp::Keep, pass_op: StencilOp::Keep, depth_fail_op: StencilOp::Keep,
+			compare_op: CompareOp::Always, compare_mask: !0, write_mask: !0, reference: 0
+		}
+	}
+}
+impl<'a> Into<VkStencilOpState> for &'a StencilFaceState
+{
+	fn into(self) -> VkStencilOpState
+	{
+		VkStencilOpState
+		{
+			failOp: self.fail_op as _, passOp: self.pass_op as _, depthFailOp: self.depth_fail_op as _,
+			compareOp: self.compare_op as _, compareMask: self.compare_mask, writeMask: self.write_mask, reference: self.reference
+		}
+	}
+}
+/// Depth/Stencil tests(ignored in subpasses without depth/stencil attachments)
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepthStencilState
+{
+	/// (compare operator, write enable)
+	pub depth_test: Option<(CompareOp, bool)>,
+	/// (min, max)
+	pub depth_bounds: Option<(f32, f32)>,
+	/// (front, back)
+	pub stencil_test: Option<(StencilFaceState, StencilFaceState)>
+}
+impl Default for DepthStencilState
+{
+	/// All tests are disabled
+	fn default() -> Self { DepthStencilState { depth_test: None, depth_bounds: None, stencil_test: None } }
+}
+impl<'a> Into<VkPipelineDepthStencilStateCreateInfo> for &'a DepthStencilState
+{
+	fn into(self) -> VkPipelineDepthStencilStateCreateInfo
+	{
+		let default_face = StencilFaceState::default();
+		let (front, back) = self.stencil_test.as_ref().map(|&(ref f, ref b)| (f, b)).unwrap_or((&default_face, &default_face));
+		let (min_bounds, max_bounds) = self.depth_bounds.unwrap_or((0.0, 1.0));
+		VkPipelineDepthStencilStateCreateInfo
+		{
+			depthTestEnable: self.depth_test.is_some() as _,
+			depthWriteEnable: self.depth_test.map(|(_, w)| w).unwrap_or(false) as _,
+			depthCompareOp: self.depth_test.map(|(op, _)| op).unwrap_or(CompareOp::Always) as _,
+			depthBoundsTestEnable: self.depth_bounds.is_some() as _, minDepthBounds: min_bounds, maxDepthBounds: max_bounds,
+			stencilTestEnable: self.stencil_test.is_some() as _, front: front.into(), back: back.into(),
+			.. Default::default()
+		}
+	}
+}
+/// Pipeline states specified by commands instead of baked in the pipeline
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DynamicState
+{
+	Viewport = VK_DYNAMIC_STATE_VIEWPORT as _, Scissor = VK_DYNAMIC_STATE_SCISSOR as _, LineWidth = VK_DYNAMIC_STATE_LINE_WIDTH as _,
+	DepthBias = VK_DYNAMIC_STATE_DEPTH_BIAS as _, BlendConstants = VK_DYNAMIC_STATE_BLEN_CONSTANTS as _, DepthBounds = VK_DYNAMIC_STATE_DEPTH_BOUNDS as _,
+	StencilCompareMask = VK_DYNAMIC_STATE_STENCIL_COMPARE_MASK as _, StencilWriteMask = VK_DYNAMIC_STATE_STENCIL_WRITE_MASk as _,
+	StencilReference = VK_DYNAMIC_STATE_STENCIL_REFERENCE as _
+}
+/// Blending of an attachment: presets or a fully custom state
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum AttachmentBlendState
 {
-	Disabled, AlphaBlend, PremultipliedAlphaBlend
+	Disabled, AlphaBlend, PremultipliedAlphaBlend,
+	/// src * src.a + dst(alpha is accumulated)
+	Additive,
+	/// src * dst
+	Multiplicative,
+	/// min(src, dst) / max(src, dst) per component
+	Min, Max,
+	Custom(CustomBlendState)
+}
+impl AttachmentBlendState
+{
+	/// The preset expressed as a custom state
+	pub fn custom(&self) -> CustomBlendState
+	{
+		use self::BlendFactor::*;
+		let eq = |src, dst, op| BlendEquation { src, dst, op };
+		match *self
+		{
+			AttachmentBlendState::Disabled => CustomBlendState::disabled(),
+			AttachmentBlendState::AlphaBlend => CustomBlendState::blended(eq(SrcAlpha, OneMinusSrcAlpha, BlendOp::Add), eq(One, OneMinusSrcAlpha, BlendOp::Add)),
+			AttachmentBlendState::PremultipliedAlphaBlend => CustomBlendState::blended(eq(One, OneMinusSrcAlpha, BlendOp::Add), eq(One, OneMinusSrcAlpha, BlendOp::Add)),
+			AttachmentBlendState::Additive => CustomBlendState::blended(eq(SrcAlpha, One, BlendOp::Add), eq(One, One, BlendOp::Add)),
+			AttachmentBlendState::Multiplicative => CustomBlendState::blended(eq(DstColor, Zero, BlendOp::Add), eq(DstAlpha, Zero, BlendOp::Add)),
+			AttachmentBlendState::Min => CustomBlendState::blended(eq(One, One, BlendOp::Min), eq(One, One, BlendOp::Min)),
+			AttachmentBlendState::Max => CustomBlendState::blended(eq(One, One, BlendOp::Max), eq(One, One, BlendOp::Max)),
+			AttachmentBlendState::Custom(c) => c
+		}
+	}
+}
+/// Factor multiplied to source/destination values(Src1 factors require the dualSrcBlend feature)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlendFactor
+{
+	Zero = VK_BLEND_FACTOR_ZERO as _, One = VK_BLEND_FACTOR_ONE as _,
+	SrcColor = VK_BLEND_FACTOR_SRC_COLOR as _, OneMinusSrcColor = VK_BLEND_FACTOR_ONE_MINUS_SRC_COLOR as _,
+	DstColor = VK_BLEND_FACTOR_DST_COLOR as _, OneMinusDstColor = VK_BLEND_FACTOR_ONE_MINUS_DST_COLOR as _,
+	SrcAlpha = VK_BLEND_FACTOR_SRC_ALPHA as _, OneMinusSrcAlpha = VK_BLEND_FACTOR_ONE_MINUS_SRC_ALPHA as _,
+	DstAlpha = VK_BLEND_FACTOR_DST_ALPHA as _, OneMinusDstAlpha = VK_BLEND_FACTOR_ONE_MINUS_DST_ALPHA as _,
+	ConstantColor = VK_BLEND_FACTOR_CONSTANT_COLOR as _, OneMinusConstantColor = VK_BLEND_FACTOR_ONE_MINUS_CONSTANT_COLOR as _,
+	ConstantAlpha = VK_BLEND_FACTOR_CONSTANT_ALPHA as _, OneMinusConstantAlpha = VK_BLEND_FACTOR_ONE_MINUS_CONSTANT_ALPHA as _,
+	SrcAlphaSaturate = VK_BLEND_FACTOR_SRC_ALPHA_SATURATE as _,
+	Src1Color = VK_BLEND_FACTOR_SRC1_COLOR as _, OneMinusSrc1Color = VK_BLEND_FACTOR_ONE_MINUS_SRC1_COLOR as _,
+	Src1Alpha = VK_BLEND_FACTOR_SRC1_ALPHA as _, OneMinusSrc1Alpha = VK_BLEND_FACTOR_ONE_MINUS_SRC1_ALPHA as _
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlendOp
+{
+	Add = VK_BLEND_OP_ADD as _, Subtract = VK_BLEND_OP_SUBTRACT as _, ReverseSubtract = VK_BLEND_OP_REVERSE_SUBTRACT as _,
+	Min = VK_BLEND_OP_MIN as _, Max = VK_BLEND_OP_MAX as _
+}
+/// src * src_factor (op) dst * dst_factor
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlendEquation { pub src: BlendFactor, pub dst: BlendFactor, pub op: BlendOp }
+/// Color Component bitflags
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] #[repr(u8)]
+pub enum ColorComponent
+{
+	R = VK_COLOR_COMPONENT_R_BIT as u8, G = VK_COLOR_COMPONENT_G_BIT as u8, B = VK_COLOR_COMPONENT_B_BIT as u8, A = VK_COLOR_COMPONENT_A_BIT as u8
+}
+/// Set of Color Component bitflags
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ColorComponentSet(VkFlags);
+BitCombinationWrapper!(for ColorComponentSet(ColorComponent));
+impl ColorComponentSet
+{
+	pub fn all() -> Self { ColorComponent::R | ColorComponent::G | ColorComponent::B | ColorComponent::A }
+	pub fn empty() -> Self { ColorComponentSet(0) }
+}
+impl Into<ColorComponentSet> for ColorComponent { fn into(self) -> ColorComponentSet { ColorComponentSet(self as _) } }
+/// Per-attachment blending. `blend` is the pair of equations for color and alpha(None to write the source as is)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CustomBlendState { pub blend: Option<(BlendEquation, BlendEquation)>, pub write_mask: ColorComponentSet }
+impl CustomBlendState
+{
+	pub fn disabled() -> Self { CustomBlendState { blend: None, write_mask: ColorComponentSet::all() } }
+	pub fn blended(color: BlendEquation, alpha: BlendEquation) -> Self { CustomBlendState { blend: Some((color, alpha)), write_mask: ColorComponentSet::all() } }
+	pub fn write_mask<M: Into<ColorComponentSet>>(mut self, mask: M) -> Self { self.write_mask = mask.into(); self }
+}
+impl Into<VkPipelineColorBlendAttachmentState> for CustomBlendState
+{
+	fn into(self) -> VkPipelineColorBlendAttachmentState
+	{
+		match self.blend
+		{
+			None => VkPipelineColorBlendAttachmentState { blendEnable: false as _, colorWriteMask: self.write_mask.0 as _, .. Default::default() },
+			Some((color, alpha)) => VkPipelineColorBlendAttachmentState
+			{
+				blendEnable: true as _,
+				srcColorBlendFactor: color.src as _, dstColorBlendFactor: color.dst as _, colorBlendOp: color.op as _,
+				srcAlphaBlendFactor: alpha.src as _, dstAlphaBlendFactor: alpha.dst as _, alphaBlendOp: alpha.op as _,
+				colorWriteMask: self.write_mask.0 as _
+			}
+		}
+	}
+}
+/// Logical operation applied instead of blending(requires the logicOp feature, only for integer/normalized attachments)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogicOp
+{
+	Clear = VK_LOGIC_OP_CLEAR as _, And = VK_LOGIC_OP_AND as _, AndReverse = VK_LOGIC_OP_AND_REVERSE as _, Copy = VK_LOGIC_OP_COPY as _,
+	AndInverted = VK_LOGIC_OP_AND_INVERTED as _, NoOp = VK_LOGIC_OP_NO_OP as _, Xor = VK_LOGIC_OP_XOR as _, Or = VK_LOGIC_OP_OR as _,
+	Nor = VK_LOGIC_OP_NOR as _, Equivalent = VK_LOGIC_OP_EQUIVALENT as _, Invert = VK_LOGIC_OP_INVERT as _, OrReverse = VK_LOGIC_OP_OR_REVERSE as _,
+	CopyInverted = VK_LOGIC_OP_COPY_INVERTED as _, OrInverted = VK_LOGIC_OP_OR_INVERTED as _, Nand = VK_LOGIC_OP_NAND as _, Set = VK_LOGIC_OP_SET as _
 }
 
-#[derive(Clone)]
+/// Value of a specialization constant
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ConstantEntry
 {
-	Float(f32), Uint(u32)
+	Bool(bool), Int(i32), Uint(u32), Float(f32), Double(f64)
 }
 impl ConstantEntry
 {
-	fn _sizeof(&self) -> usize { match self { &ConstantEntry::Float(_) | &ConstantEntry::Uint(_) => 4 } }
+	pub fn ty(&self) -> SpecConstantType
+	{
+		match *self
+		{
+			ConstantEntry::Bool(_) => SpecConstantType::Bool, ConstantEntry::Int(_) => SpecConstantType::Int,
+			ConstantEntry::Uint(_) => SpecConstantType::Uint, ConstantEntry::Float(_) => SpecConstantType::Float,
+			ConstantEntry::Double(_) => SpecConstantType::Double
+		}
+	}
+	pub fn size(&self) -> usize { self.ty().size() }
 	fn as_bytes(&self) -> Vec<u8>
 	{
-		match self
+		match *self
 		{
-			&ConstantEntry::Float(v) => Vec::from(&unsafe { transmute::<_, [u8; 4]>(v) }[..]),
-			&ConstantEntry::Uint(v) => Vec::from(&unsafe { transmute::<_, [u8; 4]>(v) }[..])
+			ConstantEntry::Bool(v) => Vec::from(&unsafe { transmute::<_, [u8; 4]>(v as VkBool32) }[..]),
+			ConstantEntry::Int(v) => Vec::from(&unsafe { transmute::<_, [u8; 4]>(v) }[..]),
+			ConstantEntry::Uint(v) => Vec::from(&unsafe { transmute::<_, [u8; 4]>(v) }[..]),
+			ConstantEntry::Float(v) => Vec::from(&unsafe { transmute::<_, [u8; 4]>(v) }[..]),
+			ConstantEntry::Double(v) => Vec::from(&unsafe { transmute::<_, [u8; 8]>(v) }[..])
 		}
 	}
 }
+impl From<bool> for ConstantEntry { fn from(v: bool) -> Self { ConstantEntry::Bool(v) } }
+impl From<i32> for ConstantEntry { fn from(v: i32) -> Self { ConstantEntry::Int(v) } }
+impl From<u32> for ConstantEntry { fn from(v: u32) -> Self { ConstantEntry::Uint(v) } }
+impl From<f32> for ConstantEntry { fn from(v: f32) -> Self { ConstantEntry::Float(v) } }
+impl From<f64> for ConstantEntry { fn from(v: f64) -> Self { ConstantEntry::Double(v) } }
+/// Shader with values of specialization constants(pairs of SpecId and value)
 pub struct PipelineShaderProgram<Module: Shader + Clone>(pub Module, pub Vec<(usize, ConstantEntry)>);
 impl<Module: Shader + Clone> PipelineShaderProgram<Module>
 {
 	pub fn unspecialized(shref: &Module) -> Self { PipelineShaderProgram(shref.clone(), Vec::new()) }
+	/// Sets the value of the constant decorated with `SpecId` = `id`
+	pub fn specialize<V: Into<ConstantEntry>>(mut self, id: u32, value: V) -> Self
+	{
+		self.1.push((id as _, value.into())); self
+	}
+	/// Sets the value of the constant found by its name(`OpName` must be kept in the SPIR-V code)
+	pub fn specialize_named<V: Into<ConstantEntry>>(self, name: &str, value: V) -> EngineResult<Self>
+	{
+		let id = self.0.module().reflect()?.specialization_constant_by_name(name).map(|c| c.id);
+		match id
+		{
+			Some(id) => Ok(self.specialize(id, value)),
+			None =>
+			{
+				error!(target: "Interlude::ShaderProgram", "No specialization constant named {:?} in the {:?} shader", name, self.0.stage());
+				Err(EngineError::GenericError("Specialization constant with the name is not found"))
+			}
+		}
+	}
+	/// Checks that each constant exists in the shader, is specified once and has the declared type.
+	/// Booleans and unsigned integers are interchangeable(both are passed as VkBool32-compatible 32bit values)
+	pub fn validate_specialization(&self) -> EngineResult<()>
+	{
+		if self.1.is_empty() { return Ok(()); }
+		let reflection = self.0.module().reflect()?;
+		for (i, &(id, ref value)) in self.1.iter().enumerate()
+		{
+			if self.1[..i].iter().any(|&(p, _)| p == id)
+			{
+				error!(target: "Interlude::ShaderProgram", "Specialization constant #{} is specified more than once for the {:?} shader", id, self.0.stage());
+				return Err(EngineError::GenericError("Specialization constant is specified more than once"));
+			}
+			match reflection.specialization_constant(id as _)
+			{
+				None =>
+				{
+					error!(target: "Interlude::ShaderProgram", "Specialization constant #{} does not exist in the {:?} shader", id, self.0.stage());
+					return Err(EngineError::GenericError("Specializing a constant which does not exist in the shader"));
+				},
+				Some(c) => match (c.ty, value.ty())
+				{
+					(SpecConstantType::Bool, SpecConstantType::Uint) | (SpecConstantType::Uint, SpecConstantType::Bool) => (),
+					(declared, specified) if declared != specified =>
+					{
+						error!(target: "Interlude::ShaderProgram", "Specialization constant #{}({}) in the {:?} shader is declared as {:?}, but specified {:?}",
+							id, c.name.as_ref().map_or("<unnamed>", |n| n), self.0.stage(), c.ty, value);
+						return Err(EngineError::GenericError("Type of the specialization constant does not match"));
+					},
+					_ => ()
+				}
+			}
+		}
+		Ok(())
+	}
 }
 impl<Module: Shader + Clone> Clone for PipelineShaderProgram<Module>
 {
@@ -334,7 +669,10 @@ pub struct GraphicsPipelineBuilder<'a>
 	geometry_shader: Option<PipelineShaderProgram<GeometryShader>>,
 	fragment_shader: Option<PipelineShaderProgram<FragmentShader>>,
 	primitive_topology: PrimitiveTopology, vp_sc: Vec<ViewportWithScissorRect>,
-	rasterizer_state: RasterizerState, use_alpha_to_coverage: bool, attachment_blend_states: Vec<AttachmentBlendState>
+	rasterizer_state: RasterizerState, samples: SampleCount, min_sample_shading: Option<f32>,
+	use_alpha_to_coverage: bool, attachment_blend_states: Vec<AttachmentBlendState>,
+	logic_op: Option<LogicOp>, blend_constants: [f32; 4],
+	depth_stencil_state: DepthStencilState, dynamic_states: Vec<DynamicState>
 }
 impl<'a> GraphicsPipelineBuilder<'a>
 {
@@ -345,8 +683,10 @@ impl<'a> GraphicsPipelineBuilder<'a>
 			layout: layout, render_pass: render_pass.0, subpass_index: render_pass.1,
 			vertex_shader: None, tesscontrol_shader: None, tessevaluation_shader: None, geometry_shader: None, fragment_shader: None,
 			primitive_topology: PrimitiveTopology::TriangleList(false),
-			vp_sc: Vec::new(), rasterizer_state: RasterizerState { wired_render: false, cull_side: None },
-			use_alpha_to_coverage: false, attachment_blend_states: Vec::new()
+			vp_sc: Vec::new(), rasterizer_state: Default::default(), samples: VK_SAMPLE_COUNT_1_BIT, min_sample_shading: None,
+			use_alpha_to_coverage: false, attachment_blend_states: Vec::new(), logic_op: None, blend_constants: [0.0; 4],
+			depth_stencil_state: Default::default(),
+			dynamic_states: Vec::new()
 		}
 	}
 	pub fn inherit(base: &GraphicsPipelineBuilder<'a>) -> Self
@@ -360,7 +700,10 @@ impl<'a> GraphicsPipelineBuilder<'a>
 			geometry_shader: base.geometry_shader.clone(),
 			fragment_shader: base.fragment_shader.clone(),
 			primitive_topology: base.primitive_topology, vp_sc: base.vp_sc.clone(), rasterizer_state: base.rasterizer_state.clone(),
-			use_alpha_to_coverage: base.use_alpha_to_coverage, attachment_blend_states: base.attachment_blend_states.clone()
+			samples: base.samples, min_sample_shading: base.min_sample_shading,
+			use_alpha_to_coverage: base.use_alpha_to_coverage, attachment_blend_states: base.attachment_blend_states.clone(),
+			logic_op: base.logic_op, blend_constants: base.blend_constants,
+			depth_stencil_state: base.depth_stencil_state.clone(), dynamic_states: base.dynamic_states.clone()
 		}
 	}
 	pub fn for_postprocess<Engine: AssetProvider + Deref<Target = GraphicsInterface>>(engine: &'a Engine, layout: &'a PipelineLayout,
@@ -374,8 +717,9 @@ impl<'a> GraphicsPipelineBuilder<'a>
 			geometry_shader: None, fragment_shader: Some(fragment_shader),
 			primitive_topology: PrimitiveTopology::TriangleStrip(false),
 			vp_sc: vec![ViewportWithScissorRect::default_scissor(processing_viewport)],
-			rasterizer_state: RasterizerState { wired_render: false, cull_side: None },
-			use_alpha_to_coverage: false, attachment_blend_states: vec![AttachmentBlendState::Disabled]
+			rasterizer_state: Default::default(), samples: VK_SAMPLE_COUNT_1_BIT, min_sample_shading: None,
+			use_alpha_to_coverage: false, attachment_blend_states: vec![AttachmentBlendState::Disabled], logic_op: None, blend_constants: [0.0; 4],
+			depth_stencil_state: Default::default(), dynamic_states: Vec::new()
 		})
 	}
 
@@ -399,6 +743,125 @@ impl<'a> GraphicsPipelineBuilder<'a>
 	{
 		self.fragment_shader = Some(fshader); self
 	}
+	/// Replaces the shader of the stage with the one recreated from the file, keeping its specialization(for hot-reloading)
+	pub(crate) fn reload_shader(mut self, engine: &GraphicsInterface, stage: ShaderStage, path: &Path) -> EngineResult<Self>
+	{
+		const NOT_SET: EngineError = EngineError::GenericError("Reloading a shader stage which is not set to the builder");
+		match stage
+		{
+			ShaderStage::Vertex => { let s = self.vertex_shader.as_mut().ok_or(NOT_SET)?; s.0 = s.0.reload(engine, path)?; },
+			ShaderStage::TessControl => { let s = self.tesscontrol_shader.as_mut().ok_or(NOT_SET)?; s.0 = s.0.reload(engine, path)?; },
+			ShaderStage::TessEvaluation => { let s = self.tessevaluation_shader.as_mut().ok_or(NOT_SET)?; s.0 = s.0.reload(engine, path)?; },
+			ShaderStage::Geometry => { let s = self.geometry_shader.as_mut().ok_or(NOT_SET)?; s.0 = s.0.reload(engine, path)?; },
+			ShaderStage::Fragment => { let s = self.fragment_shader.as_mut().ok_or(NOT_SET)?; s.0 = s.0.reload(engine, path)?; },
+			ShaderStage::Compute => return Err(NOT_SET)
+		}
+		Ok(self)
+	}
+	/// Checks the rasterizer, multisample and depth bounds states against the enabled device features and limits
+	fn validate_rasterizer_state(&self, engine: &GraphicsInterface) -> EngineResult<()>
+	{
+		let subpass = self.render_pass.subpass_attachments(self.subpass_index).ok_or(EngineError::GenericError("Subpass index is out of the render pass"))?;
+		let limits = &engine.device_limits;
+		// sample counts supported by all kinds of attachments used in the subpass
+		let mut supported = if subpass.color { limits.framebufferColorSampleCounts }
+			else if subpass.depth || subpass.stencil { !0 } else { limits.framebufferNoAttachmentsSampleCounts };
+		if subpass.depth { supported &= limits.framebufferDepthSampleCounts; }
+		if subpass.stencil { supported &= limits.framebufferStencilSampleCounts; }
+		if !self.samples.is_power_of_two() || (supported & self.samples) == 0
+		{
+			error!(target: "Interlude::Pipeline", "Sample count {} is not supported for the attachments of the subpass(supported: {:#x})", self.samples, supported);
+			return Err(EngineError::GenericError("Unsupported rasterization sample count"));
+		}
+		if subpass.samples.iter().any(|&s| (s & self.samples) == 0)
+		{
+			error!(target: "Interlude::Pipeline", "Sample count {} does not match the attachments of the subpass({:?})", self.samples, subpass.samples);
+			return Err(EngineError::GenericError("Rasterization sample count does not match the subpass attachments"));
+		}
+		if let Some(f) = self.min_sample_shading
+		{
+			if engine.enabled_features().sampleRateShading == false as VkBool32
+			{
+				return Err(EngineError::GenericError("Sample shading requires the sampleRateShading feature, which is not supported by the device"));
+			}
+			if f < 0.0 || 1.0 < f { return Err(EngineError::GenericError("Minimum sample shading fraction must be in 0.0 - 1.0")); }
+		}
+		let (state, features, limits) = (&self.rasterizer_state, engine.enabled_features(), &engine.device_limits);
+		if state.depth_clamp && features.depthClamp == false as VkBool32
+		{
+			return Err(EngineError::GenericError("Depth clamp requires the depthClamp feature, which is not supported by the device"));
+		}
+		let dynamic_bounds = self.dynamic_states.contains(&DynamicState::DepthBounds);
+		if (dynamic_bounds || self.depth_stencil_state.depth_bounds.is_some()) && features.depthBounds == false as VkBool32
+		{
+			return Err(EngineError::GenericError("Depth bounds test requires the depthBounds feature, which is not supported by the device"));
+		}
+		if let (false, Some((min, max))) = (dynamic_bounds, self.depth_stencil_state.depth_bounds)
+		{
+			if min < 0.0 || 1.0 < min || max < 0.0 || 1.0 < max { return Err(EngineError::GenericError("Depth bounds must be in 0.0 - 1.0")); }
+		}
+		if !self.dynamic_states.contains(&DynamicState::DepthBias) && state.depth_bias.map_or(false, |b| b.clamp != 0.0)
+			&& features.depthBiasClamp == false as VkBool32
+		{
+			return Err(EngineError::GenericError("Depth bias clamp requires the depthBiasClamp feature, which is not supported by the device"));
+		}
+		if !self.dynamic_states.contains(&DynamicState::LineWidth)
+		{
+			if state.line_width != 1.0 && features.wideLines == false as VkBool32
+			{
+				return Err(EngineError::GenericError("Line width other than 1.0 requires the wideLines feature, which is not supported by the device"));
+			}
+			if state.line_width < limits.lineWidthRange[0] || limits.lineWidthRange[1] < state.line_width
+			{
+				error!(target: "Interlude::Pipeline", "Line width {} is out of the device range {:?}", state.line_width, limits.lineWidthRange);
+				return Err(EngineError::GenericError("Line width is out of the supported range"));
+			}
+		}
+		Ok(())
+	}
+	/// Checks the color blend state against the enabled device features and limits
+	fn validate_blend_state(&self, engine: &GraphicsInterface) -> EngineResult<()>
+	{
+		let (features, limits) = (engine.enabled_features(), &engine.device_limits);
+		if self.logic_op.is_some() && features.logicOp == false as VkBool32
+		{
+			return Err(EngineError::GenericError("Logic operations require the logicOp feature, which is not supported by the device"));
+		}
+		let states = self.attachment_blend_states.iter().map(AttachmentBlendState::custom).collect::<Vec<_>>();
+		if states.iter().any(|s| *s != states[0]) && features.independentBlend == false as VkBool32
+		{
+			return Err(EngineError::GenericError("Different blend states per attachment require the independentBlend feature, which is not supported by the device"));
+		}
+		let is_dual_source = |f: BlendFactor| match f
+		{
+			BlendFactor::Src1Color | BlendFactor::OneMinusSrc1Color | BlendFactor::Src1Alpha | BlendFactor::OneMinusSrc1Alpha => true,
+			_ => false
+		};
+		let dual_source = states.iter().filter_map(|s| s.blend)
+			.any(|(color, alpha)| is_dual_source(color.src) || is_dual_source(color.dst) || is_dual_source(alpha.src) || is_dual_source(alpha.dst));
+		if dual_source
+		{
+			if features.dualSrcBlend == false as VkBool32
+			{
+				return Err(EngineError::GenericError("Src1 blend factors require the dualSrcBlend feature, which is not supported by the device"));
+			}
+			if states.len() > limits.maxFragmentDualSrcAttachments as usize
+			{
+				error!(target: "Interlude::Pipeline", "Dual source blending is used with {} attachments(device limit: {})", states.len(), limits.maxFragmentDualSrcAttachments);
+				return Err(EngineError::GenericError("Too many attachments for dual source blending"));
+			}
+		}
+		Ok(())
+	}
+	fn validate_specializations(&self) -> EngineResult<()>
+	{
+		if let Some(ref s) = self.vertex_shader { s.validate_specialization()?; }
+		if let Some(ref s) = self.tesscontrol_shader { s.validate_specialization()?; }
+		if let Some(ref s) = self.tessevaluation_shader { s.validate_specialization()?; }
+		if let Some(ref s) = self.geometry_shader { s.validate_specialization()?; }
+		if let Some(ref s) = self.fragment_shader { s.validate_specialization()?; }
+		Ok(())
+	}
 	pub fn primitive_topology(mut self, pt: PrimitiveTopology) -> Self
 	{
 		self.primitive_topology = pt;
@@ -419,6 +882,47 @@ impl<'a> GraphicsPipelineBuilder<'a>
 		self.rasterizer_state.cull_side = Some(side);
 		self
 	}
+	pub fn rasterizer_front_face(mut self, face: FrontFace) -> Self
+	{
+		self.rasterizer_state.front_face = face;
+		self
+	}
+	/// Nonzero `clamp` requires the depthBiasClamp feature. Ignored if `DynamicState::DepthBias` is declared
+	pub fn rasterizer_depth_bias(mut self, constant_factor: f32, slope_factor: f32, clamp: f32) -> Self
+	{
+		self.rasterizer_state.depth_bias = Some(DepthBias { constant_factor, slope_factor, clamp });
+		self
+	}
+	/// Widths other than 1.0 require the wideLines feature. Ignored if `DynamicState::LineWidth` is declared
+	pub fn rasterizer_line_width(mut self, width: f32) -> Self
+	{
+		self.rasterizer_state.line_width = width;
+		self
+	}
+	/// Clamps depths instead of clipping primitives by the near/far planes(requires the depthClamp feature)
+	pub fn rasterizer_enable_depth_clamp(mut self) -> Self
+	{
+		self.rasterizer_state.depth_clamp = true;
+		self
+	}
+	/// Discards primitives just before rasterization(for transform feedback or side effects only)
+	pub fn rasterizer_enable_discard(mut self) -> Self
+	{
+		self.rasterizer_state.discard = true;
+		self
+	}
+	/// Rasterization sample count(one of VK_SAMPLE_COUNT_*_BIT), must match the samples of the subpass attachments
+	pub fn multisample(mut self, samples: SampleCount) -> Self
+	{
+		self.samples = samples;
+		self
+	}
+	/// Shades at least `min_fraction`(0.0 - 1.0) of the samples per fragment individually(requires the sampleRateShading feature)
+	pub fn sample_shading(mut self, min_fraction: f32) -> Self
+	{
+		self.min_sample_shading = Some(min_fraction);
+		self
+	}
 	pub fn enable_alpha_to_coverage(mut self) -> Self
 	{
 		self.use_alpha_to_coverage = true;
@@ -429,6 +933,47 @@ impl<'a> GraphicsPipelineBuilder<'a>
 		self.attachment_blend_states = Vec::from(state);
 		self
 	}
+	/// Applies the logical operation to all attachments instead of blending
+	pub fn logic_op(mut self, op: LogicOp) -> Self
+	{
+		self.logic_op = Some(op);
+		self
+	}
+	/// RGBA constants for Constant* blend factors(ignored if `DynamicState::BlendConstants` is declared)
+	pub fn blend_constants(mut self, constants: [f32; 4]) -> Self
+	{
+		self.blend_constants = constants;
+		self
+	}
+	pub fn depth_stencil_state(mut self, state: DepthStencilState) -> Self
+	{
+		self.depth_stencil_state = state;
+		self
+	}
+	/// Enables depth testing with the operator. Depth values are written if `write` is set
+	pub fn depth_test(mut self, compare_op: CompareOp, write: bool) -> Self
+	{
+		self.depth_stencil_state.depth_test = Some((compare_op, write));
+		self
+	}
+	/// Enables depth bounds testing(requires the depthBounds feature)
+	pub fn depth_bounds(mut self, min: f32, max: f32) -> Self
+	{
+		self.depth_stencil_state.depth_bounds = Some((min, max));
+		self
+	}
+	pub fn stencil_test(mut self, front: StencilFaceState, back: StencilFaceState) -> Self
+	{
+		self.depth_stencil_state.stencil_test = Some((front, back));
+		self
+	}
+	/// Declares states set by commands while recording(e.g. `set_viewport`).
+	/// Dynamic viewports/scissors need no `viewport_scissors`, but their count is taken from it if specified
+	pub fn dynamic_states(mut self, states: &[DynamicState]) -> Self
+	{
+		self.dynamic_states = Vec::from(states);
+		self
+	}
 }
 pub struct IntoNativeGraphicsPipelineCreateInfoStruct<'a>
 {
@@ -442,7 +987,10 @@ pub struct IntoNativeGraphicsPipelineCreateInfoStruct<'a>
 	viewport_state: VkPipelineViewportStateCreateInfo,
 	rasterization_state: VkPipelineRasterizationStateCreateInfo,
 	multisample_state: VkPipelineMultisampleStateCreateInfo,
-	color_blend_state: VkPipelineColorBlendStateCreateInfo
+	depth_stencil_state: VkPipelineDepthStencilStateCreateInfo,
+	color_blend_state: VkPipelineColorBlendStateCreateInfo,
+	dynamic_states: Vec<VkDynamicState>,
+	dynamic_state: VkPipelineDynamicStateCreateInfo
 }
 fn make_shaderstage_data<Module: Shader + NativeHandleProvider<NativeT = VkShaderModule> + Clone>(s: &PipelineShaderProgram<Module>)
 	-> IntoNativeShaderStageCreateInfoStruct
@@ -451,7 +999,7 @@ fn make_shaderstage_data<Module: Shader + NativeHandleProvider<NativeT = VkShade
 	{
 		let map_entries = s.1.iter().scan(0usize, |o, &(id, ref v)|
 		{
-			let size = v._sizeof();
+			let size = v.size();
 			let rval = VkSpecializationMapEntry { constantID: id as _, offset: *o as _, size };
 			*o += size;
 			Some(rval)
@@ -491,32 +1039,6 @@ fn make_native_shaderstage(s: &IntoNativeShaderStageCreateInfoStruct) -> VkPipel
 		.. Default::default()
 	}
 }
-fn make_attachment_blend_state(s: AttachmentBlendState) -> VkPipelineColorBlendAttachmentState
-{
-	const COLOR_COMPONENT_ALL: VkColorComponentFlags = VK_COLOR_COMPONENT_R_BIT | VK_COLOR_COMPONENT_G_BIT | VK_COLOR_COMPONENT_B_BIT | VK_COLOR_COMPONENT_A_BIT;
-
-	match s
-	{
-		AttachmentBlendState::Disabled => VkPipelineColorBlendAttachmentState
-		{
-			blendEnable: false as VkBool32, colorWriteMask: COLOR_COMPONENT_ALL, .. Default::default()
-		},
-		AttachmentBlendState::AlphaBlend => VkPipelineColorBlendAttachmentState
-		{
-			blendEnable: true as VkBool32,
-			srcColorBlendFactor: VK_BLEND_FACTOR_SRC_ALPHA, dstColorBlendFactor: VK_BLEND_FACTOR_ONE_MINUS_SRC_ALPHA,
-			srcAlphaBlendFactor: VK_BLEND_FACTOR_ONE, dstAlphaBlendFactor: VK_BLEND_FACTOR_ONE_MINUS_SRC_ALPHA,
-			colorBlendOp: VK_BLEND_OP_ADD, alphaBlendOp: VK_BLEND_OP_ADD, colorWriteMask: COLOR_COMPONENT_ALL
-		},
-		AttachmentBlendState::PremultipliedAlphaBlend => VkPipelineColorBlendAttachmentState
-		{
-			blendEnable: true as VkBool32,
-			srcColorBlendFactor: VK_BLEND_FACTOR_ONE, dstColorBlendFactor: VK_BLEND_FACTOR_ONE_MINUS_SRC_ALPHA,
-			srcAlphaBlendFactor: VK_BLEND_FACTOR_ONE, dstAlphaBlendFactor: VK_BLEND_FACTOR_ONE_MINUS_SRC_ALPHA,
-			colorBlendOp: VK_BLEND_OP_ADD, alphaBlendOp: VK_BLEND_OP_ADD, colorWriteMask: COLOR_COMPONENT_ALL
-		},
-	}
-}
 impl<'a> Into<IntoNativeGraphicsPipelineCreateInfoStruct<'a>> for &'a GraphicsPipelineBuilder<'a>
 {
 	fn into(self) -> IntoNativeGraphicsPipelineCreateInfoStruct<'a>
@@ -529,7 +1051,10 @@ impl<'a> Into<IntoNativeGraphicsPipelineCreateInfoStruct<'a>> for &'a GraphicsPi
 		let shader_stage = shader_stage_vec.iter().map(make_native_shaderstage).collect();
 		let (vports, scissors): (Vec<_>, Vec<_>) = self.vp_sc.iter().map(|&ViewportWithScissorRect(ref vp, ref sc)|
 			unsafe { (transmute::<_, VkViewport>(vp.clone()), transmute::<_, VkRect2D>(sc.clone())) }).unzip();
-		let attachment_blend_states = self.attachment_blend_states.iter().map(|&b| make_attachment_blend_state(b)).collect::<Vec<_>>();
+		let attachment_blend_states = self.attachment_blend_states.iter().map(|b| b.custom().into()).collect::<Vec<_>>();
+		let dynamic_states = self.dynamic_states.iter().map(|&d| d as VkDynamicState).collect::<Vec<_>>();
+		// at least one viewport/scissor is required even if they are dynamic
+		let dynamic_count = |d, n: usize| if n == 0 && self.dynamic_states.contains(&d) { 1 } else { n as u32 };
 		IntoNativeGraphicsPipelineCreateInfoStruct
 		{
 			into_shader_stage: shader_stage_vec,
@@ -541,28 +1066,41 @@ impl<'a> Into<IntoNativeGraphicsPipelineCreateInfoStruct<'a>> for &'a GraphicsPi
 			},
 			viewport_state: VkPipelineViewportStateCreateInfo
 			{
-				viewportCount: vports.len() as u32, pViewports: vports.as_ptr(),
-				scissorCount: scissors.len() as u32, pScissors: scissors.as_ptr(),
+				viewportCount: dynamic_count(DynamicState::Viewport, vports.len()), pViewports: vports.as_ptr(),
+				scissorCount: dynamic_count(DynamicState::Scissor, scissors.len()), pScissors: scissors.as_ptr(),
 				.. Default::default()
 			},
 			rasterization_state: VkPipelineRasterizationStateCreateInfo
 			{
-				rasterizerDiscardEnable: self.fragment_shader.is_none() as VkBool32,
+				depthClampEnable: self.rasterizer_state.depth_clamp as VkBool32,
+				rasterizerDiscardEnable: (self.rasterizer_state.discard || self.fragment_shader.is_none()) as VkBool32,
 				polygonMode: if self.rasterizer_state.wired_render { VK_POLYGON_MODE_LINE } else { VK_POLYGON_MODE_FILL },
 				cullMode: if let Some(side) = self.rasterizer_state.cull_side { side as _ } else { VK_CULL_MODE_NONE },
-				.. Default::default()
+				frontFace: self.rasterizer_state.front_face as _,
+				depthBiasEnable: self.rasterizer_state.depth_bias.is_some() as VkBool32,
+				depthBiasConstantFactor: self.rasterizer_state.depth_bias.map_or(0.0, |b| b.constant_factor),
+				depthBiasClamp: self.rasterizer_state.depth_bias.map_or(0.0, |b| b.clamp),
+				depthBiasSlopeFactor: self.rasterizer_state.depth_bias.map_or(0.0, |b| b.slope_factor),
+				lineWidth: self.rasterizer_state.line_width, .. Default::default()
 			},
 			multisample_state: VkPipelineMultisampleStateCreateInfo
 			{
-				rasterizationSamples: VK_SAMPLE_COUNT_1_BIT, alphaToCoverageEnable: self.use_alpha_to_coverage as VkBool32,
+				rasterizationSamples: self.samples, alphaToCoverageEnable: self.use_alpha_to_coverage as VkBool32,
+				sampleShadingEnable: self.min_sample_shading.is_some() as VkBool32, minSampleShading: self.min_sample_shading.unwrap_or(0.0),
 				.. Default::default()
 			},
+			depth_stencil_state: (&self.depth_stencil_state).into(),
 			color_blend_state: VkPipelineColorBlendStateCreateInfo
 			{
+				logicOpEnable: self.logic_op.is_some() as _, logicOp: self.logic_op.unwrap_or(LogicOp::Copy) as _,
 				attachmentCount: attachment_blend_states.len() as u32, pAttachments: attachment_blend_states.as_ptr(),
-				.. Default::default()
+				blendConstants: self.blend_constants, .. Default::default()
 			},
-			attachment_blend_states: attachment_blend_states,
+			dynamic_state: VkPipelineDynamicStateCreateInfo
+			{
+				dynamicStateCount: dynamic_states.len() as _, pDynamicStates: dynamic_states.as_ptr(), .. Default::default()
+			},
+			attachment_blend_states: attachment_blend_states, dynamic_states: dynamic_states,
 			viewports: vports, scissors: scissors, base: self
 		}
 	}
@@ -576,7 +1114,8 @@ impl<'a> Into<VkGraphicsPipelineCreateInfo> for &'a IntoNativeGraphicsPipelineCr
 			stageCount: self.shader_stage.len() as u32, pStages: self.shader_stage.as_ptr(),
 			pVertexInputState: &self.vertex_input_state, pInputAssemblyState: &self.input_assembly_state,
 			pViewportState: &self.viewport_state, pRasterizationState: &self.rasterization_state,
-			pMultisampleState: &self.multisample_state, pColorBlendState: &self.color_blend_state,
+			pMultisampleState: &self.multisample_state, pDepthStencilState: &self.depth_stencil_state, pColorBlendState: &self.color_blend_state,
+			pDynamicState: if self.dynamic_states.is_empty() { null() } else { &self.dynamic_state },
 			layout: self.base.layout.native(), renderPass: self.base.render_pass.native(), subpass: self.base.subpass_index,
 			.. Default::default()
 		}
@@ -587,12 +1126,14 @@ pub struct GraphicsPipeline(VkPipeline, Rc<Device>);
 pub struct GraphicsPipelines(Vec<GraphicsPipeline>);
 impl GraphicsPipelines
 {
+	/// Specialization constants of each stage are validated against the shader, and rasterizer states against the device before creation
 	pub fn new(engine: &GraphicsInterface, builders: &[&GraphicsPipelineBuilder]) -> EngineResult<Self>
 	{
+		for b in builders { b.validate_specializations()?; b.validate_rasterizer_state(engine)?; b.validate_blend_state(engine)?; }
 		let builders_n1 = builders.into_iter().map(|&x| x.into()).collect::<Vec<IntoNativeGraphicsPipelineCreateInfoStruct>>();
 		let builders_n = builders_n1.iter().map(|x| x.into()).collect::<Vec<_>>();
 		let mut pipelines = vec![unsafe { zeroed() }; builders.len()];
-		unsafe { vkCreateGraphicsPipelines(engine.device().native(), zeroed(), builders_n.len() as _, builders_n.as_ptr(), null(), pipelines.as_mut_ptr()) }
+		unsafe { vkCreateGraphicsPipelines(engine.device().native(), engine.pipeline_cache().native(), builders_n.len() as _, builders_n.as_ptr(), null(), pipelines.as_mut_ptr()) }
 			.make_result_with(|| GraphicsPipelines(pipelines.into_iter().map(|p| GraphicsPipeline(p, engine.device().clone())).collect()))
 	}
 }
@@ -613,6 +1154,82 @@ impl Drop for GraphicsPipeline
 	fn drop(&mut self) { unsafe { vkDestroyPipeline(self.1.native(), self.0, null()) }; }
 }
 
+/// Building parameters for Compute Pipeline
+pub struct ComputePipelineBuilder<'a>
+{
+	layout: &'a PipelineLayout, shader: PipelineShaderProgram<ComputeShader>
+}
+impl<'a> ComputePipelineBuilder<'a>
+{
+	pub fn new(layout: &'a PipelineLayout, shader: PipelineShaderProgram<ComputeShader>) -> Self
+	{
+		ComputePipelineBuilder { layout: layout, shader: shader }
+	}
+	pub fn inherit(base: &ComputePipelineBuilder<'a>) -> Self
+	{
+		ComputePipelineBuilder { layout: base.layout, shader: base.shader.clone() }
+	}
+
+	pub fn shader(mut self, cshader: PipelineShaderProgram<ComputeShader>) -> Self
+	{
+		self.shader = cshader; self
+	}
+}
+pub struct IntoNativeComputePipelineCreateInfoStruct<'a>
+{
+	base: &'a ComputePipelineBuilder<'a>, into_shader_stage: IntoNativeShaderStageCreateInfoStruct
+}
+impl<'a> Into<IntoNativeComputePipelineCreateInfoStruct<'a>> for &'a ComputePipelineBuilder<'a>
+{
+	fn into(self) -> IntoNativeComputePipelineCreateInfoStruct<'a>
+	{
+		IntoNativeComputePipelineCreateInfoStruct { into_shader_stage: make_shaderstage_data(&self.shader), base: self }
+	}
+}
+impl<'a> Into<VkComputePipelineCreateInfo> for &'a IntoNativeComputePipelineCreateInfoStruct<'a>
+{
+	fn into(self) -> VkComputePipelineCreateInfo
+	{
+		VkComputePipelineCreateInfo
+		{
+			stage: make_native_shaderstage(&self.into_shader_stage), layout: self.base.layout.native(), basePipelineIndex: -1,
+			.. Default::default()
+		}
+	}
+}
+
+pub struct ComputePipeline(VkPipeline, Rc<Device>);
+pub struct ComputePipelines(Vec<ComputePipeline>);
+impl ComputePipelines
+{
+	/// Specialization constants are validated against the shader before creation
+	pub fn new(engine: &GraphicsInterface, builders: &[&ComputePipelineBuilder]) -> EngineResult<Self>
+	{
+		for b in builders { b.shader.validate_specialization()?; }
+		let builders_n1 = builders.into_iter().map(|&x| x.into()).collect::<Vec<IntoNativeComputePipelineCreateInfoStruct>>();
+		let builders_n = builders_n1.iter().map(|x| x.into()).collect::<Vec<_>>();
+		let mut pipelines = vec![unsafe { zeroed() }; builders.len()];
+		unsafe { vkCreateComputePipelines(engine.device().native(), engine.pipeline_cache().native(), builders_n.len() as _, builders_n.as_ptr(), null(), pipelines.as_mut_ptr()) }
+			.make_result_with(|| ComputePipelines(pipelines.into_iter().map(|p| ComputePipeline(p, engine.device().clone())).collect()))
+	}
+}
+impl Deref for ComputePipelines
+{
+	type Target = Vec<ComputePipeline>;
+	fn deref(&self) -> &Self::Target { &self.0 }
+}
+impl DerefMut for ComputePipelines { fn deref_mut(&mut self) -> &mut Self::Target { &mut self.0 } }
+impl AsRef<VkPipeline> for ComputePipeline { fn as_ref(&self) -> &VkPipeline { &self.0 } }
+impl NativeHandleProvider for ComputePipeline
+{
+	type NativeT = VkPipeline;
+	fn native(&self) -> VkPipeline { self.0 }
+}
+impl Drop for ComputePipeline
+{
+	fn drop(&mut self) { unsafe { vkDestroyPipeline(self.1.native(), self.0, null()) }; }
+}
+
 /// Pipeline Stage
 #[repr(u32)] #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum PipelineStage
@@ -0,0 +1,502 @@
+//! Interlude: Texture Assets(Decoding PNG/TGA, Parsing DDS/KTX containers and Uploading into Image2D)
+
+use interlude_vk_defs::*;
+use {EngineResult, EngineError, GraphicsInterface, AssetProvider, AssetPath, Size2};
+use {Format, FormatType, PackedPixelOrder, CompressionAlgorithm};
+use {Image2D, ImageView2D, DeviceImages, ImageDescriptor2, ImagePreallocator, StagingBuffer, MipmapChain, ComponentMapping};
+use {ImageSubresourceRange, ImageSubresourceLayers, ImageAspect, ImageLayout, AccessFlag, PipelineStage, StagingResource};
+use {ImageMemoryBarrier, BufferMemoryBarrier, BufferImageCopyRegion, ImmediateGraphicsCommandSubmission};
+use {QueueSyncOperationCommandRecorder, PrimaryGraphicsCommandRecorder, PrimaryTransferCommandRecorder, ImmediateSubmissionCommands};
+use data::{format as into_vkformat, ASTCPixelPack};
+use resource::{packed_image_size, mip_level_count, MIPMAP_BLIT_FORMAT_FEATURES};
+use capture::decode_png;
+use std::ops::Deref;
+use std::path::Path;
+use std::io::prelude::*;
+use std::fs::File;
+
+/// Supported texture file formats
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFileFormat { PNG, TGA, DDS, KTX }
+impl TextureFileFormat
+{
+	/// Detects from the extension of the path
+	pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self>
+	{
+		match path.as_ref().extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase())
+		{
+			Some(ref e) if e == "png" => Some(TextureFileFormat::PNG),
+			Some(ref e) if e == "tga" => Some(TextureFileFormat::TGA),
+			Some(ref e) if e == "dds" => Some(TextureFileFormat::DDS),
+			Some(ref e) if e == "ktx" => Some(TextureFileFormat::KTX),
+			_ => None
+		}
+	}
+}
+
+/// Placement of a level of a layer in `TextureData::bytes`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextureSubresource { pub layer: u32, pub level: u32, pub size: Size2, pub offset: usize }
+/// Decoded texels or compressed blocks of all levels and layers, tightly packed
+pub struct TextureData
+{
+	pub format: Format, pub size: Size2, pub mip_levels: u32, pub array_layers: u32,
+	pub subresources: Vec<TextureSubresource>, pub bytes: Vec<u8>
+}
+impl TextureData
+{
+	/// PNG/TGA images are decoded into RGBA8, in sRGB if `srgb` is set.
+	/// `srgb` also selects the color space of legacy DDS files(other containers have their own)
+	pub fn load<P: AsRef<Path>>(path: P, srgb: bool) -> EngineResult<Self>
+	{
+		let file_format = TextureFileFormat::from_path(&path).ok_or(EngineError::GenericError("Unknown texture file format"))?;
+		let mut bytes = Vec::new();
+		File::open(path)?.read_to_end(&mut bytes)?;
+		Self::decode(&bytes, file_format, srgb)
+	}
+	pub fn decode(bytes: &[u8], file_format: TextureFileFormat, srgb: bool) -> EngineResult<Self>
+	{
+		match file_format
+		{
+			TextureFileFormat::PNG => decode_png(bytes).map(|(size, pixels)| Self::rgba8(size, pixels, srgb)),
+			TextureFileFormat::TGA => decode_tga(bytes).map(|(size, pixels)| Self::rgba8(size, pixels, srgb)),
+			TextureFileFormat::DDS => parse_dds(bytes, srgb),
+			TextureFileFormat::KTX => parse_ktx(bytes)
+		}
+	}
+	fn rgba8(size: Size2, pixels: Vec<u8>, srgb: bool) -> Self
+	{
+		TextureData
+		{
+			format: Format::Component(8, PackedPixelOrder::RGBA, if srgb { FormatType::SRGB } else { FormatType::UNormalized }),
+			subresources: vec![TextureSubresource { layer: 0, level: 0, size: size.clone(), offset: 0 }],
+			size, mip_levels: 1, array_layers: 1, bytes: pixels
+		}
+	}
+	/// Places tightly packed subresources in layer-major order(as DDS files do) and checks the data length
+	fn layer_major(format: Format, size: Size2, mip_levels: u32, array_layers: u32, bytes: &[u8]) -> EngineResult<Self>
+	{
+		let vkformat = into_vkformat(&format)?;
+		let (mut subresources, mut offset) = (Vec::new(), 0);
+		for layer in 0 .. array_layers
+		{
+			for level in 0 .. mip_levels
+			{
+				let lsize = Size2((size.0 >> level).max(1), (size.1 >> level).max(1));
+				let length = packed_image_size(vkformat, &lsize.clone().into()).ok_or(EngineError::InvalidFormatCombination)?;
+				subresources.push(TextureSubresource { layer, level, size: lsize, offset });
+				offset += length;
+			}
+		}
+		let bytes = bytes.get(.. offset).ok_or(EngineError::GenericError("Texture data is truncated"))?.to_owned();
+		Ok(TextureData { format, size, mip_levels, array_layers, subresources, bytes })
+	}
+}
+
+/// Sampled texture image with all levels and layers uploaded, in ShaderReadOnlyOptimal layout(viewed as a 2D array if it has multiple layers)
+pub struct Texture
+{
+	#[allow(dead_code)] images: DeviceImages, view: ImageView2D,
+	format: VkFormat, size: Size2, mip_levels: u32, array_layers: u32
+}
+impl Texture
+{
+	/// Uploads the data through a staging buffer. Fails if the format cannot be sampled on the device
+	/// (compressed formats need the matching `DeviceFeatures::enable_*_texture_compression`).
+	/// Mip levels are generated by blitting if `generate_mipmaps` is set, the data has only the base level and the format can be blitted
+	pub fn new(engine: &GraphicsInterface, data: &TextureData, generate_mipmaps: bool) -> EngineResult<Self>
+	{
+		let format = into_vkformat(&data.format)?;
+		let features = engine.format_properties(format).optimalTilingFeatures;
+		// transfer support is reported separately only with VK_KHR_maintenance1
+		let required = VK_FORMAT_FEATURE_SAMPLED_IMAGE_BIT
+			| if engine.device_extension_enabled("VK_KHR_maintenance1") { VK_FORMAT_FEATURE_TRANSFER_DST_BIT_KHR } else { 0 };
+		if (features & required) != required
+		{
+			error!(target: "Interlude::Texture", "The format {:?} is not supported for sampled images(features: {:#x})", data.format, features);
+			return Err(EngineError::GenericError("The texture format is not supported by the device"));
+		}
+		let blittable = (features & MIPMAP_BLIT_FORMAT_FEATURES) == MIPMAP_BLIT_FORMAT_FEATURES;
+		if generate_mipmaps && data.mip_levels == 1 && !blittable
+		{
+			warn!(target: "Interlude::Texture", "Mipmaps are not generated since the format {:?} does not support blitting", data.format);
+		}
+		let generated = generate_mipmaps && data.mip_levels == 1 && blittable;
+		let mip_levels = if generated { mip_level_count(&data.size) } else { data.mip_levels };
+		let usage = VK_IMAGE_USAGE_SAMPLED_BIT | VK_IMAGE_USAGE_TRANSFER_DST_BIT | if generated { VK_IMAGE_USAGE_TRANSFER_SRC_BIT } else { 0 };
+		let desc = ImageDescriptor2::new(format, data.size.clone(), usage).mip_levels(mip_levels).array_layers(data.array_layers).device_local();
+		let (images, _) = ImagePreallocator::new(engine, Vec::new(), vec![&desc], Vec::new()).instantiate()?;
+
+		{
+			let image = &images.dim2()[0];
+			let chain = if generated { Some(MipmapChain::new(engine, image, format, mip_levels, data.array_layers)?) } else { None };
+			let staging = StagingBuffer::new(engine, data.bytes.len() as _, VK_BUFFER_USAGE_TRANSFER_SRC_BIT)?;
+			staging.map()?.range_mut::<u8>(0, data.bytes.len()).copy_from_slice(&data.bytes);
+			let regions = data.subresources.iter().map(|s| BufferImageCopyRegion::packed(s.offset, ImageSubresourceLayers
+			{
+				aspect: ImageAspect::Color.into(), array_range: s.layer .. s.layer + 1, mip_level: s.level
+			}, &s.size.clone().into())).collect::<Vec<_>>();
+			let all_range = ImageSubresourceRange { aspect: ImageAspect::Color.into(), mip_level_range: 0 .. mip_levels, array_range: 0 .. data.array_layers };
+
+			let r = ImmediateGraphicsCommandSubmission::begin(engine)?
+				.pipeline_barrier(PipelineStage::Host, PipelineStage::Transfer, false, &[], &[BufferMemoryBarrier
+				{
+					buffer: &staging, range: 0 .. data.bytes.len(), src_access: AccessFlag::HostWrite.into(), dst_access: AccessFlag::TransferRead.into(),
+					.. Default::default()
+				}], &[ImageMemoryBarrier::initialize_undef(image, all_range.clone(), AccessFlag::TransferWrite.into(), ImageLayout::TransferDstOptimal)])
+				.copy_buffer_to_image(&staging, image, &regions);
+			match chain
+			{
+				Some(ref c) => r.generate_mipmaps(c, ImageLayout::TransferDstOptimal, ImageLayout::ShaderReadOnlyOptimal),
+				None => r.pipeline_barrier(PipelineStage::Transfer, PipelineStage::AllGraphics, false, &[], &[], &[ImageMemoryBarrier
+				{
+					src_access: AccessFlag::TransferWrite.into(), dst_access: AccessFlag::ShaderRead.into(),
+					src_layout: ImageLayout::TransferDstOptimal, dst_layout: ImageLayout::ShaderReadOnlyOptimal,
+					image, subresource_range: all_range, .. Default::default()
+				}])
+			}.submit_opt(&[], None, None)?;
+		}
+
+		let view_type = if data.array_layers > 1 { VK_IMAGE_VIEW_TYPE_2D_ARRAY } else { VK_IMAGE_VIEW_TYPE_2D };
+		let view = ImageView2D::make_typed(&images.dim2()[0], format, ComponentMapping::straight(), &ImageSubresourceRange
+		{
+			aspect: ImageAspect::Color.into(), mip_level_range: 0 .. mip_levels, array_range: 0 .. data.array_layers
+		}, view_type)?;
+		info!(target: "Interlude::Texture", "Uploaded texture: {:?}, {} levels, {} layers", data.size, mip_levels, data.array_layers);
+		Ok(Texture { images, view, format, size: data.size.clone(), mip_levels, array_layers: data.array_layers })
+	}
+	/// Loads the texture asset with the extension(png, tga, dds or ktx)
+	pub fn from_asset<Engine: AssetProvider + Deref<Target = GraphicsInterface>, P: AssetPath>(engine: &Engine, path: P, extension: &str,
+		srgb: bool, generate_mipmaps: bool) -> EngineResult<Self>
+	{
+		let path = engine.parse_asset(path, extension);
+		info!(target: "Interlude::Texture", "Loading Texture from {:?}...", path);
+		TextureData::load(&path, srgb).and_then(|d| Self::new(engine, &d, generate_mipmaps))
+	}
+
+	pub fn image(&self) -> &Image2D { &self.view }
+	pub fn view(&self) -> &ImageView2D { &self.view }
+	pub fn format(&self) -> VkFormat { self.format }
+	pub fn size(&self) -> &Size2 { &self.size }
+	pub fn mip_levels(&self) -> u32 { self.mip_levels }
+	pub fn array_layers(&self) -> u32 { self.array_layers }
+}
+
+fn u16le(b: &[u8], offs: usize) -> u16 { b[offs] as u16 | (b[offs + 1] as u16) << 8 }
+fn u32le(b: &[u8], offs: usize) -> u32 { u16le(b, offs) as u32 | (u16le(b, offs + 2) as u32) << 16 }
+
+/// Truecolor and grayscale TGA, optionally RLE compressed
+fn decode_tga(b: &[u8]) -> EngineResult<(Size2, Vec<u8>)>
+{
+	const TRUNCATED: EngineError = EngineError::GenericError("TGA data is truncated");
+	if b.len() < 18 { return Err(TRUNCATED); }
+	let (id_length, colormap_type, image_type) = (b[0] as usize, b[1], b[2]);
+	let (width, height, bits, descriptor) = (u16le(b, 12) as usize, u16le(b, 14) as usize, b[16], b[17]);
+	if colormap_type != 0 { return Err(EngineError::GenericError("Color-mapped TGA is not supported")); }
+	let grayscale = match (image_type, bits)
+	{
+		(2, 24) | (2, 32) | (10, 24) | (10, 32) => false,
+		(3, 8) | (3, 16) | (11, 8) | (11, 16) => true,
+		_ => return Err(EngineError::GenericError("Unsupported TGA image type"))
+	};
+	let (texel_bytes, count) = (bits as usize / 8, width * height);
+	let mut p = 18 + id_length;
+	let texels = if image_type >= 9
+	{
+		let mut t = Vec::with_capacity(count * texel_bytes);
+		while t.len() < count * texel_bytes
+		{
+			let header = *b.get(p).ok_or(TRUNCATED)?;
+			let n = (header & 0x7f) as usize + 1;
+			p += 1;
+			if (header & 0x80) != 0
+			{
+				let texel = b.get(p .. p + texel_bytes).ok_or(TRUNCATED)?;
+				for _ in 0 .. n { t.extend_from_slice(texel); }
+				p += texel_bytes;
+			}
+			else
+			{
+				t.extend_from_slice(b.get(p .. p + n * texel_bytes).ok_or(TRUNCATED)?);
+				p += n * texel_bytes;
+			}
+		}
+		t.truncate(count * texel_bytes);
+		t
+	}
+	else { b.get(p .. p + count * texel_bytes).ok_or(TRUNCATED)?.to_owned() };
+
+	let mut pixels = Vec::with_capacity(count * 4);
+	// rows are stored bottom-up unless the top-left origin bit is set
+	let top_down = (descriptor & 0x20) != 0;
+	for y in 0 .. height
+	{
+		let row = if top_down { y } else { height - 1 - y };
+		for t in texels[row * width * texel_bytes .. (row + 1) * width * texel_bytes].chunks(texel_bytes)
+		{
+			match (grayscale, texel_bytes)
+			{
+				(true, 1) => pixels.extend_from_slice(&[t[0], t[0], t[0], 255]),
+				(true, _) => pixels.extend_from_slice(&[t[0], t[0], t[0], t[1]]),
+				(false, 3) => pixels.extend_from_slice(&[t[2], t[1], t[0], 255]),
+				(false, _) => pixels.extend_from_slice(&[t[2], t[1], t[0], t[3]])
+			}
+		}
+	}
+	Ok((Size2(width as _, height as _), pixels))
+}
+
+/// DDS with legacy FourCC/RGBA pixel formats or DX10 extended header. Cube maps are loaded as 6 layers per cube
+fn parse_dds(b: &[u8], srgb: bool) -> EngineResult<TextureData>
+{
+	let invalid = EngineError::GenericError("Invalid DDS header");
+	if b.len() < 128 || &b[0 .. 4] != b"DDS " || u32le(b, 4) != 124 { return Err(invalid); }
+	let (height, width, mip_count) = (u32le(b, 12), u32le(b, 16), u32le(b, 28).max(1));
+	let (pf_flags, fourcc, rgb_bits, r_mask) = (u32le(b, 80), &b[84 .. 88], u32le(b, 88), u32le(b, 92));
+	let cubemap = (u32le(b, 112) & 0x200) != 0;
+	let lin_or_srgb = |l, s| Format::Compressed(if srgb { s } else { l });
+
+	let (format, layers, data_offset) = if (pf_flags & 0x04) != 0 && fourcc == b"DX10"
+	{
+		if b.len() < 148 { return Err(invalid); }
+		let (dxgi_format, misc_flags, array_size) = (u32le(b, 128), u32le(b, 136), u32le(b, 140).max(1));
+		let format = match dxgi_format
+		{
+			2 => Format::Component(32, PackedPixelOrder::RGBA, FormatType::Float),
+			10 => Format::Component(16, PackedPixelOrder::RGBA, FormatType::Float),
+			28 => Format::Component(8, PackedPixelOrder::RGBA, FormatType::UNormalized),
+			29 => Format::Component(8, PackedPixelOrder::RGBA, FormatType::SRGB),
+			87 => Format::Component(8, PackedPixelOrder::BGRA, FormatType::UNormalized),
+			91 => Format::Component(8, PackedPixelOrder::BGRA, FormatType::SRGB),
+			71 => Format::Compressed(CompressionAlgorithm::BC1AlphaLinear),
+			72 => Format::Compressed(CompressionAlgorithm::BC1AlphaNonLinear),
+			74 => Format::Compressed(CompressionAlgorithm::BC2Linear),
+			75 => Format::Compressed(CompressionAlgorithm::BC2NonLinear),
+			77 => Format::Compressed(CompressionAlgorithm::BC3Linear),
+			78 => Format::Compressed(CompressionAlgorithm::BC3NonLinear),
+			80 => Format::Compressed(CompressionAlgorithm::BC4Unsigned),
+			81 => Format::Compressed(CompressionAlgorithm::BC4Signed),
+			83 => Format::Compressed(CompressionAlgorithm::BC5Unsigned),
+			84 => Format::Compressed(CompressionAlgorithm::BC5Signed),
+			95 => Format::Compressed(CompressionAlgorithm::BC6HUnsigned),
+			96 => Format::Compressed(CompressionAlgorithm::BC6HSigned),
+			98 => Format::Compressed(CompressionAlgorithm::BC7Linear),
+			99 => Format::Compressed(CompressionAlgorithm::BC7NonLinear),
+			_ => return Err(EngineError::GenericError("Unsupported DXGI format in DDS"))
+		};
+		(format, array_size * if (misc_flags & 0x04) != 0 { 6 } else { 1 }, 148)
+	}
+	else if (pf_flags & 0x04) != 0
+	{
+		let format = match fourcc
+		{
+			b"DXT1" => lin_or_srgb(CompressionAlgorithm::BC1AlphaLinear, CompressionAlgorithm::BC1AlphaNonLinear),
+			b"DXT2" | b"DXT3" => lin_or_srgb(CompressionAlgorithm::BC2Linear, CompressionAlgorithm::BC2NonLinear),
+			b"DXT4" | b"DXT5" => lin_or_srgb(CompressionAlgorithm::BC3Linear, CompressionAlgorithm::BC3NonLinear),
+			b"ATI1" | b"BC4U" => Format::Compressed(CompressionAlgorithm::BC4Unsigned),
+			b"BC4S" => Format::Compressed(CompressionAlgorithm::BC4Signed),
+			b"ATI2" | b"BC5U" => Format::Compressed(CompressionAlgorithm::BC5Unsigned),
+			b"BC5S" => Format::Compressed(CompressionAlgorithm::BC5Signed),
+			_ => return Err(EngineError::GenericError("Unsupported FourCC in DDS"))
+		};
+		(format, if cubemap { 6 } else { 1 }, 128)
+	}
+	else if (pf_flags & 0x40) != 0 && rgb_bits == 32
+	{
+		let order = if r_mask == 0x00ff_0000 { PackedPixelOrder::BGRA } else { PackedPixelOrder::RGBA };
+		(Format::Component(8, order, if srgb { FormatType::SRGB } else { FormatType::UNormalized }), if cubemap { 6 } else { 1 }, 128)
+	}
+	else { return Err(EngineError::GenericError("Unsupported pixel format in DDS")); };
+
+	TextureData::layer_major(format, Size2(width, height), mip_count, layers, &b[data_offset ..])
+}
+
+const KTX_IDENTIFIER: [u8; 12] = [0xab, 0x4b, 0x54, 0x58, 0x20, 0x31, 0x31, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a];
+/// Format from glInternalFormat of KTX
+fn ktx_format(internal_format: u32) -> Option<Format>
+{
+	const ASTC_PACKS: [ASTCPixelPack; 14] = [
+		ASTCPixelPack::_4x4, ASTCPixelPack::_5x4, ASTCPixelPack::_5x5, ASTCPixelPack::_6x5, ASTCPixelPack::_6x6,
+		ASTCPixelPack::_8x5, ASTCPixelPack::_8x6, ASTCPixelPack::_8x8, ASTCPixelPack::_10x5, ASTCPixelPack::_10x6,
+		ASTCPixelPack::_10x8, ASTCPixelPack::_10x10, ASTCPixelPack::_12x10, ASTCPixelPack::_12x12
+	];
+	let c = |a| Some(Format::Compressed(a));
+	match internal_format
+	{
+		0x8058 => Some(Format::Component(8, PackedPixelOrder::RGBA, FormatType::UNormalized)),
+		0x8c43 => Some(Format::Component(8, PackedPixelOrder::RGBA, FormatType::SRGB)),
+		0x83f0 => c(CompressionAlgorithm::BC1Linear), 0x83f1 => c(CompressionAlgorithm::BC1AlphaLinear),
+		0x83f2 => c(CompressionAlgorithm::BC2Linear), 0x83f3 => c(CompressionAlgorithm::BC3Linear),
+		0x8c4c => c(CompressionAlgorithm::BC1NonLinear), 0x8c4d => c(CompressionAlgorithm::BC1AlphaNonLinear),
+		0x8c4e => c(CompressionAlgorithm::BC2NonLinear), 0x8c4f => c(CompressionAlgorithm::BC3NonLinear),
+		0x8dbb => c(CompressionAlgorithm::BC4Unsigned), 0x8dbc => c(CompressionAlgorithm::BC4Signed),
+		0x8dbd => c(CompressionAlgorithm::BC5Unsigned), 0x8dbe => c(CompressionAlgorithm::BC5Signed),
+		0x8e8c => c(CompressionAlgorithm::BC7Linear), 0x8e8d => c(CompressionAlgorithm::BC7NonLinear),
+		0x8e8e => c(CompressionAlgorithm::BC6HSigned), 0x8e8f => c(CompressionAlgorithm::BC6HUnsigned),
+		// ETC1 is a subset of ETC2
+		0x8d64 | 0x9274 => c(CompressionAlgorithm::ETC2Linear(0)), 0x9275 => c(CompressionAlgorithm::ETC2NonLinear(0)),
+		0x9276 => c(CompressionAlgorithm::ETC2Linear(1)), 0x9277 => c(CompressionAlgorithm::ETC2NonLinear(1)),
+		0x9278 => c(CompressionAlgorithm::ETC2Linear(8)), 0x9279 => c(CompressionAlgorithm::ETC2NonLinear(8)),
+		0x9270 => c(CompressionAlgorithm::EACUnsigned), 0x9271 => c(CompressionAlgorithm::EACSigned),
+		0x9272 => c(CompressionAlgorithm::EACGUnsigned), 0x9273 => c(CompressionAlgorithm::EACGSigned),
+		0x93b0..=0x93bd => c(CompressionAlgorithm::ASTCLinear(ASTC_PACKS[(internal_format - 0x93b0) as usize])),
+		0x93d0..=0x93dd => c(CompressionAlgorithm::ASTCNonLinear(ASTC_PACKS[(internal_format - 0x93d0) as usize])),
+		_ => None
+	}
+}
+/// KTX 1.1 with 2D images, arrays and cube maps(faces are loaded as layers)
+fn parse_ktx(b: &[u8]) -> EngineResult<TextureData>
+{
+	const TRUNCATED: EngineError = EngineError::GenericError("KTX data is truncated");
+	if b.len() < 64 || b[0 .. 12] != KTX_IDENTIFIER { return Err(EngineError::GenericError("Invalid KTX header")); }
+	let swap = u32le(b, 12) != 0x0403_0201;
+	let field = |offs| { let v = u32le(b, offs); if swap { v.swap_bytes() } else { v } };
+	let format = ktx_format(field(28)).ok_or(EngineError::GenericError("Unsupported glInternalFormat in KTX"))?;
+	let (width, height, depth) = (field(36), field(40).max(1), field(44));
+	if depth > 1 { return Err(EngineError::GenericError("3D textures in KTX are not supported")); }
+	let (elements, faces, mip_levels) = (field(48), field(52).max(1), field(56).max(1));
+	let array_layers = elements.max(1) * faces;
+	let vkformat = into_vkformat(&format)?;
+
+	// KTX stores layers and faces for each level
+	let (mut subresources, mut data) = (Vec::new(), Vec::new());
+	let mut p = 64 + field(60) as usize;
+	for level in 0 .. mip_levels
+	{
+		let image_size = if p + 4 <= b.len() { field(p) as usize } else { return Err(TRUNCATED); };
+		p += 4;
+		let size = Size2((width >> level).max(1), (height >> level).max(1));
+		let length = packed_image_size(vkformat, &size.clone().into()).ok_or(EngineError::InvalidFormatCombination)?;
+		for layer in 0 .. array_layers
+		{
+			subresources.push(TextureSubresource { layer, level, size: size.clone(), offset: data.len() });
+			data.extend_from_slice(b.get(p .. p + length).ok_or(TRUNCATED)?);
+			// faces of non-array cube maps are padded to 4 bytes
+			p += if elements == 0 && faces == 6 { (length + 3) & !3 } else { length };
+		}
+		if !(elements == 0 && faces == 6) && image_size != length * array_layers as usize
+		{
+			return Err(EngineError::GenericError("Unexpected imageSize in KTX"));
+		}
+		p = (p + 3) & !3;
+	}
+	Ok(TextureData { format, size: Size2(width, height), mip_levels, array_layers, subresources, bytes: data })
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn put_u32(b: &mut [u8], offs: usize, v: u32) { for i in 0 .. 4 { b[offs + i] = (v >> (i * 8)) as u8; } }
+	fn tga_header(image_type: u8, width: u16, height: u16, bits: u8, descriptor: u8) -> Vec<u8>
+	{
+		let mut h = vec![0u8; 18];
+		h[2] = image_type;
+		h[12 .. 14].copy_from_slice(&[width as u8, (width >> 8) as u8]);
+		h[14 .. 16].copy_from_slice(&[height as u8, (height >> 8) as u8]);
+		h[16] = bits; h[17] = descriptor;
+		h
+	}
+
+	#[test]
+	fn tga_rle_bottom_up()
+	{
+		// 2x2, 24bit BGR: a run of 3 red texels and a raw blue texel, stored from the bottom row
+		let mut b = tga_header(10, 2, 2, 24, 0);
+		b.extend_from_slice(&[0x82, 0, 0, 255, 0x00, 255, 0, 0]);
+		let (size, pixels) = decode_tga(&b).unwrap();
+		assert_eq!(size, Size2(2, 2));
+		assert_eq!(pixels, vec![255, 0, 0, 255, 0, 0, 255, 255, 255, 0, 0, 255, 255, 0, 0, 255]);
+		// the last packet is missing
+		assert!(decode_tga(&b[.. b.len() - 4]).is_err());
+	}
+
+	#[test]
+	fn tga_grayscale_top_down()
+	{
+		let mut b = tga_header(3, 2, 1, 8, 0x20);
+		b.extend_from_slice(&[10, 20]);
+		assert_eq!(decode_tga(&b).unwrap().1, vec![10, 10, 10, 255, 20, 20, 20, 255]);
+		assert!(decode_tga(&tga_header(1, 2, 1, 8, 0)).is_err());
+	}
+
+	fn dds_header(width: u32, height: u32, mip_count: u32, fourcc: &[u8; 4]) -> Vec<u8>
+	{
+		let mut h = vec![0u8; 128];
+		h[0 .. 4].copy_from_slice(b"DDS ");
+		put_u32(&mut h, 4, 124);
+		put_u32(&mut h, 12, height); put_u32(&mut h, 16, width); put_u32(&mut h, 28, mip_count);
+		put_u32(&mut h, 80, 0x04);
+		h[84 .. 88].copy_from_slice(fourcc);
+		h
+	}
+
+	#[test]
+	fn dds_dx10_array()
+	{
+		let mut b = dds_header(2, 1, 1, b"DX10");
+		let mut dx10 = vec![0u8; 20];
+		// DXGI_FORMAT_R8G8B8A8_UNORM, TEXTURE2D, 2 elements
+		put_u32(&mut dx10, 0, 28); put_u32(&mut dx10, 4, 3); put_u32(&mut dx10, 12, 2);
+		b.extend(dx10);
+		b.extend((0 .. 16).collect::<Vec<u8>>());
+		let t = parse_dds(&b, false).unwrap();
+		assert_eq!(t.format, Format::Component(8, PackedPixelOrder::RGBA, FormatType::UNormalized));
+		assert_eq!((t.size, t.mip_levels, t.array_layers), (Size2(2, 1), 1, 2));
+		assert_eq!(t.subresources, vec![
+			TextureSubresource { layer: 0, level: 0, size: Size2(2, 1), offset: 0 },
+			TextureSubresource { layer: 1, level: 0, size: Size2(2, 1), offset: 8 }
+		]);
+		assert_eq!(t.bytes, (0 .. 16).collect::<Vec<u8>>());
+		assert!(parse_dds(&b[.. b.len() - 1], false).is_err());
+	}
+
+	#[test]
+	fn dds_legacy_fourcc_mipmaps()
+	{
+		// 8x8 BC1 with 2 levels: 4 blocks + 1 block
+		let mut b = dds_header(8, 8, 2, b"DXT1");
+		b.extend(vec![0u8; 40]);
+		let t = parse_dds(&b, true).unwrap();
+		assert_eq!(t.format, Format::Compressed(CompressionAlgorithm::BC1AlphaNonLinear));
+		assert_eq!(t.subresources[1], TextureSubresource { layer: 0, level: 1, size: Size2(4, 4), offset: 32 });
+		assert_eq!(t.bytes.len(), 40);
+		assert!(parse_dds(&dds_header(8, 8, 1, b"ABCD"), false).is_err());
+	}
+
+	/// RGBA8 KTX with the fields written in the byte order of `swap`
+	fn ktx(width: u32, height: u32, mip_levels: u32, levels: &[(u32, Vec<u8>)], swap: bool) -> Vec<u8>
+	{
+		let word = |v: u32| { let v = if swap { v.swap_bytes() } else { v }; vec![v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8] };
+		let mut b = KTX_IDENTIFIER.to_vec();
+		for &v in &[0x0403_0201, 0x1401, 1, 0x1908, 0x8058, 0x1908, width, height, 0, 0, 1, mip_levels, 0] { b.extend(word(v)); }
+		for &(image_size, ref data) in levels { b.extend(word(image_size)); b.extend_from_slice(data); }
+		b
+	}
+
+	#[test]
+	fn ktx_mipmaps_in_both_byte_orders()
+	{
+		for &swap in &[false, true]
+		{
+			let b = ktx(2, 2, 2, &[(16, (0 .. 16).collect()), (4, vec![16, 17, 18, 19])], swap);
+			let t = parse_ktx(&b).unwrap();
+			assert_eq!(t.format, Format::Component(8, PackedPixelOrder::RGBA, FormatType::UNormalized));
+			assert_eq!((t.size, t.mip_levels, t.array_layers), (Size2(2, 2), 2, 1));
+			assert_eq!(t.subresources, vec![
+				TextureSubresource { layer: 0, level: 0, size: Size2(2, 2), offset: 0 },
+				TextureSubresource { layer: 0, level: 1, size: Size2(1, 1), offset: 16 }
+			]);
+			assert_eq!(t.bytes, (0 .. 20).collect::<Vec<u8>>());
+		}
+	}
+
+	#[test]
+	fn ktx_invalid_image_size()
+	{
+		assert!(parse_ktx(&ktx(2, 2, 1, &[(12, (0 .. 16).collect())], false)).is_err());
+		assert!(parse_ktx(&ktx(2, 2, 2, &[(16, (0 .. 16).collect())], false)).is_err());
+		assert!(parse_ktx(&b"not a ktx file"[..]).is_err());
+	}
+}